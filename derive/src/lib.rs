@@ -0,0 +1,169 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Derive macro making a user struct intrusive.
+//!
+//! This crate is re-exported by `intrusive_containers` when its `derive`
+//! feature is enabled and should not be used directly.
+
+#![crate_name = "intrusive_containers_derive"]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Field, Index, Member};
+
+/// Implements `Linkable` and `Node` for every field marked `#[list_link]`
+///
+/// Each link field must have its own link type, declared with
+/// `declare_list_link!`. The value stored in the lists is the single field
+/// marked `#[list_value]`, so a `LinkedList` of `Page` holding a `Frame` and
+/// linked through `PageLink` is a `LinkedList<Frame, Box<Page>, Page, PageLink>`.
+/// The value is kept apart from the links so that iterating mutably over a
+/// list cannot overwrite the links of its elements.
+///
+/// The attributes are spelled `list_link` and `list_value` because `link` is
+/// already a built-in attribute.
+///
+/// ```
+/// #[macro_use] extern crate intrusive_containers;
+/// use intrusive_containers::{Intrusive, LinkedList};
+///
+/// declare_list_link!(LruLink);
+///
+/// #[derive(Intrusive)]
+/// struct Page {
+///     #[list_value]
+///     addr: u64,
+///     #[list_link]
+///     lru: LruLink,
+/// }
+///
+/// # fn main() {
+/// let mut lru = LinkedList::new();
+/// lru.push_back(Box::new(Page { addr: 0x1000, lru: Default::default() }));
+/// assert_eq!(lru.front(), Some(&0x1000));
+/// # let _: Option<Box<Page>> = lru.pop_front();
+/// # }
+/// ```
+#[proc_macro_derive(Intrusive, attributes(list_link, list_value))]
+pub fn derive_intrusive(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics,
+                                      "Intrusive cannot be derived for \
+                                       generic structs"));
+    }
+    let fields = match input.data {
+        Data::Struct(ref data) => &data.fields,
+        _ => return Err(Error::new_spanned(&input.ident,
+                                           "Intrusive can only be derived \
+                                            for structs")),
+    };
+
+    let elt = &input.ident;
+    let member = |i: usize, field: &Field| match field.ident {
+        Some(ref ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index::from(i)),
+    };
+    let marked = |field: &Field, name: &str| {
+        field.attrs.iter().any(|attr| attr.path().is_ident(name))
+    };
+
+    let mut values = fields.iter().enumerate()
+        .filter(|(_, field)| marked(field, "list_value"));
+    let (value, val) = match (values.next(), values.next()) {
+        (Some((i, field)), None) => (member(i, field), &field.ty),
+        (_, Some((_, field))) =>
+            return Err(Error::new_spanned(field, "Intrusive allows a single \
+                                                  #[list_value] field")),
+        (None, None) =>
+            return Err(Error::new_spanned(&input.ident,
+                                          "Intrusive requires a \
+                                           #[list_value] field")),
+    };
+
+    let mut impls = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        if !marked(field, "list_link") {
+            continue;
+        }
+        let member = member(i, field);
+        let link = &field.ty;
+        impls.push(quote! {
+            unsafe impl ::intrusive_containers::linked_list::Linkable for #link {
+                type Container = #elt;
+
+                #[inline]
+                fn get_links(&self)
+                             -> &::intrusive_containers::linked_list::Links<#link> {
+                    &self.0
+                }
+
+                #[inline]
+                fn get_links_mut(&mut self)
+                                 -> &mut ::intrusive_containers::linked_list::Links<#link> {
+                    &mut self.0
+                }
+
+                #[inline]
+                fn offset() -> usize {
+                    ::intrusive_containers::linked_list::offset_of!(#elt, #member)
+                }
+            }
+
+            unsafe impl ::intrusive_containers::linked_list::Node<#val, #link> for #elt {
+                #[inline]
+                fn get_val(&self) -> &#val {
+                    &self.#value
+                }
+
+                #[inline]
+                fn get_val_mut(&mut self) -> &mut #val {
+                    &mut self.#value
+                }
+
+                #[inline]
+                fn get_links(&self) -> &#link {
+                    &self.#member
+                }
+
+                #[inline]
+                fn get_links_mut(&mut self) -> &mut #link {
+                    &mut self.#member
+                }
+            }
+        });
+    }
+
+    if impls.is_empty() {
+        return Err(Error::new_spanned(&input.ident,
+                                      "Intrusive requires at least one \
+                                       #[list_link] field"));
+    }
+    Ok(quote! { #(#impls)* })
+}
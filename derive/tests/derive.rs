@@ -0,0 +1,103 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+#[macro_use] extern crate intrusive_containers;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use intrusive_containers::{Intrusive, LinkedList};
+use intrusive_containers::linked_list::Linkable;
+
+declare_list_link!(LruLink);
+declare_list_link!(DirtyLink);
+
+struct Frame {
+    addr: u64,
+    flags: Cell<u8>,
+}
+
+#[derive(Intrusive)]
+struct Page {
+    #[list_link]
+    lru: LruLink,
+    #[list_value]
+    frame: Frame,
+    #[list_link]
+    dirty: DirtyLink,
+}
+
+impl Page {
+    fn new(addr: u64) -> Page {
+        Page { lru: Default::default(),
+               frame: Frame { addr, flags: Cell::new(0) },
+               dirty: Default::default() }
+    }
+}
+
+#[derive(Intrusive)]
+struct Pair(#[list_value] u32, #[list_link] LruPairLink);
+
+declare_list_link!(LruPairLink);
+
+#[test]
+fn test_offsets() {
+    let page = Page::new(0);
+    let base = &page as *const Page as usize;
+    assert_eq!(LruLink::offset(), &page.lru as *const _ as usize - base);
+    assert_eq!(DirtyLink::offset(), &page.dirty as *const _ as usize - base);
+
+    let pair = Pair(0, Default::default());
+    assert_eq!(pair.0, 0);
+    let base = &pair as *const Pair as usize;
+    assert_eq!(LruPairLink::offset(), &pair.1 as *const _ as usize - base);
+}
+
+#[test]
+fn test_two_lists() {
+    let pages: Vec<_> = (0..4).map(|i| Rc::new(Page::new(i * 0x1000))).collect();
+    let mut lru: LinkedList<Frame, Rc<Page>, Page, LruLink> = LinkedList::new();
+    let mut dirty: LinkedList<Frame, Rc<Page>, Page, DirtyLink> =
+        LinkedList::new();
+    for p in pages.iter() {
+        lru.push_back(p.clone());
+    }
+    for p in pages.iter().rev() {
+        dirty.push_back(p.clone());
+    }
+    for frame in lru.iter() {
+        frame.flags.set(1);
+    }
+
+    assert_eq!(lru.iter().map(|f| f.addr).collect::<Vec<_>>(),
+               [0, 0x1000, 0x2000, 0x3000]);
+    assert_eq!(dirty.iter().map(|f| f.addr).collect::<Vec<_>>(),
+               [0x3000, 0x2000, 0x1000, 0]);
+    assert!(dirty.iter().all(|f| f.flags.get() == 1));
+
+    dirty.clear();
+    lru.clear();
+    assert!(pages.iter().all(|p| Rc::strong_count(p) == 1));
+}
+
+#[test]
+fn test_iter_mut() {
+    let mut pairs: LinkedList<u32, Box<Pair>, Pair, LruPairLink> =
+        (0..4).map(|i| Box::new(Pair(i, Default::default()))).collect();
+    for val in pairs.iter_mut() {
+        *val *= 10;
+    }
+    assert_eq!(pairs.iter().cloned().collect::<Vec<_>>(), [0, 10, 20, 30]);
+    pairs.clear();
+}
@@ -0,0 +1,633 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive concurrent LIFO stack.
+//!
+//! The `AtomicStack` is a Treiber stack, meant for object pools shared between
+//! threads: any number of threads may push and pop elements at once, and
+//! `pop_all` takes the whole stack at once as a `SinglyLinkedList`.
+//!
+//! # ABA
+//!
+//! A naive Treiber pop reads the top element and its `next` pointer, then
+//! swings the head from the former to the latter. If the top element is popped
+//! and pushed back by other threads in between, the head compares equal but
+//! `next` is stale (the ABA problem).
+//!
+//! The head is therefore a 64 bit word holding the address of the top element
+//! along with a generation, which every removal bumps: a popper whose top
+//! element was popped and pushed back in between finds another generation,
+//! and retries. The generation has 16 bits on 64 bit targets and 32 bits on
+//! 32 bit ones. Pushes, pops and `pop_all` are all lock-free.
+//!
+//! This module is thus only built for 32 bit targets, and for x86_64 and
+//! aarch64, whose user addresses fit in 48 bits unless a program asks for
+//! memory above them (with 5-level paging or a 52 bit address space): `push`
+//! panics on such an element rather than corrupt the head. Android, which
+//! tags heap pointers in their top byte, is left out.
+//!
+//! # Reclamation
+//!
+//! A popper may still read the `next` pointer of an element which another
+//! thread popped in the meantime. It only ever loads that atomic pointer,
+//! never the rest of the element, which its new owner may be writing to, but
+//! the element must still be there: the stack does no reclamation, so `pop`
+//! and `pop_all` are unsafe, and elements taken off a stack must not be freed
+//! as long as the stack is alive. An object pool, whose elements go back onto
+//! the stack once used and are only freed along with it, is fine.
+//!
+//! # Memory orderings
+//!
+//! A successful push publishes the element with Release, so that the thread
+//! popping it, which loads the head with Acquire, sees it initialized. All
+//! other operations on the head are read-modify-writes, which extend the
+//! release sequence of the push.
+use core::fmt;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+use super::linked_list::OwningPointer;
+use super::slist::{Node, SinglyLinkedList, SLinkable, SLinks};
+
+/// The number of low bits of the head holding the address of the top element,
+/// the others holding the generation
+const ADDR_BITS: u32 = if usize::BITS < 64 { usize::BITS } else { 48 };
+
+/// The address bits of the head
+const ADDR_MASK: u64 = (1 << ADDR_BITS) - 1;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// Link trait allowing a struct to be pushed onto an `AtomicStack`
+///
+/// The links also embed the links of a `SinglyLinkedList`, which is what
+/// `pop_all` returns, so elements implement `slist::Node`.
+///
+/// # Safety
+///
+/// The trait is unsafe because `get_links` and `get_links_mut` must always
+/// return the same field, the one embedding the `SLinks` of the `SLinkable`
+/// implementation.
+pub unsafe trait AtomicStackLinkable : SLinkable
+{
+    fn get_links(&self) -> &StackLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut StackLinks<Self>;
+
+    /// Returns the field `get_links` returns, without making a reference to
+    /// `links`
+    ///
+    /// # Safety
+    ///
+    /// `links` must point to links which have not been freed.
+    unsafe fn links_ptr(links: *const Self) -> *const StackLinks<Self>;
+}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be pushed onto an `AtomicStack`
+///
+/// `define_atomic_stack_element!(MyI32 = i32 : MyLink)` defines an element
+/// `MyI32` holding an `i32` and a link type `MyLink` used to put it on a stack,
+/// or on a `SinglyLinkedList`.
+#[macro_export]
+macro_rules! define_atomic_stack_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_atomic_stack_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_atomic_stack_link!($link = $elt);
+        impl_slist_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_atomic_stack_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_atomic_stack_link!($link = $elt);
+        impl_slist_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_atomic_stack_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::atomic_stack::StackLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::atomic_stack::StackLinks<$link>);
+    );
+}
+
+/// Implements `AtomicStackLinkable` and `SLinkable` for `$link`, which is found
+/// at the field path `$field` (`link` by default) of the `NodeImpl` wrapped by
+/// `$elt`
+#[macro_export]
+macro_rules! impl_atomic_stack_link {
+    ($link:ident = $elt:ident) => (
+        impl_atomic_stack_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::atomic_stack::AtomicStackLinkable for $link {
+            #[inline]
+            fn get_links(&self) -> &$crate::atomic_stack::StackLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self)
+                             -> &mut $crate::atomic_stack::StackLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            unsafe fn links_ptr(links: *const $link)
+                                -> *const $crate::atomic_stack::StackLinks<$link> {
+                ::core::ptr::addr_of!((*links).0)
+            }
+        }
+
+        unsafe impl $crate::slist::SLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::slist::SLinks<$link> {
+                self.0.list_links()
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::slist::SLinks<$link> {
+                self.0.list_links_mut()
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive concurrent LIFO stack
+pub struct AtomicStack<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AtomicStackLinkable<Container=T::Target>
+{
+    /// The address of the top element, tagged with the generation
+    head: AtomicU64,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>,
+    _marker4: PhantomData<*mut L>
+}
+
+#[derive(Default, Debug)]
+pub struct StackLinks<L: SLinkable>
+{
+    next: AtomicPtr<L>,
+    pushed: AtomicBool,
+    list: SLinks<L>,
+    _pin: PhantomPinned
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// AtomicStack impls
+
+impl<P, T, S, L> AtomicStack<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AtomicStackLinkable<Container=T::Target>
+{
+    /// Creates an empty `AtomicStack`
+    #[inline]
+    pub const fn new() -> AtomicStack<P, T, S, L> {
+        AtomicStack { head: AtomicU64::new(0),
+                      _marker: PhantomData, _marker2: PhantomData,
+                      _marker3: PhantomData, _marker4: PhantomData}
+    }
+
+    /// Returns the links whose address is in `head`, or null
+    #[inline]
+    fn top(head: u64) -> *mut L {
+        ptr::with_exposed_provenance_mut((head & ADDR_MASK) as usize)
+    }
+
+    /// Returns `head` with its address replaced by that of `links`, and its
+    /// generation bumped if `bump` is set
+    #[inline]
+    fn tag(head: u64, links: *mut L, bump: bool) -> u64 {
+        let gen = (head >> ADDR_BITS).wrapping_add(bump as u64);
+        gen.wrapping_shl(ADDR_BITS) | links.expose_provenance() as u64
+    }
+
+    /// Returns `true` if the stack was empty when looked at.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed) & ADDR_MASK == 0
+    }
+
+    /// Pushes an element on top of the stack.
+    ///
+    /// This operation is lock-free and may be called from any number of
+    /// threads at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element is already on a stack or on a list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use intrusive_containers::atomic_stack::AtomicStack;
+    ///
+    /// define_atomic_stack_element!(Buffer = [u8; 32] : BufferLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// // the buffers go back onto the pool, and are only freed along with it
+    /// let pool: Arc<AtomicStack<_, Box<Buffer>, _, _>> =
+    ///     Arc::new(AtomicStack::new());
+    /// for _ in 0..4 {
+    ///     pool.push(Box::new(Buffer::new([0; 32])));
+    /// }
+    /// let workers: Vec<_> = (0..4).map(|i| {
+    ///     let pool = pool.clone();
+    ///     thread::spawn(move || {
+    ///         let mut buf = unsafe { pool.pop() }.unwrap();
+    ///         buf.0.val[0] = i;
+    ///         pool.push(buf);
+    ///     })
+    /// }).collect();
+    /// for w in workers {
+    ///     w.join().unwrap();
+    /// }
+    /// assert_eq!(unsafe { pool.pop_all() }.len(), 4);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn push(&self, elt: T) {
+        let links = elt.get_links();
+        SLinkable::check_links(links);
+        let stack_links = AtomicStackLinkable::get_links(links);
+        assert!(!stack_links.pushed.swap(true, Ordering::Relaxed),
+                "Cannot push an element which is already on a stack");
+        let new = links as *const L as *mut L;
+        assert!(new.addr() as u64 & !ADDR_MASK == 0,
+                "Cannot push an element whose address does not fit in the head");
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            stack_links.next.store(Self::top(head), Ordering::Relaxed);
+            match self.head.compare_exchange_weak(head,
+                                                  Self::tag(head, new, false),
+                                                  Ordering::Release,
+                                                  Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(cur) => head = cur,
+            }
+        }
+        unsafe { elt.take() };
+    }
+
+    /// Hands `links`, which were just unlinked from the stack, back to their
+    /// owner
+    unsafe fn release(links: *mut L) -> T {
+        let links = &*links;
+        let stack_links = AtomicStackLinkable::get_links(links);
+        stack_links.next.store(ptr::null_mut(), Ordering::Relaxed);
+        stack_links.pushed.store(false, Ordering::Relaxed);
+        T::from_raw(links.container_of() as *const S as *mut S)
+    }
+
+    /// Pops the element on top of the stack, or returns `None` if it is
+    /// empty.
+    ///
+    /// This operation is lock-free and may be called from any number of
+    /// threads at once.
+    ///
+    /// # Safety
+    ///
+    /// Another thread popping at the same time may still load the `next`
+    /// pointer of the element returned, which must therefore not be freed as
+    /// long as the stack is alive.
+    pub unsafe fn pop(&self) -> Option<T> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let top = Self::top(head);
+            if top.is_null() {
+                return None;
+            }
+            // `top` may have been popped since, and be written to by its new
+            // owner, so only its `next` is read. It is then stale, but the
+            // generation of the head has moved on
+            let next = unsafe { &(*L::links_ptr(top)).next }
+                .load(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(head,
+                                                  Self::tag(head, next, true),
+                                                  Ordering::Acquire,
+                                                  Ordering::Acquire) {
+                Ok(_) => return Some(unsafe { Self::release(top) }),
+                Err(cur) => head = cur,
+            }
+        }
+    }
+
+    /// Takes every element off the stack, returning them as a
+    /// `SinglyLinkedList` in the order they would have been popped.
+    ///
+    /// Taking the elements is a single lock-free atomic operation. Building the
+    /// list takes O(n) time.
+    ///
+    /// # Safety
+    ///
+    /// Another thread popping at the same time may still load the `next`
+    /// pointer of the top element, so the elements returned must not be freed
+    /// as long as the stack is alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::atomic_stack::AtomicStack;
+    ///
+    /// define_atomic_stack_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let stack: AtomicStack<_, Box<MyI32>, _, _> = AtomicStack::new();
+    /// stack.push(Box::new(MyI32::new(1)));
+    /// stack.push(Box::new(MyI32::new(2)));
+    /// stack.push(Box::new(MyI32::new(3)));
+    ///
+    /// // no other thread pops from the stack
+    /// let list = unsafe { stack.pop_all() };
+    /// assert!(stack.is_empty());
+    /// assert_eq!(list.iter().cloned().collect::<Vec<_>>(), [3, 2, 1]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn pop_all(&self) -> SinglyLinkedList<P, T, S, L> {
+        let mut list = SinglyLinkedList::new();
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            match self.head.compare_exchange_weak(head,
+                                                  Self::tag(head, ptr::null_mut(),
+                                                            true),
+                                                  Ordering::Acquire,
+                                                  Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(cur) => head = cur,
+            }
+        }
+        let mut head = Self::top(head);
+        while !head.is_null() {
+            let next = AtomicStackLinkable::get_links(unsafe { &*head })
+                .next.load(Ordering::Relaxed);
+            list.push_front(unsafe { Self::release(head) });
+            head = next;
+        }
+        list.reverse();
+        list
+    }
+}
+
+impl<P, T, S, L> Default for AtomicStack<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AtomicStackLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> AtomicStack<P, T, S, L> {
+        AtomicStack::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for AtomicStack<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AtomicStackLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AtomicStack { .. }")
+    }
+}
+
+impl<P, T, S, L> Drop for AtomicStack<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AtomicStackLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        let mut cur: *mut L =
+            ptr::with_exposed_provenance_mut((*self.head.get_mut() & ADDR_MASK)
+                                             as usize);
+        while !cur.is_null() {
+            let links = unsafe { &*cur };
+            let stack_links = AtomicStackLinkable::get_links(links);
+            cur = stack_links.next.load(Ordering::Relaxed);
+            stack_links.next.store(ptr::null_mut(), Ordering::Relaxed);
+            stack_links.pushed.store(false, Ordering::Relaxed);
+            drop(unsafe {
+                T::from_raw(links.container_of() as *const S as *mut S)
+            });
+        }
+    }
+}
+
+// Elements are handed from one thread to another
+unsafe impl<P, T, S, L> Send for AtomicStack<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: AtomicStackLinkable<Container=T::Target>
+{}
+
+unsafe impl<P, T, S, L> Sync for AtomicStack<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: AtomicStackLinkable<Container=T::Target>
+{}
+
+// StackLinks impls
+
+impl<L: SLinkable> StackLinks<L> {
+    /// The links used while the element is on a `SinglyLinkedList`
+    #[inline]
+    pub fn list_links(&self) -> &SLinks<L> {
+        &self.list
+    }
+
+    /// The links used while the element is on a `SinglyLinkedList`
+    #[inline]
+    pub fn list_links_mut(&mut self) -> &mut SLinks<L> {
+        &mut self.list
+    }
+}
+
+impl<L: SLinkable> Clone for StackLinks<L>
+{
+    /// A clone is not on any stack, even if the original is
+    fn clone(&self) -> StackLinks<L> {
+        Default::default()
+    }
+}
+
+impl<L: SLinkable> Drop for StackLinks<L>
+{
+    fn drop(&mut self) {
+        assert!(!*self.pushed.get_mut());
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(test)]
+mod no_alloc_tests {
+    use std::vec::Vec;
+    use super::AtomicStack;
+
+    define_atomic_stack_element!(MyI32 = i32 : MyLink);
+
+    #[test]
+    fn test_stack_elements() {
+        let mut elts = [0, 1, 2, 3].map(MyI32::new);
+        let s = AtomicStack::new();
+        for elt in elts.iter_mut() {
+            s.push(elt);
+        }
+        assert_eq!(unsafe { s.pop() }.map(|elt| elt.0.val), Some(3));
+        let rest = unsafe { s.pop_all() };
+        assert!(s.is_empty());
+        assert_eq!(rest.iter().cloned().collect::<Vec<_>>(), [2, 1, 0]);
+    }
+}
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::vec::Vec;
+    use super::{AtomicStack, ADDR_MASK};
+    use super::super::slist::{Node, SinglyLinkedList};
+
+    define_atomic_stack_element!(MyI32 = i32 : MyLink);
+
+    type Stack = AtomicStack<i32, Box<MyI32>, MyI32, MyLink>;
+
+    #[test]
+    fn test_basic() {
+        let s = Stack::new();
+        assert!(s.is_empty());
+        assert!(unsafe { s.pop() }.is_none());
+        for i in 0..4 {
+            s.push(Box::new(MyI32::new(i)));
+        }
+        assert!(!s.is_empty());
+        assert_eq!(unsafe { s.pop() }.map(|e| *e.get_val()), Some(3));
+        assert_eq!(unsafe { s.pop() }.map(|e| *e.get_val()), Some(2));
+        s.push(Box::new(MyI32::new(4)));
+        let all = unsafe { s.pop_all() };
+        assert!(s.is_empty());
+        assert_eq!(all.iter().cloned().collect::<Vec<_>>(), [4, 1, 0]);
+        assert!(unsafe { s.pop_all() }.is_empty());
+        // leave some elements for the destructor
+        s.push(Box::new(MyI32::new(5)));
+        s.push(Box::new(MyI32::new(6)));
+    }
+
+    #[test]
+    fn test_pop_all_round_trip() {
+        let s = Stack::new();
+        for i in 0..3 {
+            s.push(Box::new(MyI32::new(i)));
+        }
+        let mut list: SinglyLinkedList<_, Box<MyI32>, _, _> =
+            unsafe { s.pop_all() };
+        // elements go back onto the stack once off the list
+        while let Some(e) = list.pop_front() {
+            s.push(e);
+        }
+        let all = unsafe { s.pop_all() };
+        assert_eq!(all.iter().cloned().collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_aba() {
+        let s = Stack::new();
+        s.push(Box::new(MyI32::new(1)));
+        s.push(Box::new(MyI32::new(2)));
+        // the head as seen by a popper preempted before its unlink
+        let stale = s.head.load(Ordering::Relaxed);
+        let two = unsafe { s.pop() }.unwrap();
+        let one = unsafe { s.pop() }.unwrap();
+        s.push(two);
+        // the same element is on top, but in another generation
+        let head = s.head.load(Ordering::Relaxed);
+        assert_eq!(head & ADDR_MASK, stale & ADDR_MASK);
+        assert!(head != stale);
+        assert!(s.head.compare_exchange(stale, 0, Ordering::Relaxed,
+                                        Ordering::Relaxed).is_err());
+        assert_eq!(unsafe { s.pop() }.map(|e| *e.get_val()), Some(2));
+        assert!(s.is_empty());
+        drop(one);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_twice() {
+        let s = AtomicStack::<i32, Rc<MyI32>, MyI32, MyLink>::new();
+        let elt = Rc::new(MyI32::new(1));
+        s.push(elt.clone());
+        s.push(elt);
+    }
+
+    #[test]
+    fn test_threads() {
+        let s: Arc<Stack> = Arc::new(AtomicStack::new());
+        for i in 0..16 {
+            s.push(Box::new(MyI32::new(i)));
+        }
+        let workers: Vec<_> = (0..4).map(|_| {
+            let s = s.clone();
+            thread::spawn(move || {
+                for _ in 0..10000 {
+                    if let Some(mut e) = unsafe { s.pop() } {
+                        *e.get_val_mut() += 16;
+                        s.push(e);
+                    }
+                }
+            })
+        }).collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+        // every element is still there, once
+        let all = unsafe { s.pop_all() };
+        let mut vals: Vec<_> = all.iter().map(|v| v % 16).collect();
+        vals.sort();
+        assert_eq!(vals, (0..16).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,1176 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive augmented red-black tree.
+//!
+//! The `AugTree` keeps its elements sorted by value like an `RBTree`, but
+//! each link also holds the size of its subtree and a user-defined summary
+//! of it, such as a sum or a maximum, chosen by implementing `Augment`. Both
+//! are kept up to date through insertions, removals and rotations, so
+//! finding the element at an index, the index of a value or the summary of
+//! any run of elements takes O(log n) time.
+use core::borrow::Borrow;
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ops::Range;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+use super::rbtree::{Balance, Color, RBLinkable, RBLinkOps, RBLinks};
+use super::rbtree::{left, link_of, maximum, minimum, parent, predecessor, right,
+                    same, successor};
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A summary of a run of elements of type `P` kept by an `AugTree`
+///
+/// `Default::default()` must be the summary of no elements, and `combine`
+/// must be associative with it as identity, since the tree is free to group
+/// elements in any way. Implementing `Augment<P>` for `()` keeps no summary.
+pub trait Augment<P> : Clone + Default {
+    /// Returns the summary of the single element `val`
+    fn summarize(val: &P) -> Self;
+
+    /// Returns the summary of the elements of `self` followed by those of
+    /// `next`
+    fn combine(&self, next: &Self) -> Self;
+}
+
+/// A trait that allows a struct to be inserted into an `AugTree`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_augtree_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: AugLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into an `AugTree`
+///
+/// The links embed those of an `RBTree`, whose balancing the `AugTree` shares,
+/// along with the size and summary of the subtree below them.
+///
+/// # Safety
+///
+/// The trait is unsafe because `get_links` and `get_links_mut` must always
+/// return the same field, the one embedding the `RBLinks` of the `RBLinkable`
+/// implementation.
+pub unsafe trait AugLinkable : RBLinkable
+{
+    /// The summary kept for the subtree below each link
+    type Summary: Default;
+
+    fn get_links(&self) -> &AugLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut AugLinks<Self>;
+}
+
+/// Accessors for the subtree size kept in the links of an `AugLinkable`
+///
+/// They are private to the crate, so that the links of an element are only
+/// followed or changed by the tree it is in.
+pub(crate) trait AugLinkOps : AugLinkable
+{
+    fn get_size(&self) -> usize {
+        AugLinkable::get_links(self).size.get()
+    }
+    fn set_size(&self, size: usize) {
+        AugLinkable::get_links(self).size.set(size)
+    }
+}
+
+impl<L: AugLinkable> AugLinkOps for L {}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into an `AugTree`
+///
+/// `define_augtree_element!(MyI32 = i32 : MyLink, Sum)` defines an element
+/// `MyI32` holding an `i32` and a link type `MyLink` used to put it in a tree
+/// which keeps a `Sum` of each subtree. `Sum` must implement `Augment<i32>`.
+/// The tree is ordered by the `i32`.
+#[macro_export]
+macro_rules! define_augtree_element {
+    ($elt:ident = $container:ty : $link:ident, $summary:ty) => (
+        declare_augtree_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_augtree_link!($link, $summary = $elt);
+        impl_augtree_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident, $summary:ty) => (
+        declare_augtree_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_augtree_link!($link, $summary = $elt);
+        impl_augtree_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_augtree_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::augtree::AugLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::augtree::AugLinks<$link>);
+    );
+}
+
+/// Implements `AugLinkable` and `RBLinkable` for `$link`, keeping a `$summary`
+/// of each subtree, where `$link` is found at the field path `$field` (`link`
+/// by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_augtree_link {
+    ($link:ident, $summary:ty = $elt:ident) => (
+        impl_augtree_link!($link, $summary = $elt : link);
+    );
+    ($link:ident, $summary:ty = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::augtree::AugLinkable for $link {
+            type Summary = $summary;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::augtree::AugLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::augtree::AugLinks<$link> {
+                &mut self.0
+            }
+        }
+
+        unsafe impl $crate::rbtree::RBLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::rbtree::RBLinks<$link> {
+                self.0.tree_links()
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::rbtree::RBLinks<$link> {
+                self.0.tree_links_mut()
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_augtree_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_augtree_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::augtree::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive augmented red-black tree
+pub struct AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AugLinkable<Container=T::Target>
+{
+    length: usize,
+    root: Rawlink<L>,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+#[derive(Default, Debug)]
+pub struct AugLinks<L: AugLinkable>
+{
+    links: RBLinks<L>,
+    size: Cell<usize>,
+    /// Only written by the tree while it is borrowed mutably
+    summary: UnsafeCell<L::Summary>,
+    _pin: PhantomPinned
+}
+
+/// An in-order iterator over references to the items of an `AugTree`
+pub struct Iter<'a, P: 'a, T, L: AugLinkable<Container=T>> {
+    head: Rawlink<L>,
+    tail: Rawlink<L>,
+    nelem: usize,
+    _marker: PhantomData<&'a P>
+}
+
+pub struct IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AugLinkable<Container=T::Target>
+{
+    tree: AugTree<P, T, S, L>
+}
+
+//////////////////////
+// Tree Navigation  //
+//////////////////////
+
+fn size<L: AugLinkable>(node: Option<&L>) -> usize {
+    node.map_or(0, L::get_size)
+}
+
+pub(crate) fn value<'a, P, S, L>(node: &'a L) -> &'a P
+    where S: Node<P, L> + 'a,
+          L: AugLinkable<Container=S>
+{
+    unsafe { node.container_of() }.get_val()
+}
+
+/// Returns the summary of the subtree at `node`
+pub(crate) fn summary<L: AugLinkable>(node: &L) -> &L::Summary {
+    // summaries are only written through a mutably borrowed tree
+    unsafe { &*AugLinkable::get_links(node).summary.get() }
+}
+
+/// Recomputes the size and summary of `node` from those of its children
+fn update<P, S, L>(node: &L)
+    where S: Node<P, L>,
+          L: AugLinkable<Container=S>,
+          L::Summary: Augment<P>
+{
+    let (l, r) = (left(node), right(node));
+    let mut sum = L::Summary::summarize(value(node));
+    if let Some(l) = l {
+        sum = summary(l).combine(&sum);
+    }
+    if let Some(r) = r {
+        sum = sum.combine(summary(r));
+    }
+    node.set_size(size(l) + 1 + size(r));
+    unsafe { *AugLinkable::get_links(node).summary.get() = sum };
+}
+
+/// Returns the summary of the elements of the subtree at `node` whose
+/// in-order indices within it fall in `lo..hi`
+fn range_summary<P, S, L>(node: Option<&L>, lo: usize, hi: usize) -> L::Summary
+    where S: Node<P, L>,
+          L: AugLinkable<Container=S>,
+          L::Summary: Augment<P>
+{
+    let node = match node {
+        Some(node) if lo < hi => node,
+        _ => return Default::default()
+    };
+    if lo == 0 && hi >= node.get_size() {
+        return summary(node).clone();
+    }
+    let here = size(left(node));
+    let mut sum = range_summary(left(node), lo, hi.min(here));
+    if lo <= here && here < hi {
+        sum = sum.combine(&L::Summary::summarize(value(node)));
+    }
+    if hi > here + 1 {
+        let rest = range_summary(right(node), lo.saturating_sub(here + 1),
+                                 hi - here - 1);
+        sum = sum.combine(&rest);
+    }
+    sum
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// AugTree impls
+
+impl<P, T, S, L> AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>
+{
+    /// Creates an empty `AugTree`
+    #[inline]
+    pub fn new() -> AugTree<P, T, S, L> {
+        AugTree { length: 0, root: Rawlink::none(),
+                  _marker: PhantomData, _marker2: PhantomData,
+                  _marker3: PhantomData}
+    }
+
+    /// Provides a double-ended iterator over the elements in order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        let root = self.root.resolve();
+        Iter{nelem: self.length,
+             head: link_of(root.map(minimum)),
+             tail: link_of(root.map(maximum)),
+             _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `AugTree` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of elements in the `AugTree`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all elements from the `AugTree`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = AugTree::new()
+    }
+
+    /// Provides a reference to the smallest element, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    #[inline]
+    pub fn first(&self) -> Option<&P> {
+        self.root.resolve().map(|root| value(minimum(root)))
+    }
+
+    /// Provides a reference to the largest element, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    #[inline]
+    pub fn last(&self) -> Option<&P> {
+        self.root.resolve().map(|root| value(maximum(root)))
+    }
+
+    /// Provides a reference to the element at `index` in order, or `None` if
+    /// the index is out of bounds.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn nth(&self, mut index: usize) -> Option<&P> {
+        let mut cur = self.root.resolve();
+        while let Some(node) = cur {
+            let here = size(left(node));
+            if index < here {
+                cur = left(node);
+            } else if index == here {
+                return Some(value(node));
+            } else {
+                index -= here + 1;
+                cur = right(node);
+            }
+        }
+        None
+    }
+
+    /// Returns the root of the tree, or `None` if it is empty
+    #[inline]
+    pub(crate) fn root(&self) -> Option<&L> {
+        self.root.resolve()
+    }
+
+    /// Returns the index of `elt` in the tree.
+    ///
+    /// This operation should compute in O(log n) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any tree.
+    pub fn index_of(&self, elt: &S) -> usize {
+        let mut node = elt.get_links();
+        assert!(node.get_parent().resolve().is_some(),
+                "Cannot find the index of an element which is not in a tree");
+        let mut index = size(left(node));
+        while let Some(p) = parent(node) {
+            if same(right(p), Some(node)) {
+                index += size(left(p)) + 1;
+            }
+            node = p;
+        }
+        debug_assert!(same(self.root.resolve(), Some(node)),
+                      "Cannot find the index of an element of another tree");
+        index
+    }
+}
+
+impl<P, T, S, L> AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>,
+          L::Summary: Augment<P>
+{
+    /// Returns the summary of all elements in the tree.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn summary(&self) -> L::Summary {
+        self.root.resolve().map(summary).cloned().unwrap_or_default()
+    }
+
+    /// Returns the summary of the elements whose indices fall in `range`,
+    /// which is clamped to the length of the tree. The summary of the first
+    /// n elements, a prefix sum, is `range_summary(0..n)`.
+    ///
+    /// This operation should compute in O(log n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::AugTree;
+    /// use intrusive_containers::augtree::Augment;
+    ///
+    /// #[derive(Clone, Copy, Default, Debug, PartialEq)]
+    /// struct Sum(i64);
+    ///
+    /// impl Augment<i32> for Sum {
+    ///     fn summarize(val: &i32) -> Sum { Sum(*val as i64) }
+    ///     fn combine(&self, next: &Sum) -> Sum { Sum(self.0 + next.0) }
+    /// }
+    ///
+    /// define_augtree_element!(MyI32 = i32 : MyLink, Sum);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let t: AugTree<_, _, _, MyLink> =
+    ///     [5, 1, 4, 2, 3].iter().map(|&i| Box::new(MyI32::new(i))).collect();
+    /// assert_eq!(t.nth(1), Some(&2));
+    /// assert_eq!(t.rank(&4), 3);
+    /// assert_eq!(t.summary(), Sum(15));
+    /// assert_eq!(t.range_summary(0..3), Sum(1 + 2 + 3));
+    /// assert_eq!(t.range_summary(3..10), Sum(4 + 5));
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn range_summary(&self, range: Range<usize>) -> L::Summary {
+        range_summary(self.root.resolve(), range.start, range.end)
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let node = minimum(root);
+            self.delete(node);
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Removes the largest element and returns it, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let node = maximum(root);
+            self.delete(node);
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Unlinks `elt` from the tree and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) time. In debug builds the
+    /// parent links are followed to check that `elt` belongs to this tree.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this tree. Removing an element of another tree would leave
+    /// that tree with an incorrect length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any tree.
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links();
+        assert!(links.get_parent().resolve().is_some(),
+                "Cannot remove an element which is not in a tree");
+        debug_assert!(self.contains_links(links),
+                      "Cannot remove an element of another tree");
+        self.delete(links);
+        T::from_raw(links.container_of() as *const S as *mut S)
+    }
+
+    /// Climbs to the root from `links` to check it is in this tree
+    fn contains_links(&self, links: &L) -> bool {
+        let mut node = links;
+        while let Some(p) = parent(node) {
+            node = p;
+        }
+        same(self.root.resolve(), Some(node))
+    }
+
+    /// Inserts an element into the tree, before the elements whose values its
+    /// value is `before`, and after the others.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub(crate) fn insert_by<F>(&mut self, elt: T, before: F)
+        where F: Fn(&P, &P) -> bool
+    {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        let val = elt.get_val();
+        let links = elt.get_links();
+        let mut cur = self.root.resolve();
+        let mut parent = None;
+        let mut go_left = false;
+        while let Some(node) = cur {
+            parent = Some(node);
+            go_left = before(val, value(node));
+            cur = if go_left { left(node) } else { right(node) };
+        }
+        match parent {
+            None => {
+                links.set_parent(Rawlink::some(links));
+                self.root = Rawlink::some(links);
+            }
+            Some(p) => {
+                links.set_parent(Rawlink::some(p));
+                if go_left {
+                    p.set_left(Rawlink::some(links));
+                } else {
+                    p.set_right(Rawlink::some(links));
+                }
+            }
+        }
+        links.set_color(Color::Red);
+        self.length += 1;
+        Self::update_to_root(Some(links));
+        Self::insert_fixup(&mut self.root, links);
+        unsafe { elt.take() };
+    }
+
+    /// Unlinks `node` from the tree
+    fn delete(&mut self, node: &L) {
+        Self::unlink(&mut self.root, node);
+        self.length -= 1;
+    }
+}
+
+impl<P, T, S, L> AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>,
+          L::Summary: Augment<P>
+{
+    /// Inserts an element into the tree.
+    ///
+    /// An element equal to some already in the tree is placed after them.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn insert(&mut self, elt: T) {
+        self.insert_by(elt, |val, node| val < node);
+    }
+}
+
+impl<P, T, S, L> AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>
+{
+    /// Provides a reference to an element equal to `key`, or `None` if there
+    /// is none.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn find<Q>(&self, key: &Q) -> Option<&P>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        let mut cur = self.root.resolve();
+        let mut found = None;
+        while let Some(node) = cur {
+            let val = value(node);
+            if val.borrow() >= key {
+                found = Some(val);
+                cur = left(node);
+            } else {
+                cur = right(node);
+            }
+        }
+        found.filter(|val| (*val).borrow() == key)
+    }
+
+    /// Returns the number of elements less than `key`, which is the index of
+    /// the first element not less than it.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        let mut cur = self.root.resolve();
+        let mut rank = 0;
+        while let Some(node) = cur {
+            if value(node).borrow() < key {
+                rank += size(left(node)) + 1;
+                cur = right(node);
+            } else {
+                cur = left(node);
+            }
+        }
+        rank
+    }
+}
+
+impl<P, T, S, L> Balance<L> for AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>,
+          L::Summary: Augment<P>
+{
+    /// Recomputes the size and summary of `node`
+    #[inline]
+    fn update(node: &L) {
+        update(node)
+    }
+}
+
+impl<P, T, S, L> Default for AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> AugTree<P, T, S, L> {
+        AugTree::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl<P, T, S, L> Drop for AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AugLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        // unlink the leaves one at a time, without rebalancing
+        let mut cur = self.root.take().resolve();
+        while let Some(node) = cur {
+            if let Some(l) = left(node) {
+                cur = Some(l);
+                continue;
+            }
+            if let Some(r) = right(node) {
+                cur = Some(r);
+                continue;
+            }
+            cur = parent(node);
+            if let Some(p) = cur {
+                if same(left(p), Some(node)) {
+                    p.set_left(Rawlink::none());
+                } else {
+                    p.set_right(Rawlink::none());
+                }
+            }
+            node.set_parent(Rawlink::none());
+            // hand back the reference held by the tree
+            drop(unsafe {
+                T::from_raw(node.container_of() as *const S as *mut S)
+            });
+        }
+        self.length = 0;
+    }
+}
+
+impl<P, T, S, L> Extend<T> for AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>,
+          L::Summary: Augment<P>
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.insert(elt); }
+    }
+}
+
+impl<P, T, S, L> FromIterator<T> for AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>,
+          L::Summary: Augment<P>
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> AugTree<P, T, S, L> {
+        let mut ret = AugTree::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L> IntoIterator for AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>,
+          L::Summary: Augment<P>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L>;
+
+    /// Consumes the tree into an in-order iterator yielding elements by value.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L> {
+        IntoIter{tree: self}
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: AugLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+unsafe impl<P, T, S, L> Send for AugTree<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: AugLinkable<Container=T::Target>
+{}
+
+// AugLinks impls
+
+impl<L: AugLinkable> AugLinks<L> {
+    /// The links of the underlying red-black tree
+    #[inline]
+    pub fn tree_links(&self) -> &RBLinks<L> {
+        &self.links
+    }
+
+    /// The links of the underlying red-black tree
+    #[inline]
+    pub fn tree_links_mut(&mut self) -> &mut RBLinks<L> {
+        &mut self.links
+    }
+}
+
+unsafe impl<L: AugLinkable> Send for AugLinks<L> where L::Summary: Send {}
+
+impl<L: AugLinkable> Clone for AugLinks<L>
+{
+    /// A clone is not in any tree, even if the original is
+    fn clone(&self) -> AugLinks<L> {
+        Default::default()
+    }
+}
+
+// Augment impls
+
+impl<P> Augment<P> for () {
+    #[inline]
+    fn summarize(_: &P) {}
+
+    #[inline]
+    fn combine(&self, _: &()) {}
+}
+
+// Iter impls
+
+impl<'a, P, T, L: AugLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            head: self.head,
+            tail: self.tail,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: AugLinkable<Container=T> + 'a> Iterator
+    for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve().unwrap();
+        self.nelem -= 1;
+        self.head = link_of(successor(head));
+        Some(value(head))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: AugLinkable<Container=T> + 'a>
+    DoubleEndedIterator for Iter<'a, P, T, L>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let tail = self.tail.resolve().unwrap();
+        self.nelem -= 1;
+        self.tail = link_of(predecessor(tail));
+        Some(value(tail))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: AugLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+// IntoIter impls
+
+impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>,
+          L::Summary: Augment<P>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.tree.pop_first() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.tree.length, Some(self.tree.length))
+    }
+}
+
+impl<P, T, S, L> DoubleEndedIterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>,
+          L::Summary: Augment<P>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<T> { self.tree.pop_last() }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::vec::Vec;
+    use core::ptr;
+    use rand::{self, Rng};
+    use super::{AugTree, OwningPointer, Node, AugLinkable, AugLinkOps, Augment};
+    use super::{Color, RBLinkOps};
+    use super::{left, right, parent, summary};
+
+    #[derive(Clone, Copy, Default, Debug, PartialEq)]
+    struct Sum(i64);
+
+    impl Augment<i32> for Sum {
+        fn summarize(val: &i32) -> Sum { Sum(*val as i64) }
+        fn combine(&self, next: &Sum) -> Sum { Sum(self.0 + next.0) }
+    }
+
+    /// The largest value, along with how many times it occurs
+    #[derive(Clone, Copy, Default, Debug, PartialEq)]
+    struct Max(Option<(i32, usize)>);
+
+    impl Augment<i32> for Max {
+        fn summarize(val: &i32) -> Max { Max(Some((*val, 1))) }
+        fn combine(&self, next: &Max) -> Max {
+            match (self.0, next.0) {
+                (Some((a, m)), Some((b, n))) if a == b => Max(Some((a, m + n))),
+                (Some(a), Some(b)) => Max(Some(if a.0 > b.0 { a } else { b })),
+                (a, b) => Max(a.or(b))
+            }
+        }
+    }
+
+    define_augtree_element!(MyI32 = i32 : MyLink, Sum);
+    define_augtree_element!(MaxI32 = i32 : MaxLink, Max);
+    define_augtree_element!(PlainI32 = i32 : PlainLink, ());
+
+    type Tree = AugTree<i32, Box<MyI32>, MyI32, MyLink>;
+
+    /// Checks the parent links, ordering, sizes, summaries and red-black
+    /// properties of the subtree at `node`, returning its black height
+    fn check_node<P, S, L>(node: &L) -> usize
+        where P: Ord,
+              S: Node<P, L>,
+              L: AugLinkable<Container=S>,
+              L::Summary: Augment<P> + PartialEq + core::fmt::Debug
+    {
+        let val = unsafe { node.container_of() }.get_val();
+        let mut size = 1;
+        let mut sum = L::Summary::summarize(val);
+        let mut heights = [0; 2];
+        for (i, child) in [left(node), right(node)].iter().enumerate() {
+            if let Some(child) = *child {
+                assert!(ptr::eq(parent(child).unwrap(), node));
+                let child_val = unsafe { child.container_of() }.get_val();
+                if i == 0 {
+                    assert!(child_val <= val);
+                    sum = summary(child).combine(&sum);
+                } else {
+                    assert!(child_val >= val);
+                    sum = sum.combine(summary(child));
+                }
+                if node.get_color() == Color::Red {
+                    assert_eq!(child.get_color(), Color::Black);
+                }
+                heights[i] = check_node(child);
+                size += child.get_size();
+            }
+        }
+        assert_eq!(heights[0], heights[1]);
+        assert_eq!(node.get_size(), size);
+        assert_eq!(summary(node), &sum);
+        heights[0] + (node.get_color() == Color::Black) as usize
+    }
+
+    pub fn check_links<P, T, S, L>(tree: &AugTree<P, T, S, L>)
+        where T: OwningPointer<Target=S>,
+              P: Ord,
+              S: Node<P, L>,
+              L: AugLinkable<Container=S>,
+              L::Summary: Augment<P> + PartialEq + core::fmt::Debug
+    {
+        match tree.root.resolve() {
+            None => assert_eq!(tree.length, 0),
+            Some(root) => {
+                assert!(ptr::eq(root.get_parent().resolve().unwrap(), root));
+                assert_eq!(root.get_color(), Color::Black);
+                check_node(root);
+                assert_eq!(root.get_size(), tree.length);
+            }
+        }
+    }
+
+    fn tree_from(v: &[i32]) -> Tree {
+        let tree: Tree = v.iter().map(|&i| Box::new(MyI32::new(i))).collect();
+        check_links(&tree);
+        tree
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut t = Tree::new();
+        assert!(t.is_empty());
+        assert_eq!(t.summary(), Sum(0));
+        assert_eq!(t.nth(0), None);
+        t.insert(Box::new(MyI32::new(2)));
+        t.insert(Box::new(MyI32::new(1)));
+        t.insert(Box::new(MyI32::new(3)));
+        check_links(&t);
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.summary(), Sum(6));
+        assert_eq!(t.first(), Some(&1));
+        assert_eq!(t.last(), Some(&3));
+        assert_eq!(t.pop_first(), Some(Box::new(MyI32::new(1))));
+        assert_eq!(t.pop_last(), Some(Box::new(MyI32::new(3))));
+        check_links(&t);
+        assert_eq!(t.summary(), Sum(2));
+        assert_eq!(t.iter().cloned().collect::<Vec<_>>(), [2]);
+        t.clear();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_order_statistics() {
+        let elts: Vec<_> = [50, 10, 40, 20, 30, 20].iter()
+            .map(|&i| Rc::new(PlainI32::new(i))).collect();
+        let t: AugTree<_, _, _, PlainLink> = elts.iter().cloned().collect();
+        check_links(&t);
+        let sorted: Vec<_> = (0..6).map(|i| *t.nth(i).unwrap()).collect();
+        assert_eq!(sorted, [10, 20, 20, 30, 40, 50]);
+        assert_eq!(t.nth(6), None);
+        assert_eq!(t.rank(&5), 0);
+        assert_eq!(t.rank(&20), 1);
+        assert_eq!(t.rank(&25), 3);
+        assert_eq!(t.rank(&99), 6);
+        assert_eq!(t.find(&40), Some(&40));
+        assert_eq!(t.find(&45), None);
+        // equal elements are kept in insertion order
+        assert_eq!(t.index_of(&elts[3]), 1);
+        assert_eq!(t.index_of(&elts[5]), 2);
+        assert_eq!(t.index_of(&elts[0]), 5);
+        drop(t);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_range_summary() {
+        let t = tree_from(&[8, 3, 5, 1, 9, 2, 7]);
+        // sorted: 1 2 3 5 7 8 9
+        assert_eq!(t.range_summary(0..0), Sum(0));
+        assert_eq!(t.range_summary(0..3), Sum(6));
+        assert_eq!(t.range_summary(2..5), Sum(15));
+        assert_eq!(t.range_summary(6..7), Sum(9));
+        assert_eq!(t.range_summary(4..100), Sum(24));
+        assert_eq!(t.range_summary(7..7), Sum(0));
+
+        let t: AugTree<_, _, _, MaxLink> = [4, 9, 1, 9, 3].iter()
+            .map(|&i| Box::new(MaxI32::new(i))).collect();
+        check_links(&t);
+        assert_eq!(t.summary(), Max(Some((9, 2))));
+        assert_eq!(t.range_summary(0..3), Max(Some((4, 1))));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut elts: Vec<*const MyI32> = Vec::new();
+        let mut t = Tree::new();
+        for i in 0..32 {
+            let e = Box::new(MyI32::new(i));
+            elts.push(&*e);
+            t.insert(e);
+        }
+        for i in (0..32).step_by(3) {
+            let e = unsafe { t.remove(&*elts[i]) };
+            assert_eq!(*e.get_val(), i as i32);
+            check_links(&t);
+        }
+        assert_eq!(t.len(), 21);
+        assert_eq!(t.summary(), Sum((0..32).filter(|i| i % 3 != 0).sum()));
+        assert_eq!(t.index_of(unsafe { &*elts[31] }), 20);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_unlinked() {
+        let mut t = Tree::new();
+        t.insert(Box::new(MyI32::new(1)));
+        let e = MyI32::new(2);
+        unsafe { t.remove(&e) };
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut t = Tree::new();
+            let mut v: Vec<i32> = Vec::new();
+            let mut elts: Vec<*const MyI32> = Vec::new();
+            for _ in 0..200 {
+                match rng.gen_range(0..4) {
+                    0 if !elts.is_empty() => {
+                        let i = rng.gen_range(0..elts.len());
+                        let e = unsafe { t.remove(&*elts.swap_remove(i)) };
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    _ => {
+                        let val = rng.gen_range(-50..50);
+                        let e = Box::new(MyI32::new(val));
+                        elts.push(&*e);
+                        t.insert(e);
+                        v.push(val);
+                    }
+                }
+                check_links(&t);
+                v.sort();
+                let a = rng.gen_range(0..v.len() + 2);
+                let b = rng.gen_range(0..v.len() + 2);
+                let expect = v.iter().take(b).skip(a).map(|&x| x as i64).sum();
+                assert_eq!(t.range_summary(a..b), Sum(expect));
+                assert_eq!(t.nth(a), v.get(a));
+                let key = rng.gen_range(-60..60);
+                assert_eq!(t.rank(&key), v.iter().filter(|&&x| x < key).count());
+            }
+            assert_eq!(t.iter().cloned().collect::<Vec<_>>(), v);
+        }
+    }
+}
@@ -0,0 +1,1225 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive AVL tree.
+//!
+//! The `AVLTree` keeps its elements sorted by value and supports lookup,
+//! insertion and removal in O(log n) time. Its balance is stricter than that
+//! of an `RBTree`, making lookups faster at the cost of more rotations on
+//! insertion and removal. Elements with equal values are allowed and are kept
+//! in insertion order.
+use core::borrow::Borrow;
+use core::cell::Cell;
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ptr;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into an `AVLTree`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_avltree_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: AVLLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into an `AVLTree`
+///
+/// The root of a tree is its own parent, so that an element is in a tree
+/// exactly when its parent link is set.
+///
+/// # Safety
+///
+/// The trait is unsafe because any implementation must impl Drop to call
+/// check_links(), and `offset()` must be the offset of the link within its
+/// `Container`.
+pub unsafe trait AVLLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &AVLLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut AVLLinks<Self>;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+    fn check_links(&self) {
+        assert!(self.get_parent().resolve().is_none());
+        assert!(self.get_left().resolve().is_none());
+        assert!(self.get_right().resolve().is_none());
+    }
+}
+
+/// Accessors for the links of a `AVLLinkable`
+///
+/// They are private to the crate, so that the links of an element are only
+/// followed or changed by the tree it is in.
+pub(crate) trait AVLLinkOps : AVLLinkable
+{
+    fn get_parent(&self) -> Rawlink<Self> {
+        self.get_links().parent.get()
+    }
+    fn set_parent(&self, parent: Rawlink<Self>) {
+        self.get_links().parent.set(parent)
+    }
+    fn get_left(&self) -> Rawlink<Self> {
+        self.get_links().left.get()
+    }
+    fn set_left(&self, left: Rawlink<Self>) {
+        self.get_links().left.set(left)
+    }
+    fn get_right(&self) -> Rawlink<Self> {
+        self.get_links().right.get()
+    }
+    fn set_right(&self, right: Rawlink<Self>) {
+        self.get_links().right.set(right)
+    }
+    /// Height of the right subtree minus that of the left subtree
+    fn get_balance(&self) -> i8 {
+        self.get_links().balance.get()
+    }
+    fn set_balance(&self, balance: i8) {
+        self.get_links().balance.set(balance)
+    }
+}
+
+impl<L: AVLLinkable> AVLLinkOps for L {}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into an `AVLTree`
+///
+/// `define_avltree_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it in a tree. The
+/// tree is ordered by the `i32`.
+#[macro_export]
+macro_rules! define_avltree_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_avltree_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_avltree_link!($link = $elt);
+        impl_avltree_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_avltree_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_avltree_link!($link = $elt);
+        impl_avltree_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_avltree_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::avltree::AVLLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::avltree::AVLLinks<$link>);
+    );
+}
+
+/// Implements `AVLLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_avltree_link {
+    ($link:ident = $elt:ident) => (
+        impl_avltree_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::avltree::AVLLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::avltree::AVLLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::avltree::AVLLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_avltree_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_avltree_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::avltree::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive AVL tree
+pub struct AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AVLLinkable<Container=T::Target>
+{
+    length: usize,
+    root: Rawlink<L>,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+#[derive(Default, Debug)]
+pub struct AVLLinks<L: AVLLinkable>
+{
+    parent: Cell<Rawlink<L>>,
+    left: Cell<Rawlink<L>>,
+    right: Cell<Rawlink<L>>,
+    balance: Cell<i8>,
+    _pin: PhantomPinned
+}
+
+/// An in-order iterator over references to the items of an `AVLTree`
+pub struct Iter<'a, P: 'a, T, L: AVLLinkable<Container=T>> {
+    head: Rawlink<L>,
+    tail: Rawlink<L>,
+    nelem: usize,
+    _marker: PhantomData<&'a P>
+}
+
+pub struct IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AVLLinkable<Container=T::Target>
+{
+    tree: AVLTree<P, T, S, L>
+}
+
+//////////////////////
+// Tree Navigation  //
+//////////////////////
+
+fn parent<L: AVLLinkable>(node: &L) -> Option<&L> {
+    node.get_parent().resolve().filter(|&p| !ptr::eq(p, node))
+}
+
+fn left<L: AVLLinkable>(node: &L) -> Option<&L> {
+    node.get_left().resolve()
+}
+
+fn right<L: AVLLinkable>(node: &L) -> Option<&L> {
+    node.get_right().resolve()
+}
+
+fn same<L>(a: Option<&L>, b: Option<&L>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => ptr::eq(a, b),
+        (None, None) => true,
+        _ => false
+    }
+}
+
+fn minimum<L: AVLLinkable>(mut node: &L) -> &L {
+    while let Some(l) = left(node) {
+        node = l;
+    }
+    node
+}
+
+fn maximum<L: AVLLinkable>(mut node: &L) -> &L {
+    while let Some(r) = right(node) {
+        node = r;
+    }
+    node
+}
+
+fn successor<L: AVLLinkable>(node: &L) -> Option<&L> {
+    if let Some(r) = right(node) {
+        return Some(minimum(r));
+    }
+    let mut node = node;
+    while let Some(p) = parent(node) {
+        if same(left(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+fn predecessor<L: AVLLinkable>(node: &L) -> Option<&L> {
+    if let Some(l) = left(node) {
+        return Some(maximum(l));
+    }
+    let mut node = node;
+    while let Some(p) = parent(node) {
+        if same(right(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+fn link_of<L: AVLLinkable>(node: Option<&L>) -> Rawlink<L> {
+    node.map_or(Rawlink::none(), Rawlink::some)
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// AVLTree impls
+
+impl<P, T, S, L> AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    /// Creates an empty `AVLTree`
+    #[inline]
+    pub fn new() -> AVLTree<P, T, S, L> {
+        AVLTree { length: 0, root: Rawlink::none(),
+                 _marker: PhantomData, _marker2: PhantomData,
+                 _marker3: PhantomData}
+    }
+
+    /// Provides a double-ended iterator over the elements in order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        let root = self.root.resolve();
+        Iter{nelem: self.length,
+             head: link_of(root.map(minimum)),
+             tail: link_of(root.map(maximum)),
+             _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `AVLTree` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of elements in the `AVLTree`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all elements from the `AVLTree`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = AVLTree::new()
+    }
+
+    /// Provides a reference to the smallest element, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    #[inline]
+    pub fn first(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { minimum(root).container_of() }.get_val()
+        })
+    }
+
+    /// Provides a reference to the largest element, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    #[inline]
+    pub fn last(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { maximum(root).container_of() }.get_val()
+        })
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let node = minimum(root);
+            self.delete(node);
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Removes the largest element and returns it, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let node = maximum(root);
+            self.delete(node);
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Unlinks `elt` from the tree and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) time. In debug builds the
+    /// parent links are followed to check that `elt` belongs to this tree.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this tree. Removing an element of another tree would leave
+    /// that tree with an incorrect length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::AVLTree;
+    ///
+    /// define_avltree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t = AVLTree::new();
+    /// let two = Box::new(MyI32::new(2));
+    /// let ptr: *const MyI32 = &*two;
+    /// t.insert(Box::new(MyI32::new(1)));
+    /// t.insert(two);
+    /// t.insert(Box::new(MyI32::new(3)));
+    ///
+    /// assert_eq!(unsafe { t.remove(&*ptr) }, Box::new(MyI32::new(2)));
+    /// assert_eq!(t.iter().cloned().collect::<Vec<_>>(), [1, 3]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links();
+        assert!(links.get_parent().resolve().is_some(),
+                "Cannot remove an element which is not in a tree");
+        debug_assert!(self.contains_links(links),
+                      "Cannot remove an element of another tree");
+        self.delete(links);
+        T::from_raw(links.container_of() as *const S as *mut S)
+    }
+
+    /// Climbs to the root from `links` to check it is in this tree
+    fn contains_links(&self, links: &L) -> bool {
+        let mut node = links;
+        while let Some(p) = parent(node) {
+            node = p;
+        }
+        same(self.root.resolve(), Some(node))
+    }
+
+    /// Replaces the subtree rooted at `old` with the one rooted at `new`
+    fn transplant(&mut self, old: &L, new: Option<&L>) {
+        match parent(old) {
+            None => {
+                self.root = link_of(new);
+                if let Some(n) = new {
+                    n.set_parent(Rawlink::some(n));
+                }
+            }
+            Some(p) => {
+                if same(left(p), Some(old)) {
+                    p.set_left(link_of(new));
+                } else {
+                    p.set_right(link_of(new));
+                }
+                if let Some(n) = new {
+                    n.set_parent(Rawlink::some(p));
+                }
+            }
+        }
+    }
+
+    fn rotate_left(&mut self, x: &L) {
+        let y = right(x).unwrap();
+        x.set_right(y.get_left());
+        if let Some(b) = left(y) {
+            b.set_parent(Rawlink::some(x));
+        }
+        self.transplant(x, Some(y));
+        y.set_left(Rawlink::some(x));
+        x.set_parent(Rawlink::some(y));
+
+        let x_bal = x.get_balance() - 1 - y.get_balance().max(0);
+        x.set_balance(x_bal);
+        y.set_balance(y.get_balance() - 1 + x_bal.min(0));
+    }
+
+    fn rotate_right(&mut self, x: &L) {
+        let y = left(x).unwrap();
+        x.set_left(y.get_right());
+        if let Some(b) = right(y) {
+            b.set_parent(Rawlink::some(x));
+        }
+        self.transplant(x, Some(y));
+        y.set_right(Rawlink::some(x));
+        x.set_parent(Rawlink::some(y));
+
+        let x_bal = x.get_balance() + 1 - y.get_balance().min(0);
+        x.set_balance(x_bal);
+        y.set_balance(y.get_balance() + 1 + x_bal.max(0));
+    }
+
+    /// Rotates the subtree at `node`, whose balance is -2 or 2, and returns
+    /// its new root
+    fn rebalance<'a>(&mut self, node: &'a L) -> &'a L {
+        if node.get_balance() > 0 {
+            let r = right(node).unwrap();
+            if r.get_balance() < 0 {
+                self.rotate_right(r);
+            }
+            self.rotate_left(node);
+        } else {
+            let l = left(node).unwrap();
+            if l.get_balance() > 0 {
+                self.rotate_left(l);
+            }
+            self.rotate_right(node);
+        }
+        parent(node).unwrap()
+    }
+
+    /// Updates the balance of the ancestors of `node`, which was just linked
+    /// as a leaf
+    fn insert_retrace(&mut self, mut node: &L) {
+        while let Some(p) = parent(node) {
+            let grew_left = same(left(p), Some(node));
+            p.set_balance(p.get_balance() + if grew_left { -1 } else { 1 });
+            match p.get_balance() {
+                0 => break,
+                -1 | 1 => node = p,
+                _ => {
+                    // the rotation restores the height the subtree had
+                    // before the insertion
+                    self.rebalance(p);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Updates the balance of `node` and its ancestors after the subtree on
+    /// the left (if `shrunk_left`) or right of `node` lost one level
+    fn delete_retrace(&mut self, mut node: &L, mut shrunk_left: bool) {
+        loop {
+            node.set_balance(node.get_balance() +
+                             if shrunk_left { 1 } else { -1 });
+            let sub = match node.get_balance() {
+                -1 | 1 => break,
+                0 => node,
+                _ => {
+                    let sub = self.rebalance(node);
+                    if sub.get_balance() != 0 {
+                        break;
+                    }
+                    sub
+                }
+            };
+            match parent(sub) {
+                None => break,
+                Some(p) => {
+                    shrunk_left = same(left(p), Some(sub));
+                    node = p;
+                }
+            }
+        }
+    }
+
+    /// Unlinks `node` from the tree and rebalances it
+    fn delete(&mut self, node: &L) {
+        // the node whose subtree lost a level, and on which side
+        let retrace = match (left(node), right(node)) {
+            (None, child) | (child, None) => {
+                let p = parent(node);
+                let from_left = p.is_some_and(|p| same(left(p), Some(node)));
+                self.transplant(node, child);
+                p.map(|p| (p, from_left))
+            }
+            (Some(l), Some(r)) => {
+                let next = minimum(r);
+                let retrace = if ptr::eq(r, next) {
+                    (next, false)
+                } else {
+                    let p = parent(next).unwrap();
+                    self.transplant(next, right(next));
+                    next.set_right(Rawlink::some(r));
+                    r.set_parent(Rawlink::some(next));
+                    (p, true)
+                };
+                self.transplant(node, Some(next));
+                next.set_left(Rawlink::some(l));
+                l.set_parent(Rawlink::some(next));
+                next.set_balance(node.get_balance());
+                Some(retrace)
+            }
+        };
+        node.set_parent(Rawlink::none());
+        node.set_left(Rawlink::none());
+        node.set_right(Rawlink::none());
+        node.set_balance(0);
+        self.length -= 1;
+
+        if let Some((p, from_left)) = retrace {
+            self.delete_retrace(p, from_left);
+        }
+    }
+}
+
+impl<P, T, S, L> AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    /// Inserts an element into the tree.
+    ///
+    /// An element equal to some already in the tree is placed after them.
+    ///
+    /// This operation should compute in O(log n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::AVLTree;
+    ///
+    /// define_avltree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t = AVLTree::new();
+    /// t.insert(Box::new(MyI32::new(3)));
+    /// t.insert(Box::new(MyI32::new(1)));
+    /// t.insert(Box::new(MyI32::new(2)));
+    /// assert_eq!(t.iter().cloned().collect::<Vec<_>>(), [1, 2, 3]);
+    /// assert_eq!(t.iter().rev().cloned().collect::<Vec<_>>(), [3, 2, 1]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn insert(&mut self, elt: T) {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        let val = elt.get_val();
+        let links = elt.get_links();
+        let mut cur = self.root.resolve();
+        let mut parent = None;
+        let mut go_left = false;
+        while let Some(node) = cur {
+            parent = Some(node);
+            go_left = val < unsafe { node.container_of() }.get_val();
+            cur = if go_left { left(node) } else { right(node) };
+        }
+        match parent {
+            None => {
+                links.set_parent(Rawlink::some(links));
+                self.root = Rawlink::some(links);
+            }
+            Some(p) => {
+                links.set_parent(Rawlink::some(p));
+                if go_left {
+                    p.set_left(Rawlink::some(links));
+                } else {
+                    p.set_right(Rawlink::some(links));
+                }
+            }
+        }
+        links.set_balance(0);
+        self.length += 1;
+        self.insert_retrace(links);
+        unsafe { elt.take() };
+    }
+
+    /// Provides a reference to an element equal to `key`, or `None` if there
+    /// is none.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn find<Q>(&self, key: &Q) -> Option<&P>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        self.lower_bound(key).filter(|val| (*val).borrow() == key)
+    }
+
+    /// Provides a reference to the first element which is not less than
+    /// `key`, or `None` if there is none.
+    ///
+    /// This operation should compute in O(log n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::AVLTree;
+    ///
+    /// define_avltree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let t: AVLTree<_, _, _, MyLink> =
+    ///     [10, 20, 30].iter().map(|&i| Box::new(MyI32::new(i))).collect();
+    /// assert_eq!(t.lower_bound(&20), Some(&20));
+    /// assert_eq!(t.upper_bound(&20), Some(&30));
+    /// assert_eq!(t.lower_bound(&15), Some(&20));
+    /// assert_eq!(t.upper_bound(&30), None);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn lower_bound<Q>(&self, key: &Q) -> Option<&P>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        self.bound(|val| val.borrow() >= key)
+    }
+
+    /// Provides a reference to the first element which is greater than `key`,
+    /// or `None` if there is none.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn upper_bound<Q>(&self, key: &Q) -> Option<&P>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        self.bound(|val| val.borrow() > key)
+    }
+
+    /// Finds the first element for which `past` holds, given that it holds
+    /// for every element after it
+    fn bound<F: Fn(&P) -> bool>(&self, past: F) -> Option<&P> {
+        let mut cur = self.root.resolve();
+        let mut found = None;
+        while let Some(node) = cur {
+            let val = unsafe { node.container_of() }.get_val();
+            if past(val) {
+                found = Some(val);
+                cur = left(node);
+            } else {
+                cur = right(node);
+            }
+        }
+        found
+    }
+}
+
+impl<P, T, S, L> Default for AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> AVLTree<P, T, S, L> {
+        AVLTree::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl<P, T, S, L> Drop for AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AVLLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        // unlink the leaves one at a time, without rebalancing
+        let mut cur = self.root.take().resolve();
+        while let Some(node) = cur {
+            if let Some(l) = left(node) {
+                cur = Some(l);
+                continue;
+            }
+            if let Some(r) = right(node) {
+                cur = Some(r);
+                continue;
+            }
+            cur = parent(node);
+            if let Some(p) = cur {
+                if same(left(p), Some(node)) {
+                    p.set_left(Rawlink::none());
+                } else {
+                    p.set_right(Rawlink::none());
+                }
+            }
+            node.set_parent(Rawlink::none());
+            // hand back the reference held by the tree
+            drop(unsafe {
+                T::from_raw(node.container_of() as *const S as *mut S)
+            });
+        }
+        self.length = 0;
+    }
+}
+
+impl<P, T, S, L> Extend<T> for AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.insert(elt); }
+    }
+}
+
+impl<P, T, S, L> FromIterator<T> for AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> AVLTree<P, T, S, L> {
+        let mut ret = AVLTree::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L> IntoIterator for AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L>;
+
+    /// Consumes the tree into an in-order iterator yielding elements by value.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L> {
+        IntoIter{tree: self}
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: AVLLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+unsafe impl<P, T, S, L> Send for AVLTree<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: AVLLinkable<Container=T::Target>
+{}
+
+// AVLLinks impls
+
+unsafe impl<L: AVLLinkable> Send for AVLLinks<L> {}
+
+impl<L: AVLLinkable> Clone for AVLLinks<L>
+{
+    /// A clone is not in any tree, even if the original is
+    fn clone(&self) -> AVLLinks<L> {
+        Default::default()
+    }
+}
+
+impl<L: AVLLinkable> Drop for AVLLinks<L>
+{
+    fn drop(&mut self) {
+        assert!(self.parent.get().resolve().is_none());
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, L: AVLLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            head: self.head,
+            tail: self.tail,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: AVLLinkable<Container=T> + 'a> Iterator
+    for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve().unwrap();
+        self.nelem -= 1;
+        self.head = link_of(successor(head));
+        let ret = unsafe { head.container_of() }.get_val();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: AVLLinkable<Container=T> + 'a>
+    DoubleEndedIterator for Iter<'a, P, T, L>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let tail = self.tail.resolve().unwrap();
+        self.nelem -= 1;
+        self.tail = link_of(predecessor(tail));
+        let ret = unsafe { tail.container_of() }.get_val();
+        Some(ret)
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: AVLLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+// IntoIter impls
+
+impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.tree.pop_first() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.tree.length, Some(self.tree.length))
+    }
+}
+
+impl<P, T, S, L> DoubleEndedIterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AVLLinkable<Container=T::Target>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<T> { self.tree.pop_last() }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::vec::Vec;
+    use core::ptr;
+    use rand::{self, Rng};
+    use super::{AVLTree, OwningPointer, Node, AVLLinkable, AVLLinkOps};
+    use super::{left, right, parent};
+
+    define_avltree_element!(MyI32 = i32 : MyLink);
+
+    type Tree = AVLTree<i32, Box<MyI32>, MyI32, MyLink>;
+
+    /// Checks the parent links, ordering and balance factors of the subtree
+    /// at `node`, returning its size and height
+    fn check_node<P, S, L>(node: &L) -> (usize, i32)
+        where P: Ord,
+              S: Node<P, L>,
+              L: AVLLinkable<Container=S>
+    {
+        let val = unsafe { node.container_of() }.get_val();
+        let mut size = 1;
+        let mut heights = [0; 2];
+        for (i, child) in [left(node), right(node)].iter().enumerate() {
+            if let Some(child) = *child {
+                assert!(ptr::eq(parent(child).unwrap(), node));
+                let child_val = unsafe { child.container_of() }.get_val();
+                if i == 0 {
+                    assert!(child_val <= val);
+                } else {
+                    assert!(child_val >= val);
+                }
+                let (s, h) = check_node(child);
+                size += s;
+                heights[i] = h;
+            }
+        }
+        assert_eq!(node.get_balance() as i32, heights[1] - heights[0]);
+        assert!(node.get_balance().abs() <= 1);
+        (size, heights[0].max(heights[1]) + 1)
+    }
+
+    pub fn check_links<P, T, S, L>(tree: &AVLTree<P, T, S, L>)
+        where T: OwningPointer<Target=S>,
+              P: Ord,
+              S: Node<P, L>,
+              L: AVLLinkable<Container=S>,
+    {
+        match tree.root.resolve() {
+            None => assert_eq!(tree.length, 0),
+            Some(root) => {
+                assert!(ptr::eq(root.get_parent().resolve().unwrap(), root));
+                assert_eq!(check_node(root).0, tree.length);
+            }
+        }
+    }
+
+    fn tree_from(v: &[i32]) -> Tree {
+        let tree: Tree = v.iter().map(|&i| Box::new(MyI32::new(i))).collect();
+        check_links(&tree);
+        tree
+    }
+
+    fn to_vec(tree: &Tree) -> Vec<i32> {
+        tree.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut t = Tree::new();
+        assert!(t.is_empty());
+        assert_eq!(t.first(), None);
+        assert_eq!(t.pop_first(), None);
+        t.insert(Box::new(MyI32::new(2)));
+        t.insert(Box::new(MyI32::new(1)));
+        t.insert(Box::new(MyI32::new(3)));
+        check_links(&t);
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.first(), Some(&1));
+        assert_eq!(t.last(), Some(&3));
+        assert_eq!(t.pop_first(), Some(Box::new(MyI32::new(1))));
+        assert_eq!(t.pop_last(), Some(Box::new(MyI32::new(3))));
+        check_links(&t);
+        assert_eq!(to_vec(&t), [2]);
+        t.clear();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_iter() {
+        let t = tree_from(&[5, 3, 8, 1, 4, 7, 9, 2, 6]);
+        assert_eq!(to_vec(&t), [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(t.iter().rev().cloned().collect::<Vec<_>>(),
+                   [9, 8, 7, 6, 5, 4, 3, 2, 1]);
+        let mut it = t.iter();
+        assert_eq!(it.len(), 9);
+        assert_eq!(it.next(), Some(&1));
+        assert_eq!(it.next_back(), Some(&9));
+        assert_eq!(it.len(), 7);
+        assert_eq!(it.cloned().collect::<Vec<_>>(), [2, 3, 4, 5, 6, 7, 8]);
+        let v: Vec<_> = t.into_iter().rev().map(|e| *e.get_val()).collect();
+        assert_eq!(v, [9, 8, 7, 6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_find() {
+        let t = tree_from(&[10, 20, 20, 30]);
+        assert_eq!(t.find(&20), Some(&20));
+        assert_eq!(t.find(&25), None);
+        assert_eq!(t.lower_bound(&5), Some(&10));
+        assert_eq!(t.lower_bound(&20), Some(&20));
+        assert_eq!(t.upper_bound(&20), Some(&30));
+        assert_eq!(t.lower_bound(&31), None);
+        assert_eq!(t.upper_bound(&30), None);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let elts: Vec<_> = (0..6).map(|i| Rc::new(MyI32::new(i % 2))).collect();
+        let t: AVLTree<_, _, _, MyLink> = elts.iter().cloned().collect();
+        check_links(&t);
+        // equal elements are kept in insertion order
+        let ptrs: Vec<*const i32> = t.iter().map(|v| v as *const i32).collect();
+        let expect: Vec<*const i32> = [0, 2, 4, 1, 3, 5].iter()
+            .map(|&i| elts[i].get_val() as *const i32).collect();
+        assert_eq!(ptrs, expect);
+        drop(t);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut elts: Vec<*const MyI32> = Vec::new();
+        let mut t = Tree::new();
+        for i in 0..32 {
+            let e = Box::new(MyI32::new(i));
+            elts.push(&*e);
+            t.insert(e);
+        }
+        for i in (0..32).step_by(3) {
+            let e = unsafe { t.remove(&*elts[i]) };
+            assert_eq!(*e.get_val(), i as i32);
+            check_links(&t);
+        }
+        assert_eq!(t.len(), 21);
+        assert!(t.iter().all(|&v| v % 3 != 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_unlinked() {
+        let mut t = Tree::new();
+        t.insert(Box::new(MyI32::new(1)));
+        let e = MyI32::new(2);
+        unsafe { t.remove(&e) };
+    }
+
+    #[test]
+    fn test_sorted_insert() {
+        let v: Vec<i32> = (0..1023).collect();
+        let t = tree_from(&v);
+        // inserting in order yields a perfectly balanced tree
+        assert_eq!(check_node(t.root.resolve().unwrap()).1, 10);
+        assert_eq!(to_vec(&t), v);
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut t = Tree::new();
+            let mut v: Vec<i32> = Vec::new();
+            let mut elts: Vec<*const MyI32> = Vec::new();
+            for _ in 0..200 {
+                match rng.gen_range(0..4) {
+                    0 if !elts.is_empty() => {
+                        let i = rng.gen_range(0..elts.len());
+                        let e = unsafe { t.remove(&*elts.swap_remove(i)) };
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    _ => {
+                        let val = rng.gen_range(0..50);
+                        let e = Box::new(MyI32::new(val));
+                        elts.push(&*e);
+                        t.insert(e);
+                        v.push(val);
+                    }
+                }
+                check_links(&t);
+            }
+            v.sort();
+            assert_eq!(to_vec(&t), v);
+        }
+    }
+}
@@ -0,0 +1,1007 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive array-backed d-ary heap.
+//!
+//! The `DaryHeap` is a min-heap: `pop_min` returns its smallest element. It
+//! keeps pointers to its elements in an array of slots laid out as a complete
+//! tree in which every node has `D` children, `4` by default. The slots are
+//! supplied by the caller, as a borrowed slice or a fixed-size array, so the
+//! heap never allocates and its capacity is that of its slots.
+//!
+//! The link of each element records the slot holding it, so an element can be
+//! removed or have its value changed given only a pointer to it. `push`,
+//! `pop_min`, `remove` and `update` take O(log n) time, `peek_min` and
+//! `contains` take O(1) time.
+use core::cell::Cell;
+use core::cmp;
+use core::fmt;
+use core::marker::{PhantomData, PhantomPinned};
+use core::mem::{self, ManuallyDrop};
+use core::ops::DerefMut;
+use core::ptr;
+use core::slice;
+use super::linked_list::OwningPointer;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into a `DaryHeap`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_dary_heap_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: DaryLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into a `DaryHeap`
+///
+/// # Safety
+///
+/// The trait is unsafe because any implementation must impl Drop to check
+/// that the element is not in a heap, and `offset()` must be the offset of
+/// the link within its `Container`.
+pub unsafe trait DaryLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &DaryLinks;
+    fn get_links_mut(&mut self) -> &mut DaryLinks;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+}
+
+/// The array of slots backing a `DaryHeap`
+///
+/// # Safety
+///
+/// `slots` and `slots_mut` must always return the same slice, whose contents
+/// are only changed through `slots_mut`, and the slots must be safe to send to
+/// another thread along with the heap using them.
+pub unsafe trait HeapSlots {
+    fn slots(&self) -> &[*mut ()];
+    fn slots_mut(&mut self) -> &mut [*mut ()];
+}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into a `DaryHeap`
+///
+/// `define_dary_heap_element!(MyI32 = i32 : MyLink)` defines an element
+/// `MyI32` holding an `i32` and a link type `MyLink` used to put it in a
+/// heap.
+#[macro_export]
+macro_rules! define_dary_heap_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_dary_heap_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_dary_heap_link!($link = $elt);
+        impl_dary_heap_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_dary_heap_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_dary_heap_link!($link = $elt);
+        impl_dary_heap_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_dary_heap_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::dary_heap::DaryLinks);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::dary_heap::DaryLinks);
+    );
+}
+
+/// Implements `DaryLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_dary_heap_link {
+    ($link:ident = $elt:ident) => (
+        impl_dary_heap_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::dary_heap::DaryLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::dary_heap::DaryLinks {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::dary_heap::DaryLinks {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_dary_heap_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_dary_heap_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::dary_heap::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive array-backed d-ary heap
+///
+/// `B` holds the slots of the heap and `D` is the number of children of each
+/// node, at least `2`.
+pub struct DaryHeap<P, T, S, L, B, const D: usize = 4>
+    where T: OwningPointer<Target=S>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    length: usize,
+    slots: B,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>,
+    _marker4: PhantomData<L>
+}
+
+#[derive(Default, Debug)]
+pub struct DaryLinks
+{
+    /// The slot holding the element, `None` when not in a heap
+    index: Cell<Option<usize>>,
+    _pin: PhantomPinned
+}
+
+/// An iterator over references to the items of a `DaryHeap`, in no
+/// particular order
+pub struct Iter<'a, P: 'a, S, L: DaryLinkable<Container=S>> {
+    slots: slice::Iter<'a, *mut ()>,
+    _marker: PhantomData<&'a P>,
+    _marker2: PhantomData<L>
+}
+
+/// An iterator yielding the elements of a `DaryHeap` by value, smallest first
+pub struct IntoIter<P, T, S, L, B, const D: usize>
+    where T: OwningPointer<Target=S>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    heap: DaryHeap<P, T, S, L, B, D>
+}
+
+//////////////////////
+// Heap Navigation  //
+//////////////////////
+
+/// Returns the links a slot points to
+///
+/// # Safety
+///
+/// `slot` must point to the links of an element in a heap.
+unsafe fn links_of<'a, L: DaryLinkable + 'a>(slot: *mut ()) -> &'a L {
+    &*(slot as *const L)
+}
+
+/// Returns the value of the element a slot points to
+///
+/// # Safety
+///
+/// `slot` must point to the links of an element in a heap.
+unsafe fn value<'a, P, S, L>(slot: *mut ()) -> &'a P
+    where P: 'a,
+          S: Node<P, L> + 'a,
+          L: DaryLinkable<Container=S> + 'a
+{
+    links_of::<L>(slot).container_of().get_val()
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// DaryHeap impls
+
+impl<P, T, S, L, B, const D: usize> DaryHeap<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    /// Creates an empty `DaryHeap` keeping its elements in `slots`
+    ///
+    /// The previous contents of `slots` are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `D` is less than `2`.
+    #[inline]
+    pub fn new(slots: B) -> DaryHeap<P, T, S, L, B, D> {
+        assert!(D >= 2, "A d-ary heap needs at least two children per node");
+        DaryHeap { length: 0, slots,
+                   _marker: PhantomData, _marker2: PhantomData,
+                   _marker3: PhantomData, _marker4: PhantomData }
+    }
+
+    /// Provides an iterator over the elements, in no particular order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        Iter { slots: self.slots.slots()[..self.length].iter(),
+               _marker: PhantomData, _marker2: PhantomData }
+    }
+
+    /// Returns `true` if the `DaryHeap` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of elements in the `DaryHeap`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns the number of elements the `DaryHeap` can hold, the number of
+    /// its slots.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.slots().len()
+    }
+
+    /// Removes all elements from the `DaryHeap`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.unlink_all()
+    }
+
+    /// Provides a reference to the smallest element, or `None` if the heap is
+    /// empty.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn peek_min(&self) -> Option<&P> {
+        self.iter().next()
+    }
+
+    /// Returns `true` if `elt` is in this heap
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn contains(&self, elt: &S) -> bool {
+        self.position(elt).is_some()
+    }
+
+    /// Returns the slot holding `elt`, if it is in this heap
+    fn position(&self, elt: &S) -> Option<usize> {
+        let links = elt.get_links();
+        let index = links.get_links().index.get()?;
+        let slot = links as *const L as *mut ();
+        (index < self.length && self.slots.slots()[index] == slot).then_some(index)
+    }
+}
+
+impl<P, T, S, L, B, const D: usize> DaryHeap<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    fn less(a: *mut (), b: *mut ()) -> bool {
+        unsafe { value::<P, S, L>(a) < value::<P, S, L>(b) }
+    }
+
+    /// Stores `slot` at `index`, recording the index in its links
+    fn place(&mut self, index: usize, slot: *mut ()) {
+        self.slots.slots_mut()[index] = slot;
+        unsafe { links_of::<L>(slot) }.get_links().index.set(Some(index));
+    }
+
+    /// Moves the element at `index` up past its greater ancestors, returning
+    /// where it ends up
+    fn sift_up(&mut self, mut index: usize) -> usize {
+        let slot = self.slots.slots()[index];
+        while index > 0 {
+            let parent = (index - 1) / D;
+            let p = self.slots.slots()[parent];
+            if !Self::less(slot, p) {
+                break;
+            }
+            self.place(index, p);
+            index = parent;
+        }
+        self.place(index, slot);
+        index
+    }
+
+    /// Moves the element at `index` down past its smaller descendants
+    fn sift_down(&mut self, mut index: usize) {
+        let slot = self.slots.slots()[index];
+        loop {
+            let first = index * D + 1;
+            if first >= self.length {
+                break;
+            }
+            let slots = self.slots.slots();
+            let mut min = first;
+            for c in first + 1..cmp::min(first + D, self.length) {
+                if Self::less(slots[c], slots[min]) {
+                    min = c;
+                }
+            }
+            let m = slots[min];
+            if !Self::less(m, slot) {
+                break;
+            }
+            self.place(index, m);
+            index = min;
+        }
+        self.place(index, slot);
+    }
+
+    /// Unlinks the element at `index`, filling its slot with the last one
+    fn remove_at(&mut self, index: usize) -> T {
+        let slot = self.slots.slots()[index];
+        self.length -= 1;
+        if index != self.length {
+            let last = self.slots.slots()[self.length];
+            self.place(index, last);
+            if self.sift_up(index) == index {
+                self.sift_down(index);
+            }
+        }
+        self.slots.slots_mut()[self.length] = ptr::null_mut();
+        let links = unsafe { links_of::<L>(slot) };
+        links.get_links().index.set(None);
+        unsafe { T::from_raw(links.container_of() as *const S as *mut S) }
+    }
+
+    /// Adds an element to the heap, or hands it back as an error if all
+    /// slots are taken.
+    ///
+    /// This operation should compute in O(log n) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element is already in a heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use core::ptr;
+    /// use intrusive_containers::DaryHeap;
+    ///
+    /// define_dary_heap_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut h: DaryHeap<_, Box<MyI32>, _, _, _> =
+    ///     DaryHeap::new([ptr::null_mut(); 2]);
+    /// assert!(h.push(Box::new(MyI32::new(3))).is_ok());
+    /// assert!(h.push(Box::new(MyI32::new(1))).is_ok());
+    /// assert_eq!(h.push(Box::new(MyI32::new(2))), Err(Box::new(MyI32::new(2))));
+    /// assert_eq!(h.peek_min(), Some(&1));
+    /// assert_eq!(h.len(), 2);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn push(&mut self, elt: T) -> Result<(), T> {
+        let links = elt.get_links();
+        assert!(links.get_links().index.get().is_none(),
+                "Cannot push an element which is already in a heap");
+        if self.length == self.capacity() {
+            return Err(elt);
+        }
+        self.length += 1;
+        self.place(self.length - 1, links as *const L as *mut ());
+        self.sift_up(self.length - 1);
+        unsafe { elt.take() };
+        Ok(())
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the heap is
+    /// empty. Of several smallest elements, any one may be returned.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_min(&mut self) -> Option<T> {
+        if self.length == 0 {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    /// Unlinks `elt` from the heap and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in this heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use core::ptr;
+    /// use intrusive_containers::DaryHeap;
+    ///
+    /// define_dary_heap_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut slots = [ptr::null_mut(); 8];
+    /// let mut h: DaryHeap<_, Box<MyI32>, _, _, _> = DaryHeap::new(&mut slots[..]);
+    /// let two = Box::new(MyI32::new(2));
+    /// let ptr: *const MyI32 = &*two;
+    /// h.push(Box::new(MyI32::new(1))).unwrap();
+    /// h.push(two).unwrap();
+    /// h.push(Box::new(MyI32::new(3))).unwrap();
+    ///
+    /// assert_eq!(h.remove(unsafe { &*ptr }), Box::new(MyI32::new(2)));
+    /// assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(1))));
+    /// assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(3))));
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn remove(&mut self, elt: &S) -> T {
+        let index = self.position(elt)
+            .expect("Cannot remove an element which is not in this heap");
+        self.remove_at(index)
+    }
+
+    /// Changes the value of the element `elt` points to by calling `f` on
+    /// it, then restores the order of the heap.
+    ///
+    /// The value may be made greater or smaller. This operation should
+    /// compute in O(log n) time.
+    ///
+    /// # Safety
+    ///
+    /// `elt` must point to a live element, which need not be in this heap.
+    /// It is taken as a pointer rather than a reference because no reference
+    /// to the element may be held while its value is changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in this heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use core::ptr;
+    /// use intrusive_containers::DaryHeap;
+    ///
+    /// define_dary_heap_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut h: DaryHeap<_, Box<MyI32>, _, _, _> =
+    ///     DaryHeap::new([ptr::null_mut(); 4]);
+    /// let one = Box::new(MyI32::new(1));
+    /// let ptr: *const MyI32 = &*one;
+    /// h.push(one).unwrap();
+    /// h.push(Box::new(MyI32::new(5))).unwrap();
+    ///
+    /// unsafe { h.update(ptr, |v| *v = 9) };
+    /// assert_eq!(h.peek_min(), Some(&5));
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn update<F>(&mut self, elt: *const S, f: F)
+        where T: DerefMut,
+              F: FnOnce(&mut P)
+    {
+        let index = self.position(&*elt)
+            .expect("Cannot update an element which is not in this heap");
+        // borrow the element back from the heap to change its value, without
+        // dropping it should `f` panic
+        let mut owner = ManuallyDrop::new(T::from_raw(elt as *mut S));
+        f(owner.get_val_mut());
+        if self.sift_up(index) == index {
+            self.sift_down(index);
+        }
+    }
+}
+
+impl<P, T, S, L, B, const D: usize> DaryHeap<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    /// Hands back every element, leaving the heap empty
+    fn unlink_all(&mut self) {
+        let length = mem::replace(&mut self.length, 0);
+        for slot in &mut self.slots.slots_mut()[..length] {
+            let links = unsafe { links_of::<L>(mem::replace(slot, ptr::null_mut())) };
+            links.get_links().index.set(None);
+            drop(unsafe {
+                T::from_raw(links.container_of() as *const S as *mut S)
+            });
+        }
+    }
+}
+
+impl<P, T, S, L, B, const D: usize> fmt::Debug for DaryHeap<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl<P, T, S, L, B, const D: usize> Drop for DaryHeap<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    fn drop(&mut self) {
+        self.unlink_all();
+    }
+}
+
+impl<P, T, S, L, B, const D: usize> IntoIterator for DaryHeap<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L, B, D>;
+
+    /// Consumes the heap into an iterator yielding elements by value,
+    /// smallest first.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L, B, D> {
+        IntoIter{heap: self}
+    }
+}
+
+impl<'a, P, T, S, L, B, const D: usize> IntoIterator for &'a DaryHeap<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: DaryLinkable<Container=T::Target> + 'a,
+          B: HeapSlots
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+unsafe impl<P, T, S, L, B, const D: usize> Send for DaryHeap<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S> + Send,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{}
+
+// DaryLinks impls
+
+impl Clone for DaryLinks
+{
+    /// A clone is not in any heap, even if the original is
+    fn clone(&self) -> DaryLinks {
+        Default::default()
+    }
+}
+
+impl Drop for DaryLinks
+{
+    fn drop(&mut self) {
+        assert!(self.index.get().is_none());
+    }
+}
+
+// HeapSlots impls
+
+unsafe impl HeapSlots for &mut [*mut ()] {
+    #[inline]
+    fn slots(&self) -> &[*mut ()] {
+        self
+    }
+
+    #[inline]
+    fn slots_mut(&mut self) -> &mut [*mut ()] {
+        self
+    }
+}
+
+unsafe impl<const N: usize> HeapSlots for [*mut (); N] {
+    #[inline]
+    fn slots(&self) -> &[*mut ()] {
+        self
+    }
+
+    #[inline]
+    fn slots_mut(&mut self) -> &mut [*mut ()] {
+        self
+    }
+}
+
+// Iter impls
+
+impl<'a, P, S, L: DaryLinkable<Container=S>> Clone for Iter<'a, P, S, L> {
+    fn clone(&self) -> Iter<'a, P, S, L> {
+        Iter {
+            slots: self.slots.clone(),
+            _marker: PhantomData,
+            _marker2: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, S: Node<P, L> + 'a, L: DaryLinkable<Container=S> + 'a> Iterator
+    for Iter<'a, P, S, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        self.slots.next().map(|&slot| unsafe { value::<P, S, L>(slot) })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<'a, P: 'a, S: Node<P, L> + 'a, L: DaryLinkable<Container=S> + 'a>
+    ExactSizeIterator for Iter<'a, P, S, L> {}
+
+// IntoIter impls
+
+impl<P, T, S, L, B, const D: usize> Iterator for IntoIter<P, T, S, L, B, D>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: DaryLinkable<Container=T::Target>,
+          B: HeapSlots
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.heap.pop_min() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.length, Some(self.heap.length))
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::format;
+    use std::rc::Rc;
+    use std::vec;
+    use std::vec::Vec;
+    use core::ptr;
+    use rand::{self, Rng};
+    use super::{DaryHeap, DaryLinkable, HeapSlots, Node, OwningPointer};
+    use super::{links_of, value};
+
+    define_dary_heap_element!(MyI32 = i32 : MyLink);
+
+    type Heap<'a, const D: usize> =
+        DaryHeap<i32, Box<MyI32>, MyI32, MyLink, &'a mut [*mut ()], D>;
+
+    /// Checks the recorded indices and the heap order of every slot
+    pub fn check_links<P, T, S, L, B, const D: usize>(heap: &DaryHeap<P, T, S, L, B, D>)
+        where T: OwningPointer<Target=S>,
+              P: Ord,
+              S: Node<P, L>,
+              L: DaryLinkable<Container=S>,
+              B: HeapSlots
+    {
+        let slots = &heap.slots.slots()[..heap.length];
+        for (i, &slot) in slots.iter().enumerate() {
+            let links = unsafe { links_of::<L>(slot) };
+            assert_eq!(links.get_links().index.get(), Some(i));
+            if i > 0 {
+                let parent = slots[(i - 1) / D];
+                assert!(unsafe { value::<P, S, L>(parent) <= value::<P, S, L>(slot) });
+            }
+        }
+        assert!(heap.length <= heap.capacity());
+    }
+
+    fn fill<const D: usize>(heap: &mut Heap<'_, D>, v: &[i32]) {
+        for &i in v {
+            heap.push(Box::new(MyI32::new(i))).unwrap();
+        }
+        check_links(heap);
+    }
+
+    fn drain<const D: usize>(heap: Heap<'_, D>) -> Vec<i32> {
+        heap.into_iter().map(|e| *e.get_val()).collect()
+    }
+
+    fn check_sort<const D: usize>(v: &[i32]) {
+        let mut slots = vec![ptr::null_mut(); v.len()];
+        let mut h = Heap::<D>::new(&mut slots[..]);
+        fill(&mut h, v);
+        let mut sorted = v.to_vec();
+        sorted.sort();
+        assert_eq!(drain(h), sorted);
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut slots = [ptr::null_mut(); 16];
+        let mut h = Heap::<4>::new(&mut slots[..]);
+        assert!(h.is_empty());
+        assert_eq!(h.capacity(), 16);
+        assert_eq!(h.peek_min(), None);
+        assert_eq!(h.pop_min(), None);
+        fill(&mut h, &[2, 1, 3]);
+        assert_eq!(h.len(), 3);
+        assert_eq!(h.peek_min(), Some(&1));
+        assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(1))));
+        check_links(&h);
+        let mut v: Vec<i32> = h.iter().cloned().collect();
+        v.sort();
+        assert_eq!(v, [2, 3]);
+        let s = format!("{:?}", h);
+        assert!(s == "{2, 3}" || s == "{3, 2}");
+        h.clear();
+        assert!(h.is_empty());
+        fill(&mut h, &[4]);
+    }
+
+    #[test]
+    fn test_sort() {
+        let v = [5, 3, 8, 1, 4, 7, 9, 2, 6, 3, 0, 5, 11, -2, 6, 10, 1];
+        check_sort::<2>(&v);
+        check_sort::<3>(&v);
+        check_sort::<4>(&v);
+        check_sort::<8>(&v);
+        check_sort::<32>(&v);
+    }
+
+    #[test]
+    fn test_full() {
+        let mut h: DaryHeap<_, Box<MyI32>, _, _, _, 2> =
+            DaryHeap::new([ptr::null_mut(); 3]);
+        for i in [3, 2, 1] {
+            h.push(Box::new(MyI32::new(i))).unwrap();
+        }
+        let e = h.push(Box::new(MyI32::new(0))).unwrap_err();
+        assert_eq!(e.get_val(), &0);
+        check_links(&h);
+        assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(1))));
+        h.push(e).unwrap();
+        check_links(&h);
+        assert_eq!(h.len(), h.capacity());
+        assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(0))));
+    }
+
+    #[test]
+    fn test_remove_update() {
+        let mut slots = [ptr::null_mut(); 10];
+        let mut h = Heap::<3>::new(&mut slots[..]);
+        let elts: Vec<*const MyI32> = (0..10).map(|i| {
+            let e = Box::new(MyI32::new(10 + i));
+            let ptr: *const MyI32 = &*e;
+            h.push(e).unwrap();
+            ptr
+        }).collect();
+        let e = h.remove(unsafe { &*elts[4] });
+        assert_eq!(e.get_val(), &14);
+        assert!(!h.contains(&e));
+        check_links(&h);
+        unsafe {
+            h.update(elts[7], |v| *v = 5);
+            check_links(&h);
+            h.update(elts[0], |v| *v = 30);
+            check_links(&h);
+            h.update(elts[9], |v| *v -= 1);
+            check_links(&h);
+        }
+        assert!(h.contains(unsafe { &*elts[0] }));
+        assert_eq!(drain(h), [5, 11, 12, 13, 15, 16, 18, 18, 30]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_foreign() {
+        let mut slots = [ptr::null_mut(); 4];
+        let mut other_slots = [ptr::null_mut(); 4];
+        let mut h = Heap::<4>::new(&mut slots[..]);
+        let mut other = Heap::<4>::new(&mut other_slots[..]);
+        let e = Box::new(MyI32::new(1));
+        let ptr: *const MyI32 = &*e;
+        other.push(e).unwrap();
+        fill(&mut h, &[1]);
+        assert!(!h.contains(unsafe { &*ptr }));
+        h.remove(unsafe { &*ptr });
+    }
+
+    #[test]
+    fn test_rc() {
+        let elts: Vec<_> = (0..6).map(|i| Rc::new(MyI32::new(i))).collect();
+        let mut h: DaryHeap<_, _, _, MyLink, _> = DaryHeap::new([ptr::null_mut(); 6]);
+        for e in &elts {
+            h.push(e.clone()).unwrap();
+        }
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        h.pop_min();
+        h.remove(&elts[3]);
+        assert_eq!(Rc::strong_count(&elts[0]), 1);
+        assert_eq!(Rc::strong_count(&elts[3]), 1);
+        drop(h);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut slots = vec![ptr::null_mut(); 64];
+            let mut h = Heap::<3>::new(&mut slots[..]);
+            let mut v: Vec<i32> = Vec::new();
+            let mut elts: Vec<*const MyI32> = Vec::new();
+            for _ in 0..300 {
+                match rng.gen_range(0..6) {
+                    0 if !elts.is_empty() => {
+                        let i = rng.gen_range(0..elts.len());
+                        let e = h.remove(unsafe { &*elts.swap_remove(i) });
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    1 if !elts.is_empty() => {
+                        let e = h.pop_min().unwrap();
+                        assert_eq!(Some(e.get_val()), v.iter().min());
+                        let ptr: *const MyI32 = &*e;
+                        elts.retain(|&p| p != ptr);
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    2 if !elts.is_empty() => {
+                        let e = elts[rng.gen_range(0..elts.len())];
+                        let old = *unsafe { &*e }.get_val();
+                        let new = old + rng.gen_range(-10..10);
+                        unsafe { h.update(e, |x| *x = new) };
+                        let pos = v.iter().position(|&x| x == old);
+                        v[pos.unwrap()] = new;
+                    }
+                    _ => {
+                        let val = rng.gen_range(0..50);
+                        let e = Box::new(MyI32::new(val));
+                        let ptr: *const MyI32 = &*e;
+                        match h.push(e) {
+                            Ok(()) => {
+                                elts.push(ptr);
+                                v.push(val);
+                            }
+                            Err(_) => assert_eq!(v.len(), 64),
+                        }
+                    }
+                }
+                check_links(&h);
+                assert_eq!(h.peek_min(), v.iter().min());
+            }
+            v.sort();
+            assert_eq!(drain(h), v);
+        }
+    }
+}
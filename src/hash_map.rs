@@ -0,0 +1,687 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive hash table.
+//!
+//! The `IntrusiveHashMap` chains its elements in buckets which are
+//! `LinkedList`s. The bucket array is supplied by the caller, as a
+//! `&mut [Bucket]` or a `Box<[Bucket]>`, so the table never allocates. It is
+//! never resized implicitly either: the caller decides when to `rehash` into a
+//! new bucket array.
+use core::borrow::BorrowMut;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::DerefMut;
+use core::slice;
+use super::linked_list::{self, LinkedList, Linkable, Node, OwningPointer};
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// Extracts the key by which elements are looked up in an `IntrusiveHashMap`
+///
+/// The key of an element must not change while it is in the table.
+pub trait KeyAdapter<P> {
+    type Key: Hash + Eq + ?Sized;
+
+    /// Returns the key of `val`
+    fn key<'a>(&self, val: &'a P) -> &'a Self::Key;
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// A bucket of an `IntrusiveHashMap`
+pub type Bucket<P, T, S, L> = LinkedList<P, T, S, L>;
+
+/// A `KeyAdapter` using the whole value as its key
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Identity;
+
+/// An intrusive hash table
+///
+/// `A` is the `KeyAdapter` giving the key of each element, `H` builds the
+/// hashers used on the keys and `B` holds the buckets.
+pub struct IntrusiveHashMap<P, T, S, L, A, H, B>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>,
+          B: BorrowMut<[Bucket<P, T, S, L>]>
+{
+    length: usize,
+    buckets: B,
+    adapter: A,
+    hasher: H,
+    _marker: PhantomData<Bucket<P, T, S, L>>
+}
+
+/// An iterator over references to the items of an `IntrusiveHashMap`
+pub struct Iter<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
+{
+    buckets: slice::Iter<'a, Bucket<P, T, S, L>>,
+    bucket: Option<linked_list::Iter<'a, P, S, L>>,
+    nelem: usize
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+impl<P: Hash + Eq> KeyAdapter<P> for Identity {
+    type Key = P;
+
+    #[inline]
+    fn key<'a>(&self, val: &'a P) -> &'a P {
+        val
+    }
+}
+
+// IntrusiveHashMap impls
+
+impl<P, T, S, L, A, H, B> IntrusiveHashMap<P, T, S, L, A, H, B>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>,
+          A: KeyAdapter<P>,
+          H: BuildHasher,
+          B: BorrowMut<[Bucket<P, T, S, L>]>
+{
+    /// Creates an empty `IntrusiveHashMap` using `buckets` as its bucket
+    /// array
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buckets` is empty or if any bucket holds elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use std::collections::hash_map::RandomState;
+    /// use intrusive_containers::LinkedList;
+    /// use intrusive_containers::hash_map::{IntrusiveHashMap, KeyAdapter};
+    ///
+    /// define_list_element!(Entry = (u32, &'static str) : EntryLink);
+    ///
+    /// struct ById;
+    ///
+    /// impl KeyAdapter<(u32, &'static str)> for ById {
+    ///     type Key = u32;
+    ///     fn key<'a>(&self, val: &'a (u32, &'static str)) -> &'a u32 {
+    ///         &val.0
+    ///     }
+    /// }
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut buckets: [LinkedList<_, Box<Entry>, _, EntryLink>; 4] =
+    ///     Default::default();
+    /// let mut map = IntrusiveHashMap::new(&mut buckets[..], ById,
+    ///                                     RandomState::new());
+    /// map.insert(Box::new(Entry::new((1, "one"))));
+    /// map.insert(Box::new(Entry::new((2, "two"))));
+    /// assert_eq!(map.find(&2), Some(&(2, "two")));
+    /// assert_eq!(map.find(&3), None);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn new(buckets: B, adapter: A, hasher: H)
+               -> IntrusiveHashMap<P, T, S, L, A, H, B> {
+        assert!(!buckets.borrow().is_empty(), "The bucket array is empty");
+        assert!(buckets.borrow().iter().all(|b| b.is_empty()),
+                "The bucket array holds elements");
+        IntrusiveHashMap { length: 0, buckets, adapter, hasher,
+                           _marker: PhantomData }
+    }
+
+    /// Returns `true` if the `IntrusiveHashMap` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of elements in the `IntrusiveHashMap`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns the number of buckets.
+    #[inline]
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.borrow().len()
+    }
+
+    /// Removes all elements from the `IntrusiveHashMap`.
+    ///
+    /// This operation should compute in O(n) time, where n is the number of
+    /// elements plus the number of buckets.
+    pub fn clear(&mut self) {
+        for bucket in self.buckets.borrow_mut().iter_mut() {
+            bucket.clear();
+        }
+        self.length = 0;
+    }
+
+    /// Provides an iterator over the elements, in no particular order.
+    pub fn iter(&self) -> Iter<'_, P, T, S, L> {
+        Iter { buckets: self.buckets.borrow().iter(), bucket: None,
+               nelem: self.length }
+    }
+
+    fn bucket_of(&self, key: &A::Key) -> usize {
+        (self.hasher.hash_one(key) % self.bucket_count() as u64) as usize
+    }
+
+    /// Returns the bucket holding `elt`, or `None` if no bucket does
+    ///
+    /// The bucket its key hashes to is looked at first, but the others are
+    /// looked at too, in case its key or hash changed since it was inserted.
+    fn bucket_holding(&self, elt: &S) -> Option<usize> {
+        let buckets = self.buckets.borrow();
+        let hashed = self.bucket_of(self.adapter.key(elt.get_val()));
+        let links = elt.get_links();
+        (hashed..buckets.len()).chain(0..hashed)
+            .find(|&idx| buckets[idx].contains_links(links))
+    }
+
+    /// Inserts an element into the table. If an element with the same key
+    /// was already in the table, it is removed and returned.
+    ///
+    /// This operation should compute in O(1) expected time.
+    pub fn insert(&mut self, elt: T) -> Option<T> {
+        let old = self.remove_key(self.adapter.key(elt.get_val()));
+        let idx = self.bucket_of(self.adapter.key(elt.get_val()));
+        self.buckets.borrow_mut()[idx].push_back(elt);
+        self.length += 1;
+        old
+    }
+
+    /// Provides a reference to the element whose key is `key`, or `None` if
+    /// there is none.
+    ///
+    /// This operation should compute in O(1) expected time.
+    pub fn find(&self, key: &A::Key) -> Option<&P> {
+        self.find_node(key).map(|elt| elt.get_val())
+    }
+
+    /// Returns the element whose key is `key`
+    pub(crate) fn find_node(&self, key: &A::Key) -> Option<&S> {
+        let idx = self.bucket_of(key);
+        let adapter = &self.adapter;
+        self.buckets.borrow()[idx].find_node(|val| adapter.key(val) == key)
+    }
+
+    /// Provides a mutable reference to the element whose key is `key`, or
+    /// `None` if there is none.
+    ///
+    /// The key of the element must not be changed: the element would stay in
+    /// the bucket of its former key, where neither `find` nor `remove_key`
+    /// looks for its new key. `remove` still finds it.
+    pub fn find_mut(&mut self, key: &A::Key) -> Option<&mut P>
+        where T: DerefMut
+    {
+        let idx = self.bucket_of(key);
+        let adapter = &self.adapter;
+        self.buckets.borrow_mut()[idx].iter_mut()
+            .find(|val| adapter.key(val) == key)
+    }
+
+    /// Removes the element whose key is `key` and returns it, or `None` if
+    /// there is none.
+    ///
+    /// This operation should compute in O(1) expected time.
+    pub fn remove_key(&mut self, key: &A::Key) -> Option<T> {
+        let idx = self.bucket_of(key);
+        let adapter = &self.adapter;
+        let mut cursor = self.buckets.borrow_mut()[idx].cursor_front_mut();
+        while let Some(val) = cursor.as_cursor().current() {
+            if adapter.key(val) == key {
+                self.length -= 1;
+                return cursor.remove_current();
+            }
+            cursor.move_next();
+        }
+        None
+    }
+
+    /// Unlinks `elt` from the table and returns ownership of it.
+    ///
+    /// The bucket `elt` is in is found by walking the bucket its key hashes
+    /// to, so this operation should compute in O(1) expected time. Should the
+    /// key or its hash have changed, the other buckets are walked too, in O(n)
+    /// time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no bucket of the table holds `elt`.
+    pub fn remove(&mut self, elt: &S) -> T {
+        let idx = self.bucket_holding(elt)
+            .expect("Cannot remove an element of another table");
+        let bucket = &mut self.buckets.borrow_mut()[idx];
+        let links = elt.get_links();
+        self.length -= 1;
+        // the bucket holds the element, and owns it through `T`
+        unsafe {
+            bucket.unlink(links);
+            T::from_raw(links.container_of() as *const S as *mut S)
+        }
+    }
+
+    /// Moves every element into `buckets`, which become the bucket array of
+    /// the table, and returns the previous bucket array, now empty.
+    ///
+    /// This operation should compute in O(n) time, where n is the number of
+    /// elements plus the number of old buckets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buckets` is empty or if any bucket holds elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use std::collections::hash_map::RandomState;
+    /// use intrusive_containers::LinkedList;
+    /// use intrusive_containers::hash_map::{IntrusiveHashMap, Identity};
+    ///
+    /// define_list_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// fn buckets(n: usize) -> Box<[LinkedList<i32, Box<MyI32>, MyI32, MyLink>]> {
+    ///     (0..n).map(|_| LinkedList::new()).collect()
+    /// }
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut map = IntrusiveHashMap::new(buckets(1), Identity,
+    ///                                     RandomState::new());
+    /// for i in 0..16 {
+    ///     map.insert(Box::new(MyI32::new(i)));
+    /// }
+    /// let old = map.rehash(buckets(16));
+    /// assert_eq!(old.len(), 1);
+    /// assert_eq!(map.bucket_count(), 16);
+    /// assert_eq!(map.len(), 16);
+    /// assert_eq!(map.find(&7), Some(&7));
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn rehash(&mut self, buckets: B) -> B {
+        assert!(!buckets.borrow().is_empty(), "The bucket array is empty");
+        assert!(buckets.borrow().iter().all(|b| b.is_empty()),
+                "The bucket array holds elements");
+        let mut old = mem::replace(&mut self.buckets, buckets);
+        for bucket in old.borrow_mut().iter_mut() {
+            while let Some(elt) = bucket.pop_front() {
+                let idx = self.bucket_of(self.adapter.key(elt.get_val()));
+                self.buckets.borrow_mut()[idx].push_back(elt);
+            }
+        }
+        old
+    }
+}
+
+impl<P, T, S, L, A, H, B> fmt::Debug for IntrusiveHashMap<P, T, S, L, A, H, B>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>,
+          A: KeyAdapter<P>,
+          H: BuildHasher,
+          B: BorrowMut<[Bucket<P, T, S, L>]>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl<P, T, S, L, A, H, B> Drop for IntrusiveHashMap<P, T, S, L, A, H, B>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>,
+          B: BorrowMut<[Bucket<P, T, S, L>]>
+{
+    /// Hands the elements back, leaving a caller-supplied bucket array empty
+    fn drop(&mut self) {
+        for bucket in self.buckets.borrow_mut().iter_mut() {
+            bucket.clear();
+        }
+    }
+}
+
+impl<P, T, S, L, A, H, B> Extend<T> for IntrusiveHashMap<P, T, S, L, A, H, B>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>,
+          A: KeyAdapter<P>,
+          H: BuildHasher,
+          B: BorrowMut<[Bucket<P, T, S, L>]>
+{
+    /// Inserts every element, dropping the ones they replace
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.insert(elt); }
+    }
+}
+
+impl<'a, P, T, S, L, A, H, B> IntoIterator
+    for &'a IntrusiveHashMap<P, T, S, L, A, H, B>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a,
+          A: KeyAdapter<P>,
+          H: BuildHasher,
+          B: BorrowMut<[Bucket<P, T, S, L>]>
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, T, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, T, S, L> {
+        self.iter()
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, S, L> Iterator for Iter<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+
+    fn next(&mut self) -> Option<&'a P> {
+        loop {
+            if let Some(val) = self.bucket.as_mut().and_then(|b| b.next()) {
+                self.nelem -= 1;
+                return Some(val);
+            }
+            if self.nelem == 0 {
+                return None;
+            }
+            self.bucket = self.buckets.next().map(|b| b.iter());
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P, T, S, L> ExactSizeIterator for Iter<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
+{}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(test)]
+mod no_alloc_tests {
+    use std::collections::hash_map::RandomState;
+    use super::{IntrusiveHashMap, Identity};
+    use super::super::linked_list::LinkedList;
+
+    define_list_element!(MyU32 = u32 : MyLink);
+
+    #[test]
+    fn test_stack_buckets() {
+        let mut elts = [1, 2, 3, 5, 8].map(MyU32::new);
+        let mut buckets: [LinkedList<u32, &mut MyU32, MyU32, MyLink>; 4] =
+            Default::default();
+        let mut map = IntrusiveHashMap::new(&mut buckets[..], Identity,
+                                            RandomState::new());
+        for elt in elts.iter_mut() {
+            assert!(map.insert(elt).is_none());
+        }
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.find(&3), Some(&3));
+        assert_eq!(map.find(&4), None);
+        assert_eq!(map.remove_key(&8).map(|elt| elt.0.val), Some(8));
+        assert_eq!(map.len(), 4);
+        map.clear();
+    }
+}
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::cell::Cell;
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::rc::Rc;
+    use std::thread;
+    use std::vec::Vec;
+    use super::{IntrusiveHashMap, KeyAdapter, Identity};
+    use super::super::linked_list::{LinkedList, Node};
+
+    define_list_element!(Entry = (u32, u32) : EntryLink);
+
+    struct ByFirst;
+
+    impl KeyAdapter<(u32, u32)> for ByFirst {
+        type Key = u32;
+
+        fn key<'a>(&self, val: &'a (u32, u32)) -> &'a u32 {
+            &val.0
+        }
+    }
+
+    type Buckets = Box<[LinkedList<(u32, u32), Box<Entry>, Entry, EntryLink>]>;
+
+    fn buckets(n: usize) -> Buckets {
+        (0..n).map(|_| LinkedList::new()).collect()
+    }
+
+    fn sorted<'a, I: Iterator<Item=&'a (u32, u32)>>(iter: I) -> Vec<(u32, u32)> {
+        let mut v: Vec<_> = iter.cloned().collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut map = IntrusiveHashMap::new(buckets(4), ByFirst,
+                                            RandomState::new());
+        assert!(map.is_empty());
+        assert_eq!(map.find(&1), None);
+        for i in 0..10 {
+            assert!(map.insert(Box::new(Entry::new((i, i * 10)))).is_none());
+        }
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.find(&3), Some(&(3, 30)));
+        map.find_mut(&3).unwrap().1 = 33;
+        assert_eq!(map.find(&3), Some(&(3, 33)));
+        let old = map.insert(Box::new(Entry::new((3, 300))));
+        assert_eq!(old, Some(Box::new(Entry::new((3, 33)))));
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.remove_key(&4), Some(Box::new(Entry::new((4, 40)))));
+        assert_eq!(map.remove_key(&4), None);
+        assert_eq!(map.len(), 9);
+        assert_eq!(map.iter().len(), 9);
+        assert_eq!(sorted(map.iter()),
+                   [(0, 0), (1, 10), (2, 20), (3, 300), (5, 50), (6, 60),
+                    (7, 70), (8, 80), (9, 90)]);
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.iter().next(), None);
+    }
+
+    #[test]
+    fn test_send() {
+        let mut map = IntrusiveHashMap::new(buckets(4), ByFirst,
+                                            RandomState::new());
+        map.insert(Box::new(Entry::new((1, 10))));
+        thread::spawn(move || {
+            assert_eq!(map.find(&1), Some(&(1, 10)));
+        }).join().ok().unwrap();
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut map = IntrusiveHashMap::new(buckets(3), ByFirst,
+                                            RandomState::new());
+        let elts: Vec<*const Entry> = (0..6).map(|i| {
+            let e = Box::new(Entry::new((i, 0)));
+            let ptr: *const Entry = &*e;
+            map.insert(e);
+            ptr
+        }).collect();
+        let e = map.remove(unsafe { &*elts[2] });
+        assert_eq!(e.get_val(), &(2, 0));
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.find(&2), None);
+        assert_eq!(map.find(&5), Some(&(5, 0)));
+    }
+
+    /// Hashes each key to another bucket every time it is hashed
+    #[derive(Default)]
+    struct Fickle(Cell<u64>);
+
+    struct FickleHasher(u64);
+
+    impl BuildHasher for Fickle {
+        type Hasher = FickleHasher;
+
+        fn build_hasher(&self) -> FickleHasher {
+            self.0.set(self.0.get() + 1);
+            FickleHasher(self.0.get())
+        }
+    }
+
+    impl Hasher for FickleHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    #[test]
+    fn test_remove_rehashed() {
+        let mut map = IntrusiveHashMap::new(buckets(4), ByFirst,
+                                            Fickle::default());
+        let elts: Vec<*const Entry> = (0..6).map(|i| {
+            let e = Box::new(Entry::new((i, 0)));
+            let ptr: *const Entry = &*e;
+            map.insert(e);
+            ptr
+        }).collect();
+        for (i, &elt) in elts.iter().enumerate() {
+            let e = map.remove(unsafe { &*elt });
+            assert_eq!(e.get_val(), &(i as u32, 0));
+            assert_eq!(map.len(), 5 - i);
+            assert_eq!(map.iter().count(), 5 - i);
+        }
+    }
+
+    #[test]
+    fn test_remove_changed_key() {
+        let mut map = IntrusiveHashMap::new(buckets(8), ByFirst,
+                                            RandomState::new());
+        let e = Box::new(Entry::new((1, 0)));
+        let ptr: *const Entry = &*e;
+        map.insert(e);
+        map.insert(Box::new(Entry::new((2, 0))));
+        map.find_mut(&1).unwrap().0 = 100;
+        let e = map.remove(unsafe { &*ptr });
+        assert_eq!(e.get_val(), &(100, 0));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.find(&2), Some(&(2, 0)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_foreign() {
+        let mut map = IntrusiveHashMap::new(buckets(8), ByFirst,
+                                            RandomState::new());
+        map.insert(Box::new(Entry::new((1, 0))));
+        let other = Entry::new((1, 0));
+        map.remove(&other);
+    }
+
+    #[test]
+    fn test_slice_buckets() {
+        let mut storage: [LinkedList<i32, Rc<MyI32>, MyI32, MyLink>; 8] =
+            Default::default();
+        let elts: Vec<_> = (0..20).map(|i| Rc::new(MyI32::new(i))).collect();
+        {
+            let mut map = IntrusiveHashMap::new(&mut storage[..], Identity,
+                                                RandomState::new());
+            map.extend(elts.iter().cloned());
+            assert_eq!(map.len(), 20);
+            assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+            assert!((0..20).all(|i| map.find(&i) == Some(&i)));
+        }
+        // dropping the table empties the caller's buckets
+        assert!(storage.iter().all(|b| b.is_empty()));
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    define_list_element!(MyI32 = i32 : MyLink);
+
+    #[test]
+    fn test_rehash() {
+        let mut map = IntrusiveHashMap::new(buckets(1), ByFirst,
+                                            RandomState::new());
+        for i in 0..32 {
+            map.insert(Box::new(Entry::new((i, i))));
+        }
+        let old = map.rehash(buckets(8));
+        assert_eq!(old.len(), 1);
+        assert!(old[0].is_empty());
+        assert_eq!(map.bucket_count(), 8);
+        assert_eq!(map.len(), 32);
+        assert!((0..32).all(|i| map.find(&i) == Some(&(i, i))));
+        assert_eq!(sorted(map.iter()), (0..32).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rehash_full() {
+        let mut map = IntrusiveHashMap::new(buckets(1), ByFirst,
+                                            RandomState::new());
+        map.insert(Box::new(Entry::new((1, 1))));
+        let mut full = buckets(1);
+        full[0].push_back(Box::new(Entry::new((2, 2))));
+        map.rehash(full);
+    }
+}
@@ -0,0 +1,1148 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive pairing heap.
+//!
+//! The `PairingHeap` is a min-heap: `pop_min` returns its smallest element.
+//! Each element is a node of a multiway tree whose links point to its first
+//! child, its next sibling, and its previous sibling (or its parent, for a
+//! first child). Since an element carries its own links, it can be removed or
+//! have its value decreased given only a reference to it.
+//!
+//! `push`, `append`, `peek_min` and `decrease_key` take O(1) time, `pop_min`
+//! and `remove` take O(log n) amortized time.
+use core::fmt;
+use core::cell::Cell;
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::mem::ManuallyDrop;
+use core::ops::DerefMut;
+use core::ptr;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into a `PairingHeap`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_heap_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: HeapLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into a `PairingHeap`
+///
+/// The `prev` link of a first child points to its parent, and the root's
+/// points to itself, so that an element is in a heap exactly when its `prev`
+/// link is set.
+///
+/// # Safety
+///
+/// The trait is unsafe because any implementation must impl Drop to call
+/// check_links(), and `offset()` must be the offset of the link within its
+/// `Container`.
+pub unsafe trait HeapLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &HeapLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut HeapLinks<Self>;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+    fn check_links(&self) {
+        assert!(self.get_child().resolve().is_none());
+        assert!(self.get_sibling().resolve().is_none());
+        assert!(self.get_prev().resolve().is_none());
+    }
+}
+
+/// Accessors for the links of a `HeapLinkable`
+///
+/// They are private to the crate, so that the links of an element are only
+/// followed or changed by the heap it is in.
+pub(crate) trait HeapLinkOps : HeapLinkable
+{
+    fn get_child(&self) -> Rawlink<Self> {
+        self.get_links().child.get()
+    }
+    fn set_child(&self, child: Rawlink<Self>) {
+        self.get_links().child.set(child)
+    }
+    fn get_sibling(&self) -> Rawlink<Self> {
+        self.get_links().sibling.get()
+    }
+    fn set_sibling(&self, sibling: Rawlink<Self>) {
+        self.get_links().sibling.set(sibling)
+    }
+    fn get_prev(&self) -> Rawlink<Self> {
+        self.get_links().prev.get()
+    }
+    fn set_prev(&self, prev: Rawlink<Self>) {
+        self.get_links().prev.set(prev)
+    }
+}
+
+impl<L: HeapLinkable> HeapLinkOps for L {}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into a `PairingHeap`
+///
+/// `define_heap_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it in a heap. The
+/// heap is ordered by the `i32`.
+#[macro_export]
+macro_rules! define_heap_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_heap_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_heap_link!($link = $elt);
+        impl_heap_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_heap_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_heap_link!($link = $elt);
+        impl_heap_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_heap_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::heap::HeapLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::heap::HeapLinks<$link>);
+    );
+}
+
+/// Implements `HeapLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_heap_link {
+    ($link:ident = $elt:ident) => (
+        impl_heap_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::heap::HeapLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::heap::HeapLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::heap::HeapLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_heap_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_heap_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::heap::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive pairing heap
+pub struct PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: HeapLinkable<Container=T::Target>
+{
+    length: usize,
+    root: Rawlink<L>,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+#[derive(Default, Debug)]
+pub struct HeapLinks<L: HeapLinkable>
+{
+    child: Cell<Rawlink<L>>,
+    sibling: Cell<Rawlink<L>>,
+    prev: Cell<Rawlink<L>>,
+    _pin: PhantomPinned
+}
+
+/// An iterator over references to the items of a `PairingHeap`, in no
+/// particular order
+pub struct Iter<'a, P: 'a, T, L: HeapLinkable<Container=T>> {
+    next: Rawlink<L>,
+    nelem: usize,
+    _marker: PhantomData<&'a P>
+}
+
+/// An iterator yielding the elements of a `PairingHeap` by value, smallest
+/// first
+pub struct IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: HeapLinkable<Container=T::Target>
+{
+    heap: PairingHeap<P, T, S, L>
+}
+
+//////////////////////
+// Heap Navigation  //
+//////////////////////
+
+fn child<L: HeapLinkable>(node: &L) -> Option<&L> {
+    node.get_child().resolve()
+}
+
+fn sibling<L: HeapLinkable>(node: &L) -> Option<&L> {
+    node.get_sibling().resolve()
+}
+
+fn prev<L: HeapLinkable>(node: &L) -> Option<&L> {
+    node.get_prev().resolve().filter(|&p| !ptr::eq(p, node))
+}
+
+fn same<L>(a: Option<&L>, b: Option<&L>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => ptr::eq(a, b),
+        (None, None) => true,
+        _ => false
+    }
+}
+
+/// Returns the parent of `node` by walking back over its elder siblings
+fn parent<L: HeapLinkable>(mut node: &L) -> Option<&L> {
+    while let Some(p) = prev(node) {
+        if same(child(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+/// Returns the node following `node` in a preorder walk of the heap
+fn preorder_next<L: HeapLinkable>(node: &L) -> Option<&L> {
+    if let Some(c) = child(node) {
+        return Some(c);
+    }
+    let mut node = Some(node);
+    while let Some(n) = node {
+        if let Some(s) = sibling(n) {
+            return Some(s);
+        }
+        node = parent(n);
+    }
+    None
+}
+
+fn link_of<L: HeapLinkable>(node: Option<&L>) -> Rawlink<L> {
+    node.map_or(Rawlink::none(), Rawlink::some)
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// PairingHeap impls
+
+impl<P, T, S, L> PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: HeapLinkable<Container=T::Target>
+{
+    /// Creates an empty `PairingHeap`
+    #[inline]
+    pub fn new() -> PairingHeap<P, T, S, L> {
+        PairingHeap { length: 0, root: Rawlink::none(),
+                      _marker: PhantomData, _marker2: PhantomData,
+                      _marker3: PhantomData}
+    }
+
+    /// Provides an iterator over the elements, in no particular order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        Iter{nelem: self.length, next: self.root, _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `PairingHeap` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of elements in the `PairingHeap`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all elements from the `PairingHeap`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = PairingHeap::new()
+    }
+
+    /// Provides a reference to the smallest element, or `None` if the heap is
+    /// empty.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn peek_min(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { root.container_of() }.get_val()
+        })
+    }
+
+    /// Climbs to the root from `links` to check it is in this heap
+    fn contains_links(&self, links: &L) -> bool {
+        let mut node = links;
+        while let Some(p) = parent(node) {
+            node = p;
+        }
+        same(self.root.resolve(), Some(node))
+    }
+
+    /// Detaches the subtree at `node`, which is not the root, from its
+    /// parent and siblings
+    fn cut(node: &L) {
+        let p = prev(node).unwrap();
+        let s = sibling(node);
+        if same(child(p), Some(node)) {
+            p.set_child(link_of(s));
+        } else {
+            p.set_sibling(link_of(s));
+        }
+        if let Some(s) = s {
+            s.set_prev(Rawlink::some(p));
+        }
+        node.set_prev(Rawlink::none());
+        node.set_sibling(Rawlink::none());
+    }
+
+    /// Makes `root` the root of the heap
+    fn set_root(&mut self, root: Option<&L>) {
+        if let Some(r) = root {
+            r.set_prev(Rawlink::some(r));
+        }
+        self.root = link_of(root);
+    }
+}
+
+impl<P, T, S, L> PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: HeapLinkable<Container=T::Target>
+{
+    /// Links two detached subtrees, making the one with the larger root the
+    /// first child of the other, and returns the new root
+    fn meld<'a>(a: &'a L, b: &'a L) -> &'a L {
+        let (parent, child) =
+            if unsafe { b.container_of() }.get_val() <
+                unsafe { a.container_of() }.get_val() {
+                (b, a)
+            } else {
+                (a, b)
+            };
+        let first = parent.get_child();
+        child.set_sibling(first);
+        if let Some(f) = first.resolve() {
+            f.set_prev(Rawlink::some(child));
+        }
+        child.set_prev(Rawlink::some(parent));
+        parent.set_child(Rawlink::some(child));
+        parent
+    }
+
+    /// Melds the list of siblings starting at `first` into a single subtree
+    /// and returns its root, using the two-pass pairing strategy
+    fn merge_pairs(first: Option<&L>) -> Option<&L> {
+        // first pass: meld pairs from the left, stacking the results on
+        // their sibling links
+        let mut pairs: Option<&L> = None;
+        let mut cur = first;
+        while let Some(a) = cur {
+            a.set_prev(Rawlink::none());
+            let merged = match sibling(a) {
+                None => {
+                    cur = None;
+                    a
+                }
+                Some(b) => {
+                    cur = sibling(b);
+                    a.set_sibling(Rawlink::none());
+                    b.set_prev(Rawlink::none());
+                    b.set_sibling(Rawlink::none());
+                    Self::meld(a, b)
+                }
+            };
+            merged.set_sibling(link_of(pairs));
+            pairs = Some(merged);
+        }
+        // second pass: meld the stacked pairs from the right
+        let mut root: Option<&L> = None;
+        while let Some(p) = pairs {
+            pairs = sibling(p);
+            p.set_sibling(Rawlink::none());
+            root = Some(match root {
+                None => p,
+                Some(r) => Self::meld(r, p),
+            });
+        }
+        root
+    }
+
+    /// Adds an element to the heap.
+    ///
+    /// This operation should compute in O(1) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::PairingHeap;
+    ///
+    /// define_heap_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut h = PairingHeap::new();
+    /// h.push(Box::new(MyI32::new(3)));
+    /// h.push(Box::new(MyI32::new(1)));
+    /// h.push(Box::new(MyI32::new(2)));
+    /// assert_eq!(h.peek_min(), Some(&1));
+    /// assert_eq!(h.len(), 3);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn push(&mut self, elt: T) {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        let links = elt.get_links();
+        let root = match self.root.resolve() {
+            None => links,
+            Some(root) => Self::meld(root, links),
+        };
+        self.set_root(Some(root));
+        self.length += 1;
+        unsafe { elt.take() };
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the heap is
+    /// empty. Of several smallest elements, any one may be returned.
+    ///
+    /// This operation should compute in O(log n) amortized time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::PairingHeap;
+    ///
+    /// define_heap_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut h: PairingHeap<_, _, _, MyLink> =
+    ///     [3, 1, 2].iter().map(|&i| Box::new(MyI32::new(i))).collect();
+    /// assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(1))));
+    /// assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(2))));
+    /// assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(3))));
+    /// assert_eq!(h.pop_min(), None);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let children = child(root);
+            root.set_child(Rawlink::none());
+            root.set_prev(Rawlink::none());
+            self.set_root(Self::merge_pairs(children));
+            self.length -= 1;
+            unsafe { T::from_raw(root.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Unlinks `elt` from the heap and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) amortized time. In debug
+    /// builds the links are followed up to the root to check that `elt`
+    /// belongs to this heap.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this heap. Removing an element of another heap would leave
+    /// that heap with an incorrect length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::PairingHeap;
+    ///
+    /// define_heap_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut h = PairingHeap::new();
+    /// let two = Box::new(MyI32::new(2));
+    /// let ptr: *const MyI32 = &*two;
+    /// h.push(Box::new(MyI32::new(1)));
+    /// h.push(two);
+    /// h.push(Box::new(MyI32::new(3)));
+    ///
+    /// assert_eq!(unsafe { h.remove(&*ptr) }, Box::new(MyI32::new(2)));
+    /// assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(1))));
+    /// assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(3))));
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links();
+        assert!(links.get_prev().resolve().is_some(),
+                "Cannot remove an element which is not in a heap");
+        debug_assert!(self.contains_links(links),
+                      "Cannot remove an element of another heap");
+        if same(self.root.resolve(), Some(links)) {
+            return self.pop_min().unwrap();
+        }
+        Self::cut(links);
+        let children = child(links);
+        links.set_child(Rawlink::none());
+        if let Some(sub) = Self::merge_pairs(children) {
+            let root = Self::meld(self.root.resolve().unwrap(), sub);
+            self.set_root(Some(root));
+        }
+        self.length -= 1;
+        T::from_raw(links.container_of() as *const S as *mut S)
+    }
+
+    /// Lowers the value of the element `elt` points to by calling `f` on it,
+    /// then restores the order of the heap.
+    ///
+    /// `f` must not make the value greater. This operation should compute in
+    /// O(1) time, although its amortized cost is O(log n).
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must point to an element
+    /// of this heap. It is taken as a pointer rather than a reference because
+    /// no reference to the element may be held while its value is changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any heap. In debug builds, also panics if
+    /// `f` made the value greater than that of one of its children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::PairingHeap;
+    ///
+    /// define_heap_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut h = PairingHeap::new();
+    /// let five = Box::new(MyI32::new(5));
+    /// let ptr: *const MyI32 = &*five;
+    /// h.push(Box::new(MyI32::new(1)));
+    /// h.push(five);
+    ///
+    /// unsafe { h.decrease_key(ptr, |v| *v = 0) };
+    /// assert_eq!(h.peek_min(), Some(&0));
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn decrease_key<F>(&mut self, elt: *const S, f: F)
+        where T: DerefMut,
+              F: FnOnce(&mut P)
+    {
+        let links = (*elt).get_links();
+        assert!(links.get_prev().resolve().is_some(),
+                "Cannot update an element which is not in a heap");
+        debug_assert!(self.contains_links(links),
+                      "Cannot update an element of another heap");
+        // borrow the element back from the heap to change its value, before
+        // any link is changed and without dropping it should `f` panic
+        let mut owner = ManuallyDrop::new(T::from_raw(elt as *mut S));
+        f(owner.get_val_mut());
+        let is_root = same(self.root.resolve(), Some(links));
+        if !is_root {
+            Self::cut(links);
+        }
+        let mut c = child(links);
+        while let Some(n) = c {
+            debug_assert!(n.container_of().get_val() >= owner.get_val(),
+                          "decrease_key made the value greater");
+            c = sibling(n);
+        }
+        if !is_root {
+            let root = Self::meld(self.root.resolve().unwrap(), links);
+            self.set_root(Some(root));
+        }
+    }
+
+    /// Moves all elements from `other` into the heap. After this operation,
+    /// `other` becomes empty.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn append(&mut self, other: &mut PairingHeap<P, T, S, L>) {
+        if let Some(other_root) = other.root.take().resolve() {
+            let root = match self.root.resolve() {
+                None => other_root,
+                Some(root) => {
+                    other_root.set_prev(Rawlink::none());
+                    Self::meld(root, other_root)
+                }
+            };
+            self.set_root(Some(root));
+            self.length += other.length;
+            other.length = 0;
+        }
+    }
+}
+
+impl<P, T, S, L> Default for PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: HeapLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> PairingHeap<P, T, S, L> {
+        PairingHeap::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: HeapLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl<P, T, S, L> Drop for PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: HeapLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        // unlink the leaves one at a time, always descending to a first child
+        let mut cur = self.root.take().resolve();
+        while let Some(node) = cur {
+            if let Some(c) = child(node) {
+                cur = Some(c);
+                continue;
+            }
+            cur = prev(node);
+            let s = sibling(node);
+            if let Some(p) = cur {
+                p.set_child(link_of(s));
+                if let Some(s) = s {
+                    s.set_prev(Rawlink::some(p));
+                }
+            }
+            node.set_prev(Rawlink::none());
+            node.set_sibling(Rawlink::none());
+            // hand back the reference held by the heap
+            drop(unsafe {
+                T::from_raw(node.container_of() as *const S as *mut S)
+            });
+        }
+        self.length = 0;
+    }
+}
+
+impl<P, T, S, L> Extend<T> for PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: HeapLinkable<Container=T::Target>
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.push(elt); }
+    }
+}
+
+impl<P, T, S, L> FromIterator<T> for PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: HeapLinkable<Container=T::Target>
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> PairingHeap<P, T, S, L> {
+        let mut ret = PairingHeap::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L> IntoIterator for PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: HeapLinkable<Container=T::Target>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L>;
+
+    /// Consumes the heap into an iterator yielding elements by value,
+    /// smallest first.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L> {
+        IntoIter{heap: self}
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: HeapLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+unsafe impl<P, T, S, L> Send for PairingHeap<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: HeapLinkable<Container=T::Target>
+{}
+
+// HeapLinks impls
+
+unsafe impl<L: HeapLinkable> Send for HeapLinks<L> {}
+
+impl<L: HeapLinkable> Clone for HeapLinks<L>
+{
+    /// A clone is not in any heap, even if the original is
+    fn clone(&self) -> HeapLinks<L> {
+        Default::default()
+    }
+}
+
+impl<L: HeapLinkable> Drop for HeapLinks<L>
+{
+    fn drop(&mut self) {
+        assert!(self.prev.get().resolve().is_none());
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, L: HeapLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            next: self.next,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: HeapLinkable<Container=T> + 'a> Iterator
+    for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let next = self.next.resolve().unwrap();
+        self.nelem -= 1;
+        self.next = link_of(preorder_next(next));
+        let ret = unsafe { next.container_of() }.get_val();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: HeapLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+// IntoIter impls
+
+impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: HeapLinkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.heap.pop_min() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.length, Some(self.heap.length))
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(test)]
+mod no_alloc_tests {
+    use std::vec::Vec;
+    use super::PairingHeap;
+
+    define_heap_element!(MyI32 = i32 : MyLink);
+
+    #[test]
+    fn test_stack_elements() {
+        let mut elts = [3, 1, 4, 1, 5, 9, 2, 6].map(MyI32::new);
+        let mut heap = PairingHeap::new();
+        for elt in elts.iter_mut() {
+            heap.push(elt);
+        }
+        assert_eq!(heap.peek_min(), Some(&1));
+        let mut popped = Vec::new();
+        while let Some(elt) = heap.pop_min() {
+            popped.push(elt.0.val);
+        }
+        assert_eq!(popped, [1, 1, 2, 3, 4, 5, 6, 9]);
+    }
+}
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::panic;
+    use std::rc::Rc;
+    use std::vec::Vec;
+    use core::ptr;
+    use rand::{self, Rng};
+    use super::{PairingHeap, OwningPointer, Node, HeapLinkable, HeapLinkOps};
+    use super::{child, sibling, prev};
+
+    define_heap_element!(MyI32 = i32 : MyLink);
+
+    type Heap = PairingHeap<i32, Box<MyI32>, MyI32, MyLink>;
+
+    /// Checks the links and heap order of the subtree at `node`, returning
+    /// its size
+    fn check_node<P, S, L>(node: &L) -> usize
+        where P: Ord,
+              S: Node<P, L>,
+              L: HeapLinkable<Container=S>
+    {
+        let val = unsafe { node.container_of() }.get_val();
+        let mut size = 1;
+        let mut last = node;
+        let mut cur = child(node);
+        while let Some(c) = cur {
+            assert!(ptr::eq(prev(c).unwrap(), last));
+            assert!(unsafe { c.container_of() }.get_val() >= val);
+            size += check_node(c);
+            last = c;
+            cur = sibling(c);
+        }
+        size
+    }
+
+    pub fn check_links<P, T, S, L>(heap: &PairingHeap<P, T, S, L>)
+        where T: OwningPointer<Target=S>,
+              P: Ord,
+              S: Node<P, L>,
+              L: HeapLinkable<Container=S>,
+    {
+        match heap.root.resolve() {
+            None => assert_eq!(heap.length, 0),
+            Some(root) => {
+                assert!(ptr::eq(root.get_prev().resolve().unwrap(), root));
+                assert!(sibling(root).is_none());
+                assert_eq!(check_node(root), heap.length);
+            }
+        }
+    }
+
+    fn heap_from(v: &[i32]) -> Heap {
+        let heap: Heap = v.iter().map(|&i| Box::new(MyI32::new(i))).collect();
+        check_links(&heap);
+        heap
+    }
+
+    fn drain(heap: Heap) -> Vec<i32> {
+        heap.into_iter().map(|e| *e.get_val()).collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut h = Heap::new();
+        assert!(h.is_empty());
+        assert_eq!(h.peek_min(), None);
+        assert_eq!(h.pop_min(), None);
+        h.push(Box::new(MyI32::new(2)));
+        h.push(Box::new(MyI32::new(1)));
+        h.push(Box::new(MyI32::new(3)));
+        check_links(&h);
+        assert_eq!(h.len(), 3);
+        assert_eq!(h.peek_min(), Some(&1));
+        assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(1))));
+        check_links(&h);
+        let mut v: Vec<i32> = h.iter().cloned().collect();
+        v.sort();
+        assert_eq!(v, [2, 3]);
+        h.clear();
+        assert!(h.is_empty());
+    }
+
+    #[test]
+    fn test_sort() {
+        let v = [5, 3, 8, 1, 4, 7, 9, 2, 6, 3, 0, 5];
+        let mut sorted = v.to_vec();
+        sorted.sort();
+        assert_eq!(drain(heap_from(&v)), sorted);
+    }
+
+    #[test]
+    fn test_decrease_key() {
+        let mut h = Heap::new();
+        let elts: Vec<*const MyI32> = (0..10).map(|i| {
+            let e = Box::new(MyI32::new(10 + i));
+            let ptr: *const MyI32 = &*e;
+            h.push(e);
+            ptr
+        }).collect();
+        assert_eq!(h.pop_min(), Some(Box::new(MyI32::new(10))));
+        check_links(&h);
+        unsafe {
+            h.decrease_key(elts[7], |v| *v = 5);
+            check_links(&h);
+            h.decrease_key(elts[5], |v| *v -= 1);
+            check_links(&h);
+            h.decrease_key(elts[7], |v| *v = 4);
+            check_links(&h);
+        }
+        assert_eq!(drain(h), [4, 11, 12, 13, 14, 14, 16, 18, 19]);
+    }
+
+    #[test]
+    fn test_decrease_key_panic() {
+        let mut h = heap_from(&[1, 2, 3]);
+        let e = Box::new(MyI32::new(5));
+        let ptr: *const MyI32 = &*e;
+        h.push(e);
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            unsafe { h.decrease_key(ptr, |_| panic!()) };
+        }));
+        assert!(res.is_err());
+        // the element is still in the heap
+        check_links(&h);
+        assert_eq!(drain(h), [1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn test_remove_append() {
+        let mut h = Heap::new();
+        let elts: Vec<*const MyI32> = (0..8).map(|i| {
+            let e = Box::new(MyI32::new(i));
+            let ptr: *const MyI32 = &*e;
+            h.push(e);
+            ptr
+        }).collect();
+        h.pop_min();
+        let e = unsafe { h.remove(&*elts[4]) };
+        assert_eq!(e.get_val(), &4);
+        check_links(&h);
+        let e = unsafe { h.remove(&*elts[1]) };
+        assert_eq!(e.get_val(), &1);
+        check_links(&h);
+        let mut other = heap_from(&[9, 1, 5]);
+        h.append(&mut other);
+        check_links(&h);
+        check_links(&other);
+        assert!(other.is_empty());
+        assert_eq!(drain(h), [1, 2, 3, 5, 5, 6, 7, 9]);
+    }
+
+    #[test]
+    fn test_rc() {
+        let elts: Vec<_> = (0..6).map(|i| Rc::new(MyI32::new(i))).collect();
+        let mut h: PairingHeap<_, _, _, MyLink> = elts.iter().cloned().collect();
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        h.pop_min();
+        h.pop_min();
+        assert_eq!(Rc::strong_count(&elts[0]), 1);
+        drop(h);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut h = Heap::new();
+            let mut v: Vec<i32> = Vec::new();
+            let mut elts: Vec<*const MyI32> = Vec::new();
+            for _ in 0..200 {
+                match rng.gen_range(0..6) {
+                    0 if !elts.is_empty() => {
+                        let i = rng.gen_range(0..elts.len());
+                        let e = unsafe { h.remove(&*elts.swap_remove(i)) };
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    1 if !elts.is_empty() => {
+                        let e = h.pop_min().unwrap();
+                        assert_eq!(Some(e.get_val()), v.iter().min());
+                        let ptr: *const MyI32 = &*e;
+                        elts.retain(|&p| p != ptr);
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    2 if !elts.is_empty() => {
+                        let e = elts[rng.gen_range(0..elts.len())];
+                        let old = *unsafe { &*e }.get_val();
+                        let new = old - rng.gen_range(0..10);
+                        unsafe { h.decrease_key(e, |x| *x = new) };
+                        let pos = v.iter().position(|&x| x == old);
+                        v[pos.unwrap()] = new;
+                    }
+                    _ => {
+                        let val = rng.gen_range(0..50);
+                        let e = Box::new(MyI32::new(val));
+                        elts.push(&*e);
+                        h.push(e);
+                        v.push(val);
+                    }
+                }
+                check_links(&h);
+                assert_eq!(h.peek_min(), v.iter().min());
+            }
+            v.sort();
+            assert_eq!(drain(h), v);
+        }
+    }
+}
@@ -0,0 +1,713 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+//! An intrusive interval tree.
+//!
+//! The `IntervalTree` is an `AugTree` of half-open intervals ordered by their
+//! start, which keeps the largest end of the intervals of each subtree as its
+//! summary, a `MaxEnd`. This lets `overlapping` skip every subtree that ends
+//! before the queried range, so finding the k elements overlapping a range
+//! takes O((k + 1) log n) time and no allocation.
+//!
+//! Elements are those of an `AugTree` keeping a `MaxEnd`, as defined by
+//! `define_interval_element`.
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::ops::Range;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+use super::augtree::{summary, value, AugLinkable, AugTree, Augment};
+use super::rbtree::{left, link_of, parent, right, same};
+
+pub use super::augtree::{IntoIter, Iter, Node};
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A half-open interval `[start, end)` which can be kept in an `IntervalTree`
+pub trait Interval {
+    /// The type of the bounds of the interval
+    type Key: Ord + Clone;
+
+    /// The first point in the interval
+    fn start(&self) -> &Self::Key;
+
+    /// The first point past the end of the interval
+    fn end(&self) -> &Self::Key;
+}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into an `IntervalTree`
+///
+/// `define_interval_element!(MyRegion = Region : MyLink)` defines an element
+/// `MyRegion` holding a `Region`, which must implement `Interval`, and a link
+/// type `MyLink` used to put it in a tree. They are the element and link of an
+/// `AugTree` keeping the `MaxEnd` of each subtree.
+#[macro_export]
+macro_rules! define_interval_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_augtree_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_augtree_link!($link, $crate::interval_tree::MaxEnd<
+            <$container as $crate::interval_tree::Interval>::Key> = $elt);
+        impl_augtree_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_augtree_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_augtree_link!($link, $crate::interval_tree::MaxEnd<
+            <$container as $crate::interval_tree::Interval>::Key> = $elt);
+        impl_augtree_elt!($elt = $container : $link);
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive interval tree
+pub struct IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: AugLinkable<Container=T::Target>
+{
+    tree: AugTree<P, T, S, L>
+}
+
+/// The summary kept by the `AugTree` underlying an `IntervalTree`: the
+/// largest end of the intervals of a subtree, or `None` for no intervals
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaxEnd<K>(Option<K>);
+
+/// An iterator over references to the items of an `IntervalTree` which
+/// overlap a range, in order of their start
+pub struct Overlapping<'a, P, T, L>
+    where P: Interval + 'a,
+          L: AugLinkable<Container=T>
+{
+    next: Rawlink<L>,
+    range: Range<P::Key>,
+    _marker: PhantomData<&'a P>
+}
+
+//////////////////////
+// Tree Navigation  //
+//////////////////////
+
+/// Returns the largest end of the intervals in the subtree at `node`
+fn max_end<K, L: AugLinkable<Summary=MaxEnd<K>>>(node: &L) -> &K {
+    // a subtree holds at least the interval of its root
+    summary(node).0.as_ref().unwrap()
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// IntervalTree impls
+
+impl<P, T, S, L> IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>
+{
+    /// Creates an empty `IntervalTree`
+    #[inline]
+    pub fn new() -> IntervalTree<P, T, S, L> {
+        IntervalTree { tree: AugTree::new() }
+    }
+
+    /// Provides a double-ended iterator over the elements in order of their
+    /// start.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        self.tree.iter()
+    }
+
+    /// Returns `true` if the `IntervalTree` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns the number of elements in the `IntervalTree`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Removes all elements from the `IntervalTree`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.tree.clear()
+    }
+
+    /// Provides a reference to the element which starts first, or `None` if
+    /// the tree is empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    #[inline]
+    pub fn first(&self) -> Option<&P> {
+        self.tree.first()
+    }
+
+    /// Provides a reference to the element which starts last, or `None` if
+    /// the tree is empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    #[inline]
+    pub fn last(&self) -> Option<&P> {
+        self.tree.last()
+    }
+}
+
+impl<P, T, S, L> IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Interval,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target, Summary=MaxEnd<P::Key>>
+{
+    /// Inserts an element into the tree.
+    ///
+    /// An element starting at the same point as some already in the tree is
+    /// placed after them.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn insert(&mut self, elt: T) {
+        self.tree.insert_by(elt, |val, node| val.start() < node.start());
+    }
+
+    /// Removes the element which starts first and returns it, or `None` if
+    /// the tree is empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.tree.pop_first()
+    }
+
+    /// Removes the element which starts last and returns it, or `None` if the
+    /// tree is empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.tree.pop_last()
+    }
+
+    /// Unlinks `elt` from the tree and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) time. In debug builds the
+    /// parent links are followed to check that `elt` belongs to this tree.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this tree. Removing an element of another tree would leave
+    /// that tree with an incorrect length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any tree.
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        self.tree.remove(elt)
+    }
+
+    /// Provides an iterator over the elements which overlap `range`, in order
+    /// of their start.
+    ///
+    /// An element overlaps `range` when it starts before `range.end` and
+    /// ends after `range.start`, so intervals which merely touch do not
+    /// overlap.
+    ///
+    /// This operation should compute in O((k + 1) log n) time for k
+    /// overlapping elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::IntervalTree;
+    /// use intrusive_containers::interval_tree::Interval;
+    ///
+    /// #[derive(Clone, Default, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    /// struct Region { start: u64, end: u64 }
+    ///
+    /// impl Interval for Region {
+    ///     type Key = u64;
+    ///     fn start(&self) -> &u64 { &self.start }
+    ///     fn end(&self) -> &u64 { &self.end }
+    /// }
+    ///
+    /// define_interval_element!(MyRegion = Region : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t = IntervalTree::new();
+    /// for &(start, end) in &[(0, 0x1000), (0x3000, 0x5000), (0x4000, 0x8000)] {
+    ///     t.insert(Box::new(MyRegion::new(Region { start, end })));
+    /// }
+    ///
+    /// let found: Vec<_> = t.overlapping(0x1000..0x4800)
+    ///     .map(|r| r.start).collect();
+    /// assert_eq!(found, [0x3000, 0x4000]);
+    /// assert_eq!(t.overlapping(0x1000..0x3000).count(), 0);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn overlapping(&self, range: Range<P::Key>) -> Overlapping<'_, P, S, L> {
+        let mut ret = Overlapping{next: Rawlink::none(), range,
+                                  _marker: PhantomData};
+        ret.next = link_of(ret.first_in(self.tree.root()));
+        ret
+    }
+}
+
+impl<P, T, S, L> Default for IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> IntervalTree<P, T, S, L> {
+        IntervalTree::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.tree, f)
+    }
+}
+
+impl<P, T, S, L> Extend<T> for IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Interval,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target, Summary=MaxEnd<P::Key>>
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.insert(elt); }
+    }
+}
+
+impl<P, T, S, L> FromIterator<T> for IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Interval,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target, Summary=MaxEnd<P::Key>>
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I)
+                                          -> IntervalTree<P, T, S, L> {
+        let mut ret = IntervalTree::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L> IntoIterator for IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Interval,
+          S: Node<P, L>,
+          L: AugLinkable<Container=T::Target, Summary=MaxEnd<P::Key>>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L>;
+
+    /// Consumes the tree into an iterator yielding elements by value in order
+    /// of their start.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L> {
+        self.tree.into_iter()
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a IntervalTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: AugLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+// MaxEnd impls
+
+impl<K> Default for MaxEnd<K> {
+    #[inline]
+    fn default() -> MaxEnd<K> {
+        MaxEnd(None)
+    }
+}
+
+impl<P: Interval> Augment<P> for MaxEnd<P::Key> {
+    #[inline]
+    fn summarize(val: &P) -> MaxEnd<P::Key> {
+        MaxEnd(Some(val.end().clone()))
+    }
+
+    #[inline]
+    fn combine(&self, next: &MaxEnd<P::Key>) -> MaxEnd<P::Key> {
+        match (&self.0, &next.0) {
+            (Some(a), Some(b)) if b > a => next.clone(),
+            (Some(_), _) => self.clone(),
+            (None, _) => next.clone()
+        }
+    }
+}
+
+// Interval impls
+
+impl<K: Ord + Clone> Interval for Range<K> {
+    type Key = K;
+
+    #[inline]
+    fn start(&self) -> &K {
+        &self.start
+    }
+
+    #[inline]
+    fn end(&self) -> &K {
+        &self.end
+    }
+}
+
+// Overlapping impls
+
+impl<'a, P, T, L> Overlapping<'a, P, T, L>
+    where P: Interval + 'a,
+          T: Node<P, L> + 'a,
+          L: AugLinkable<Container=T, Summary=MaxEnd<P::Key>> + 'a
+{
+    /// Returns `true` if the interval of `node` starts at or past the end of
+    /// the range, as do those of all the nodes after it
+    fn past(&self, node: &L) -> bool {
+        value::<P, T, L>(node).start() >= &self.range.end
+    }
+
+    /// Returns `true` if the interval of `node` overlaps the range, given that
+    /// it does not start past it
+    fn overlaps(&self, node: &L) -> bool {
+        value::<P, T, L>(node).end() > &self.range.start
+    }
+
+    /// Finds the first node overlapping the range in the subtree at `node`
+    fn first_in(&self, mut node: Option<&'a L>) -> Option<&'a L> {
+        while let Some(n) = node {
+            if max_end(n) <= &self.range.start {
+                // everything here ends before the range
+                return None;
+            }
+            // if something on the left ends after the range starts but still
+            // does not overlap, it starts past the range and so does `n`
+            if let Some(l) = left(n) {
+                if max_end(l) > &self.range.start {
+                    node = Some(l);
+                    continue;
+                }
+            }
+            if self.past(n) {
+                return None;
+            }
+            if self.overlaps(n) {
+                return Some(n);
+            }
+            node = right(n);
+        }
+        None
+    }
+
+    /// Finds the first node after `node` overlapping the range
+    fn next_after(&self, node: &'a L) -> Option<&'a L> {
+        if let Some(n) = self.first_in(right(node)) {
+            return Some(n);
+        }
+        let mut node = node;
+        while let Some(p) = parent(node) {
+            if same(left(p), Some(node)) {
+                if self.past(p) {
+                    return None;
+                }
+                if self.overlaps(p) {
+                    return Some(p);
+                }
+                if let Some(n) = self.first_in(right(p)) {
+                    return Some(n);
+                }
+            }
+            node = p;
+        }
+        None
+    }
+}
+
+impl<'a, P, T, L> Iterator for Overlapping<'a, P, T, L>
+    where P: Interval + 'a,
+          T: Node<P, L> + 'a,
+          L: AugLinkable<Container=T, Summary=MaxEnd<P::Key>> + 'a
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        let node = self.next.resolve()?;
+        self.next = link_of(self.next_after(node));
+        Some(value(node))
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::thread;
+    use std::vec::Vec;
+    use core::ptr;
+    use rand::{self, Rng};
+    use super::{IntervalTree, OwningPointer, Node, Interval, MaxEnd, max_end};
+    use super::super::augtree::AugLinkable;
+    use super::super::rbtree::{Color, RBLinkOps, left, right, parent};
+
+    #[derive(Clone, Copy, Default, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    struct Iv(u32, u32);
+
+    impl Interval for Iv {
+        type Key = u32;
+        fn start(&self) -> &u32 { &self.0 }
+        fn end(&self) -> &u32 { &self.1 }
+    }
+
+    define_interval_element!(MyIv = Iv : MyLink);
+
+    type Tree = IntervalTree<Iv, Box<MyIv>, MyIv, MyLink>;
+
+    /// Checks the parent links, ordering, largest ends and red-black
+    /// properties of the subtree at `node`, returning its size, black height
+    /// and largest end
+    fn check_node<'a, P, S, L>(node: &'a L) -> (usize, usize, &'a P::Key)
+        where P: Interval + 'a,
+              S: Node<P, L> + 'a,
+              L: AugLinkable<Container=S, Summary=MaxEnd<P::Key>>
+    {
+        let val = unsafe { node.container_of() }.get_val();
+        let mut size = 1;
+        let mut heights = [0; 2];
+        let mut max = val.end();
+        for (i, child) in [left(node), right(node)].iter().enumerate() {
+            if let Some(child) = *child {
+                assert!(ptr::eq(parent(child).unwrap(), node));
+                let child_val = unsafe { child.container_of() }.get_val();
+                if i == 0 {
+                    assert!(child_val.start() <= val.start());
+                } else {
+                    assert!(child_val.start() >= val.start());
+                }
+                if node.get_color() == Color::Red {
+                    assert_eq!(child.get_color(), Color::Black);
+                }
+                let (s, h, m) = check_node(child);
+                size += s;
+                heights[i] = h;
+                max = max.max(m);
+            }
+        }
+        assert_eq!(heights[0], heights[1]);
+        assert!(max_end(node) == max);
+        let black = (node.get_color() == Color::Black) as usize;
+        (size, heights[0] + black, max)
+    }
+
+    pub fn check_links<P, T, S, L>(tree: &IntervalTree<P, T, S, L>)
+        where T: OwningPointer<Target=S>,
+              P: Interval,
+              S: Node<P, L>,
+              L: AugLinkable<Container=S, Summary=MaxEnd<P::Key>>,
+    {
+        match tree.tree.root() {
+            None => assert_eq!(tree.len(), 0),
+            Some(root) => {
+                assert!(ptr::eq(root.get_parent().resolve().unwrap(), root));
+                assert_eq!(root.get_color(), Color::Black);
+                assert_eq!(check_node(root).0, tree.len());
+            }
+        }
+    }
+
+    fn tree_from(v: &[(u32, u32)]) -> Tree {
+        let tree: Tree = v.iter()
+            .map(|&(s, e)| Box::new(MyIv::new(Iv(s, e)))).collect();
+        check_links(&tree);
+        tree
+    }
+
+    fn overlapping(tree: &Tree, start: u32, end: u32) -> Vec<(u32, u32)> {
+        tree.overlapping(start..end).map(|iv| (iv.0, iv.1)).collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut t = Tree::new();
+        assert!(t.is_empty());
+        assert_eq!(t.first(), None);
+        assert_eq!(t.overlapping(0..10).next(), None);
+        t.insert(Box::new(MyIv::new(Iv(5, 10))));
+        t.insert(Box::new(MyIv::new(Iv(0, 20))));
+        t.insert(Box::new(MyIv::new(Iv(8, 9))));
+        check_links(&t);
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.first(), Some(&Iv(0, 20)));
+        assert_eq!(t.last(), Some(&Iv(8, 9)));
+        assert_eq!(t.iter().rev().cloned().collect::<Vec<_>>(),
+                   [Iv(8, 9), Iv(5, 10), Iv(0, 20)]);
+        assert_eq!(t.pop_first(), Some(Box::new(MyIv::new(Iv(0, 20)))));
+        check_links(&t);
+        assert_eq!(t.iter().cloned().collect::<Vec<_>>(),
+                   [Iv(5, 10), Iv(8, 9)]);
+        t.clear();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_send() {
+        let mut t = Tree::new();
+        t.insert(Box::new(MyIv::new(Iv(5, 10))));
+        thread::spawn(move || {
+            check_links(&t);
+            assert_eq!(t.overlapping(0..6).cloned().collect::<Vec<_>>(),
+                       [Iv(5, 10)]);
+        }).join().ok().unwrap();
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let t = tree_from(&[(0, 4), (2, 3), (5, 6), (6, 10), (7, 8), (12, 20),
+                            (14, 15)]);
+        assert_eq!(overlapping(&t, 0, 1), [(0, 4)]);
+        assert_eq!(overlapping(&t, 3, 7), [(0, 4), (5, 6), (6, 10)]);
+        // touching intervals do not overlap
+        assert_eq!(overlapping(&t, 4, 5), []);
+        assert_eq!(overlapping(&t, 10, 12), []);
+        assert_eq!(overlapping(&t, 9, 13), [(6, 10), (12, 20)]);
+        assert_eq!(overlapping(&t, 15, 100), [(12, 20)]);
+        assert_eq!(overlapping(&t, 0, 100).len(), 7);
+        assert_eq!(overlapping(&t, 20, 100), []);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let elts: Vec<_> = (0..6).map(|i| Rc::new(MyIv::new(Iv(i % 2, 5 + i))))
+            .collect();
+        let t: IntervalTree<_, _, _, MyLink> = elts.iter().cloned().collect();
+        check_links(&t);
+        // elements with equal starts are kept in insertion order
+        let found: Vec<u32> = t.overlapping(8..9).map(|iv| iv.1).collect();
+        assert_eq!(found, [9, 10]);
+        assert_eq!(t.iter().map(|iv| iv.1).collect::<Vec<_>>(),
+                   [5, 7, 9, 6, 8, 10]);
+        drop(t);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut elts: Vec<*const MyIv> = Vec::new();
+        let mut t = Tree::new();
+        for i in 0..32 {
+            let e = Box::new(MyIv::new(Iv(i, i + 100 - 3 * i)));
+            elts.push(&*e);
+            t.insert(e);
+        }
+        for i in (0..32).step_by(3) {
+            let e = unsafe { t.remove(&*elts[i]) };
+            assert_eq!(e.get_val().0, i as u32);
+            check_links(&t);
+        }
+        assert_eq!(t.len(), 21);
+        assert!(t.iter().all(|iv| iv.0 % 3 != 0));
+        let expect: Vec<_> = t.iter().filter(|iv| iv.1 > 60)
+            .map(|iv| (iv.0, iv.1)).collect();
+        assert_eq!(expect.len(), 13);
+        assert_eq!(overlapping(&t, 60, 61), expect);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_unlinked() {
+        let mut t = Tree::new();
+        t.insert(Box::new(MyIv::new(Iv(1, 2))));
+        let e = MyIv::new(Iv(2, 3));
+        unsafe { t.remove(&e) };
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut t = Tree::new();
+            let mut v: Vec<Iv> = Vec::new();
+            let mut elts: Vec<*const MyIv> = Vec::new();
+            for _ in 0..200 {
+                match rng.gen_range(0..4) {
+                    0 if !elts.is_empty() => {
+                        let i = rng.gen_range(0..elts.len());
+                        let e = unsafe { t.remove(&*elts.swap_remove(i)) };
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    _ => {
+                        let start = rng.gen_range(0..100);
+                        let iv = Iv(start, start + rng.gen_range(0..20));
+                        let e = Box::new(MyIv::new(iv));
+                        elts.push(&*e);
+                        t.insert(e);
+                        v.push(iv);
+                    }
+                }
+                check_links(&t);
+                let a = rng.gen_range(0..120);
+                let b = a + rng.gen_range(0..30);
+                let mut expect: Vec<Iv> = v.iter()
+                    .filter(|iv| iv.0 < b && a < iv.1).cloned().collect();
+                let mut found: Vec<Iv> =
+                    t.overlapping(a..b).cloned().collect();
+                assert!(found.windows(2).all(|w| w[0].0 <= w[1].0));
+                expect.sort();
+                found.sort();
+                assert_eq!(found, expect);
+            }
+        }
+    }
+}
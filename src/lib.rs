@@ -15,20 +15,57 @@
 
 #![crate_name = "intrusive_containers"]
 #![crate_type = "rlib"]
-#![feature(core)]
-#![cfg_attr(all(feature="nostd",not(test)), feature(no_std))]
-#![cfg_attr(all(feature="nostd",not(test)), no_std)]
-#![cfg_attr(any(not(feature="nostd"),test), feature(alloc))]
-#![cfg_attr(test, feature(collections, hash, test))]
+#![no_std]
 
-#[cfg(all(feature="nostd",not(test)))] #[macro_use] extern crate core as std;
-#[cfg(all(feature="nostd",not(test)))] #[macro_use] extern crate core;
-
-#[cfg(test)] extern crate test;
-#[cfg(test)] extern crate rand;
+#[cfg(feature="alloc")] extern crate alloc;
+#[cfg(test)] extern crate std;
 
 pub use linked_list::LinkedList;
+pub use slist::SinglyLinkedList;
+pub use stailq::STailQ;
+pub use rbtree::RBTree;
+pub use avltree::AVLTree;
+pub use splaytree::SplayTree;
+pub use hash_map::IntrusiveHashMap;
+pub use heap::PairingHeap;
+pub use skiplist::SkipList;
+pub use lru::LruCache;
+pub use timer_wheel::TimerWheel;
+pub use radix_tree::RadixTree;
+pub use augtree::AugTree;
+pub use interval_tree::IntervalTree;
+pub use treap::Treap;
+pub use dary_heap::DaryHeap;
+#[cfg(target_has_atomic = "ptr")] pub use mpsc::MpscQueue;
+#[cfg(all(target_has_atomic = "64",
+          any(target_pointer_width = "32", target_arch = "x86_64",
+              all(target_arch = "aarch64", not(target_os = "android")))))]
+pub use atomic_stack::AtomicStack;
+#[cfg(target_has_atomic = "ptr")] pub use sync_list::SyncLinkedList;
+#[cfg(feature="derive")] pub use intrusive_containers_derive::Intrusive;
 
-pub mod linked_list;
+#[macro_use] pub mod linked_list;
+#[macro_use] pub mod slist;
+pub mod stailq;
+pub mod rbtree;
+pub mod avltree;
+pub mod splaytree;
+pub mod hash_map;
+pub mod heap;
+pub mod skiplist;
+pub mod lru;
+pub mod timer_wheel;
+pub mod radix_tree;
+#[macro_use] pub mod augtree;
+pub mod interval_tree;
+pub mod treap;
+pub mod dary_heap;
+#[cfg(target_has_atomic = "ptr")] pub mod mpsc;
+// the head of the stack packs addresses into 48 bits
+#[cfg(all(target_has_atomic = "64",
+          any(target_pointer_width = "32", target_arch = "x86_64",
+              all(target_arch = "aarch64", not(target_os = "android")))))]
+pub mod atomic_stack;
+#[cfg(target_has_atomic = "ptr")] pub mod sync_list;
 
 mod rawlink;
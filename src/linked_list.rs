@@ -16,36 +16,98 @@
 //! An intrusive double-linked list.
 //!
 //! The 'LinkedList' allows elements to be inserted or removed from either end.
-use std::cmp::Ordering;
-use std::fmt;
-use std::hash::{Hasher, Hash};
-use std::intrinsics::forget;
-use std::iter::{self,FromIterator};
-use std::marker::PhantomData;
-use std::mem;
-use std::ops::DerefMut;
-#[cfg(all(feature="nostd",not(test)))]
-use core::prelude::*;
+//!
+//! # Threads
+//!
+//! The containers of this crate own their elements, links included, through
+//! the owning pointer `T`, and links are only ever followed by the container
+//! their element is on. A container may thus be sent to another thread
+//! whenever `T` may, its elements moving along with it, and links are `Send`
+//! for the same reason. Containers built out of others, such as
+//! `IntrusiveHashMap` or `TimerWheel`, are `Send` through them.
+//!
+//! Links are updated through shared references, so containers are not `Sync`,
+//! except for `MpscQueue`, `AtomicStack` and `SyncLinkedList`, which are meant
+//! to be shared.
+use core::cell::Cell;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hasher, Hash};
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::mem;
+use core::ptr;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
 use super::rawlink::Rawlink;
-#[cfg(any(test,not(feature="nostd")))]
-use std::boxed;
+#[cfg(feature="alloc")]
+use alloc::boxed::Box;
+#[cfg(feature="alloc")]
+use alloc::rc::Rc;
+#[cfg(all(feature="alloc", target_has_atomic="ptr"))]
+use alloc::sync::Arc;
 
 ///////////////////////
 // Trait Definitions //
 ///////////////////////
 
 /// A trait that allows insertion into a `LinkedList`.
-
+///
+/// # Safety
+///
 /// The trait is unsafe to implement due to the following constraints:
 /// 1) The deref functions must always return the same reference
 /// 2) The object cannot be moved while in the `LinkedList`
 /// 3) No references (mutable or otherwise) to the target can be used while
-///      list operations are ongoing
-//  Box and &mut both fulfill these requirements
-pub unsafe trait OwningPointer : DerefMut
+///    list operations are ongoing
+///
+/// Only `Deref` is required, so shared pointers such as `Rc` can be used. The
+/// list keeps the reference it was handed for as long as the element is
+/// linked and gives it back when the element is removed. Methods handing out
+/// mutable references to elements additionally require `DerefMut`.
+///
+/// `Links` are `!Unpin`, so elements handed over as `Pin<Box<T>>`,
+/// `Pin<&mut T>` or `Pin<&T>` cannot be moved at all: constraint 2 is then
+/// checked by the compiler rather than left to the caller. Pinning also makes
+/// elements on the stack safe to use, since an element dropped while still
+/// linked panics in the destructor of its `Links` before its memory can be
+/// reused.
+///
+/// An element on several lists at once is handed to each of them, so it must
+/// be handed over through a shared pointer: `Rc`, `Arc` or `Pin<&T>`. Handing
+/// it over as a `Box<T>` or a `&mut T` more than once would alias them.
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate intrusive_containers;
+/// # use std::default::Default;
+/// use std::mem;
+/// use std::pin::pin;
+///
+/// define_list_element!(MyI32 = i32 : MyLink);
+///
+/// # fn main() {
+/// let mut a = pin!(MyI32::new(1));
+/// let mut b = pin!(MyI32::new(2));
+/// mem::swap(&mut *a, &mut *b); // MyI32 is !Unpin
+/// # }
+/// ```
+//  Box, Rc, Arc, &mut, their pinned forms and pinned shared references all
+//  fulfill these requirements
+pub unsafe trait OwningPointer : Deref
 {
+    /// Reconstructs the pointer from a raw pointer to its target
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have been obtained from a pointer of this type which was
+    /// then released with `take`, and must only be reconstructed once.
     unsafe fn from_raw(raw: *mut Self::Target) -> Self;
 
+    /// Releases ownership without dropping the target
+    ///
+    /// # Safety
+    ///
+    /// The target is leaked unless it is later reconstructed with `from_raw`.
     unsafe fn take(self);
 }
 
@@ -53,6 +115,11 @@ pub unsafe trait OwningPointer : DerefMut
 ///
 /// Rather than implement this directly, it is expected to use the
 /// `define_list_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
 pub unsafe trait Node<T, L> : Sized
     where L: Linkable<Container=Self>
 {
@@ -67,75 +134,106 @@ pub unsafe trait Node<T, L> : Sized
 
     /// Getter for mutable links
     fn get_links_mut(&mut self) -> &mut L;
-
-    fn get_next(&self) -> &Rawlink<L> {
-        self.get_links().get_next()
-    }
-
-    fn get_next_mut(&mut self) -> &mut Rawlink<L> {
-        self.get_links_mut().get_next_mut()
-    }
-
-    fn get_prev(&self) -> &Rawlink<L> {
-        self.get_links().get_prev()
-    }
-
-    fn get_prev_mut(&mut self) -> &mut Rawlink<L> {
-        self.get_links_mut().get_prev_mut()
-    }
 }
 
 /// Link trait allowing a struct to be inserted into a `LinkedList`
 ///
+/// The links are held in `Cell`s so that they can be updated through a shared
+/// reference to the element.
+///
+/// # Safety
+///
 /// The trait is unsafe because any implementation must impl Drop to call
-/// check_links()
+/// check_links(), and `offset()` must be the offset of the link within its
+/// `Container`.
 pub unsafe trait Linkable : Default + Sized
 {
     type Container;
 
     fn get_links(&self) -> &Links<Self>;
     fn get_links_mut(&mut self) -> &mut Links<Self>;
-    fn get_next<'a>(&'a self) -> &'a Rawlink<Self>
-        where Self: 'a, Self::Container: 'a
-    {
-        &self.get_links().next
-    }
-    fn get_next_mut<'a>(&'a mut self) -> &'a mut Rawlink<Self>
-        where Self: 'a, Self::Container: 'a
-    {
-        &mut self.get_links_mut().next
-    }
-    fn get_prev<'a>(&'a self) -> &'a Rawlink<Self>
-        where Self: 'a, Self::Container: 'a
-    {
-        &self.get_links().prev
-    }
-    fn get_prev_mut<'a>(&'a mut self) -> &'a mut Rawlink<Self>
-        where Self: 'a, Self::Container: 'a
-    {
-        &mut self.get_links_mut().prev
-    }
     fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
     unsafe fn container_of(&self) -> &Self::Container {
-        let mut val = self as *const _ as usize;
-        val -= Self::offset();
-        &*(val as *const _)
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
     }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
     unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
-        let mut val = self as *const _ as usize;
-        val -= Self::offset();
-        &mut *(val as *mut _)
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
     }
+}
+
+/// Accessors for the links of a `Linkable`
+///
+/// They are private to the crate, so that the links of an element are only
+/// followed or changed by the list it is on.
+pub(crate) trait ListLinkOps : Linkable
+{
     fn check_links(&self) {
         assert!(self.get_next().resolve().is_none());
         assert!(self.get_prev().resolve().is_none());
     }
+    fn get_next(&self) -> Rawlink<Self> {
+        self.get_links().next.get()
+    }
+    fn set_next(&self, next: Rawlink<Self>) {
+        self.get_links().next.set(next)
+    }
+    fn get_prev(&self) -> Rawlink<Self> {
+        self.get_links().prev.get()
+    }
+    fn set_prev(&self, prev: Rawlink<Self>) {
+        self.get_links().prev.set(prev)
+    }
 }
 
+impl<L: Linkable> ListLinkOps for L {}
+
 ///////////////////////
 // Macro Definitions //
 ///////////////////////
 
+/// Defines a struct which can be inserted into a `LinkedList`
+///
+/// `define_list_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it on a list.
+///
+/// Several link types may be given to allow the same element to be on several
+/// lists at once, one per link type:
+///
+/// ```
+/// # #[macro_use] extern crate intrusive_containers;
+/// # use std::default::Default;
+/// use intrusive_containers::LinkedList;
+///
+/// define_list_element!(Task = u32 : RunLink, AllLink);
+///
+/// # #[cfg(feature = "alloc")]
+/// # fn main() {
+/// use std::rc::Rc;
+///
+/// let task = Rc::new(Task::new(7));
+/// let mut run: LinkedList<u32, Rc<Task>, Task, RunLink> = LinkedList::new();
+/// let mut all: LinkedList<u32, Rc<Task>, Task, AllLink> = LinkedList::new();
+/// run.push_back(task.clone());
+/// all.push_back(task.clone());
+/// assert_eq!(run.front(), Some(&7));
+/// assert_eq!(all.front(), Some(&7));
+/// run.pop_front();
+/// all.pop_front();
+/// # }
+/// # #[cfg(not(feature = "alloc"))] fn main() {}
+/// ```
 #[macro_export]
 macro_rules! define_list_element {
     ($elt:ident = $container:ty : $link:ident) => (
@@ -150,6 +248,16 @@ macro_rules! define_list_element {
         impl_list_link!($link = $elt);
         impl_list_elt!($elt = $container : $link);
     );
+    ($elt:ident = $container:ty : $($link:ident),+) => (
+        $(declare_list_link!($link);)+
+        declare_list_elt!($elt = $container : $($link),+);
+        impl_list_links!($elt = $container : $($link),+);
+    );
+    (pub $elt:ident = $container:ty : $($link:ident),+) => (
+        $(declare_list_link!($link);)+
+        declare_list_elt!(pub $elt = $container : $($link),+);
+        impl_list_links!($elt = $container : $($link),+);
+    );
 }
 
 #[macro_export]
@@ -174,11 +282,24 @@ macro_rules! declare_list_elt {
         #[derive(Clone, Default, Debug, Hash, Eq, PartialOrd, Ord, PartialEq)]
         pub struct $elt($crate::linked_list::NodeImpl<$container, $link>);
     );
+    ($elt:ident = $container:ty : $($link:ident),+) => (
+        #[derive(Clone, Default, Debug, Hash, Eq, PartialOrd, Ord, PartialEq)]
+        struct $elt($crate::linked_list::NodeImpl<$container, ($($link,)+)>);
+    );
+    (pub $elt:ident = $container:ty : $($link:ident),+) => (
+        #[derive(Clone, Default, Debug, Hash, Eq, PartialOrd, Ord, PartialEq)]
+        pub struct $elt($crate::linked_list::NodeImpl<$container, ($($link,)+)>);
+    );
 }
 
+/// Implements `Linkable` for `$link`, which is found at the field path `$field`
+/// (`link` by default) of the `NodeImpl` wrapped by `$elt`
 #[macro_export]
 macro_rules! impl_list_link {
     ($link:ident = $elt:ident) => (
+        impl_list_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
         unsafe impl $crate::linked_list::Linkable for $link {
             type Container = $elt;
 
@@ -195,7 +316,7 @@ macro_rules! impl_list_link {
 
             #[inline]
             fn offset() -> usize {
-                0
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
             }
         }
     );
@@ -209,11 +330,14 @@ macro_rules! impl_list_elt {
             fn new(val: $container) -> $elt {
                 $elt($crate::linked_list::NodeImpl {
                     link: Default::default(),
-                    val: val
+                    val
                 })
             }
         }
 
+        impl_list_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
         unsafe impl $crate::linked_list::Node<$container, $link> for $elt {
             #[inline]
             fn get_val(&self) -> &$container {
@@ -227,17 +351,48 @@ macro_rules! impl_list_elt {
 
             #[inline]
             fn get_links(&self) -> &$link {
-                &self.0.link
+                &self.0.$($field)+
             }
 
             #[inline]
             fn get_links_mut(&mut self) -> &mut $link {
-                &mut self.0.link
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+/// Implements `Linkable` and `Node` for an element declared with several link
+/// types, each of which is stored in one slot of the `link` tuple
+#[macro_export]
+macro_rules! impl_list_links {
+    ($elt:ident = $container:ty : $($link:ident),+) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
             }
         }
+
+        impl_list_links!(@each $elt = $container : [$($link),+]
+                         [0 1 2 3 4 5 6 7 8 9 10 11]);
+    );
+    (@each $elt:ident = $container:ty : [] [$($idx:tt)*]) => ();
+    (@each $elt:ident = $container:ty : [$link:ident $(, $rest:ident)*]
+     [$idx:tt $($idxs:tt)*]) => (
+        impl_list_link!($link = $elt : link.$idx);
+        impl_list_elt!(@node $elt = $container : $link : link.$idx);
+        impl_list_links!(@each $elt = $container : [$($rest),*] [$($idxs)*]);
     );
 }
 
+/// Used by `impl_list_link` and the `Intrusive` derive to compute field offsets
+#[doc(hidden)]
+pub use core::mem::offset_of;
+
 ////////////////////////
 // Struct Definitions //
 ////////////////////////
@@ -254,11 +409,12 @@ pub struct LinkedList<P, T, S, L>
     _marker3: PhantomData<S>
 }
 
-#[derive(Clone, Default, Debug)]
+#[derive(Default)]
 pub struct Links<L: Linkable>
 {
-    prev: Rawlink<L>,
-    next: Rawlink<L>
+    prev: Cell<Rawlink<L>>,
+    next: Cell<Rawlink<L>>,
+    _pin: PhantomPinned
 }
 
 #[derive(Clone, Default, Debug)]
@@ -295,6 +451,39 @@ pub struct IntoIter<P, T, S, L>
     list: LinkedList<P, T, S, L>
 }
 
+/// A cursor over a `LinkedList`
+///
+/// A `Cursor` is like an iterator, except that it can freely seek
+/// back-and-forth. Cursors always rest between two elements in the list, and
+/// index in a logically circular way. To accommodate this, there is a "ghost"
+/// non-element that yields `None` between the head and tail of the list.
+pub struct Cursor<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
+{
+    index: usize,
+    current: Rawlink<L>,
+    list: &'a LinkedList<P, T, S, L>,
+}
+
+/// A cursor over a `LinkedList` with editing operations
+///
+/// A `CursorMut` is like a `Cursor`, except that it can also insert, remove
+/// and splice elements at its position. The list cannot be accessed by other
+/// means while the cursor exists.
+pub struct CursorMut<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
+{
+    index: usize,
+    current: Rawlink<L>,
+    list: &'a mut LinkedList<P, T, S, L>,
+}
+
 // LinkedList impls
 
 impl<P, T, S, L> LinkedList<P, T, S, L>
@@ -326,6 +515,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut a = LinkedList::new();
     /// let mut b = LinkedList::new();
@@ -341,24 +531,25 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// }
     /// println!("{}", b.len()); // prints 0
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     pub fn append(&mut self, other: &mut LinkedList<P, T, S, L>) {
-        match self.head.resolve_mut() {
+        match self.head.resolve() {
             None => {
                 self.length = other.length;
                 self.head = other.head.take();
             },
             Some(head) => {
-                let tail = head.get_prev_mut().resolve_mut().unwrap();
-                match other.head.take().resolve_mut() {
+                let tail = head.get_prev().resolve().unwrap();
+                match other.head.take().resolve() {
                     None => return,
                     Some(other_head) => {
                         let other_tail =
-                            other_head.get_prev_mut().resolve_mut().unwrap();
-                        *other_tail.get_next_mut() = Rawlink::some(head);
-                        *other_head.get_prev_mut() = Rawlink::some(tail);
-                        *tail.get_next_mut() = Rawlink::some(other_head);
-                        *head.get_prev_mut() = Rawlink::some(other_tail);
+                            other_head.get_prev().resolve().unwrap();
+                        other_tail.set_next(Rawlink::some(head));
+                        other_head.set_prev(Rawlink::some(tail));
+                        tail.set_next(Rawlink::some(other_head));
+                        head.set_prev(Rawlink::some(other_tail));
                         self.length += other.length;
                     }
                 }
@@ -374,16 +565,10 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
         let tail = if self.length == 0 {
             Rawlink::none()
         } else {
-            *self.head.resolve().unwrap().get_prev()
+            self.head.resolve().unwrap().get_prev()
         };
         Iter{nelem: self.length, head: self.head,
-             tail: tail, _marker: PhantomData}
-    }
-
-    /// Consumes the list into an iterator yielding elements by value.
-    #[inline]
-    pub fn into_iter(self) -> IntoIter<P, T, S, L> {
-        IntoIter{list: self}
+             tail, _marker: PhantomData}
     }
 
     /// Returns `true` if the `LinkedList` is empty
@@ -399,6 +584,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut dl = LinkedList::new();
     /// assert!(dl.is_empty());
@@ -406,6 +592,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// dl.push_front(Box::new(MyI32::new(1)));
     /// assert!(!dl.is_empty());
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -425,6 +612,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut dl = LinkedList::new();
     ///
@@ -437,6 +625,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// dl.push_back(Box::new(MyI32::new(3)));
     /// assert_eq!(dl.len(), 3);
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     #[inline]
     pub fn len(&self) -> usize {
@@ -453,6 +642,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut dl = LinkedList::new();
     ///
@@ -465,6 +655,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// assert_eq!(dl.len(), 0);
     /// assert_eq!(dl.front(), None);
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     #[inline]
     pub fn clear(&mut self) {
@@ -483,6 +674,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut dl = LinkedList::new();
     /// assert_eq!(dl.front(), None);
@@ -490,6 +682,8 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// dl.push_front(Box::new(MyI32::new(1)));
     /// assert_eq!(dl.front(), Some(&1));
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
     #[inline]
     pub fn front(&self) -> Option<&P> {
         self.head.resolve().map(|head| unsafe{head.container_of()}.get_val())
@@ -507,6 +701,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut dl = LinkedList::new();
     /// assert_eq!(dl.front(), None);
@@ -520,9 +715,10 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// }
     /// assert_eq!(dl.front(), Some(&5));
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     #[inline]
-    pub fn front_mut(&mut self) -> Option<&mut P> {
+    pub fn front_mut(&mut self) -> Option<&mut P> where T: DerefMut {
         self.head.resolve_mut().map(|head| {
             unsafe {head.container_of_mut()}.get_val_mut()
         })
@@ -540,6 +736,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut dl = LinkedList::new();
     /// assert_eq!(dl.back(), None);
@@ -547,6 +744,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// dl.push_back(Box::new(MyI32::new(1)));
     /// assert_eq!(dl.back(), Some(&1));
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     #[inline]
     pub fn back(&self) -> Option<&P> {
@@ -567,6 +765,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut dl = LinkedList::new();
     /// assert_eq!(dl.back(), None);
@@ -580,38 +779,63 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// }
     /// assert_eq!(dl.back(), Some(&5));
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     #[inline]
-    pub fn back_mut(&mut self) -> Option<&mut P> {
-        // if pprev is not none, then it points to the link before the tail
-        self.head.resolve_mut().map(|head| {
-            let tail = head.get_prev_mut().resolve_mut().unwrap();
+    pub fn back_mut(&mut self) -> Option<&mut P> where T: DerefMut {
+        self.tail().resolve_mut().map(|tail| {
             unsafe{tail.container_of_mut()}.get_val_mut()
         })
     }
 
-    fn insert(&mut self, elt: &mut L, prev: &mut L, next: &mut L) {
-        *next.get_prev_mut() = Rawlink::some(elt);
-        *elt.get_next_mut() = Rawlink::some(next);
-        *elt.get_prev_mut() = Rawlink::some(prev);
-        *prev.get_next_mut() = Rawlink::some(elt);
+    fn insert(&mut self, elt: &L, prev: &L, next: &L) {
+        next.set_prev(Rawlink::some(elt));
+        elt.set_next(Rawlink::some(next));
+        elt.set_prev(Rawlink::some(prev));
+        prev.set_next(Rawlink::some(elt));
         self.length += 1;
     }
 
-    fn delete(&mut self, elt: &mut L) {
-        debug_assert!(*elt.get_next() != Rawlink::none());
-        debug_assert!(*elt.get_prev() != Rawlink::none());
+    fn delete(&mut self, elt: &L) {
+        debug_assert!(elt.get_next() != Rawlink::none());
+        debug_assert!(elt.get_prev() != Rawlink::none());
 
-        let next = elt.get_next_mut().resolve_mut().unwrap();
-        let prev = elt.get_prev_mut().resolve_mut().unwrap();
-        *next.get_prev_mut() = Rawlink::some(prev);
-        *prev.get_next_mut() = Rawlink::some(next);
+        let next = elt.get_next().resolve().unwrap();
+        let prev = elt.get_prev().resolve().unwrap();
+        next.set_prev(Rawlink::some(prev));
+        prev.set_next(Rawlink::some(next));
 
-        elt.get_next_mut().take();
-        elt.get_prev_mut().take();
+        elt.set_next(Rawlink::none());
+        elt.set_prev(Rawlink::none());
         self.length -= 1;
     }
 
+    fn tail(&self) -> Rawlink<L> {
+        match self.head.resolve() {
+            None => Rawlink::none(),
+            Some(head) => head.get_prev()
+        }
+    }
+
+    /// Links all the elements of `other` in between `prev` and `next`, which
+    /// must be adjacent elements of this list
+    fn splice(&mut self, other: &mut LinkedList<P, T, S, L>, prev: &L,
+              next: &L) {
+        match other.head.take().resolve() {
+            None => {},
+            Some(other_head) => {
+                let other_tail =
+                    other_head.get_prev().resolve().unwrap();
+                prev.set_next(Rawlink::some(other_head));
+                other_head.set_prev(Rawlink::some(prev));
+                other_tail.set_next(Rawlink::some(next));
+                next.set_prev(Rawlink::some(other_tail));
+                self.length += other.length;
+            }
+        }
+        other.length = 0;
+    }
+
     /// Adds an element first in the list.
     ///
     /// This operation should compute in O(1) time.
@@ -625,6 +849,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut d = LinkedList::new();
     /// assert_eq!(d.pop_front(), None);
@@ -635,21 +860,22 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// assert_eq!(d.pop_front(), Some(Box::new(MyI32::new(1))));
     /// assert_eq!(d.pop_front(), None);
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
-    pub fn push_front(&mut self, mut elt: T) {
+    pub fn push_front(&mut self, elt: T) {
         // ensure links are not already being used
         elt.get_links().check_links();
 
         if self.is_empty() {
-            *elt.get_next_mut() = Rawlink::some(elt.get_links_mut());
-            *elt.get_prev_mut() = Rawlink::some(elt.get_links_mut());
+            elt.get_links().set_next(Rawlink::some(elt.get_links()));
+            elt.get_links().set_prev(Rawlink::some(elt.get_links()));
             self.length += 1;
         } else {
-            let head = self.head.resolve_mut().unwrap();
-            let tail = head.get_prev_mut().resolve_mut().unwrap();
-            self.insert(elt.get_links_mut(), tail, head);
+            let head = self.head.resolve().unwrap();
+            let tail = head.get_prev().resolve().unwrap();
+            self.insert(elt.get_links(), tail, head);
         }
-        self.head = Rawlink::some(elt.get_links_mut());
+        self.head = Rawlink::some(elt.get_links());
         unsafe { elt.take() };
     }
 
@@ -667,6 +893,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut d = LinkedList::new();
     /// assert_eq!(d.pop_front(), None);
@@ -677,17 +904,18 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// assert_eq!(d.pop_front(), Some(Box::new(MyI32::new(1))));
     /// assert_eq!(d.pop_front(), None);
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     pub fn pop_front(&mut self) -> Option<T> {
-        self.head.take().resolve_mut().map(|mut head| {
+        self.head.take().resolve().map(|head| {
             if self.length == 1 {
                 self.head = Rawlink::none();
             } else {
-                self.head = *head.get_next_mut();
+                self.head = head.get_next();
             }
             self.delete(head);
             unsafe {
-                T::from_raw(head.container_of_mut() as *mut _)
+                T::from_raw(head.container_of() as *const S as *mut S)
             }
         })
     }
@@ -705,14 +933,16 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut d = LinkedList::new();
     /// d.push_back(Box::new(MyI32::new(1)));
     /// d.push_back(Box::new(MyI32::new(3)));
     /// assert_eq!(&3, d.back().unwrap());
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
-    pub fn push_back(&mut self, mut elt: T) {
+    pub fn push_back(&mut self, elt: T) {
         if self.is_empty() {
             return self.push_front(elt);
         }
@@ -720,9 +950,9 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
         // ensure links are not already being used
         elt.get_links().check_links();
 
-        let head = self.head.resolve_mut().unwrap();
-        let tail = head.get_prev_mut().resolve_mut().unwrap();
-        self.insert(elt.get_links_mut(), tail, head);
+        let head = self.head.resolve().unwrap();
+        let tail = head.get_prev().resolve().unwrap();
+        self.insert(elt.get_links(), tail, head);
         unsafe { elt.take() };
     }
 
@@ -740,6 +970,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut d = LinkedList::new();
     /// assert_eq!(d.pop_back(), None);
@@ -747,14 +978,109 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// d.push_back(Box::new(MyI32::new(3)));
     /// assert_eq!(d.pop_back(), Some(Box::new(MyI32::new(3))));
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     pub fn pop_back(&mut self) -> Option<T> {
         if self.len() <= 1 { return self.pop_front(); }
 
-        let head = self.head.resolve_mut().unwrap();
-        let tail = head.get_prev_mut().resolve_mut().unwrap();
+        let head = self.head.resolve().unwrap();
+        let tail = head.get_prev().resolve().unwrap();
         self.delete(tail);
-        Some(unsafe {T::from_raw(tail.container_of_mut() as *mut _)})
+        Some(unsafe {T::from_raw(tail.container_of() as *const S as *mut S)})
+    }
+
+    /// Unlinks `elt` from the list and returns ownership of it.
+    ///
+    /// This operation should compute in O(1) time. In debug builds the list is
+    /// walked to check that `elt` belongs to it, which takes O(n) time.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this list. Removing an element of another list would leave
+    /// that list with a dangling head or an incorrect length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not linked into any list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// # use std::default::Default;
+    /// use intrusive_containers::LinkedList;
+    ///
+    /// define_list_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut d = LinkedList::new();
+    /// let two = Box::new(MyI32::new(2));
+    /// let ptr: *const MyI32 = &*two;
+    /// d.push_back(Box::new(MyI32::new(1)));
+    /// d.push_back(two);
+    /// d.push_back(Box::new(MyI32::new(3)));
+    ///
+    /// assert_eq!(unsafe { d.remove(&*ptr) }, Box::new(MyI32::new(2)));
+    /// assert_eq!(d.len(), 2);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links();
+        debug_assert!(self.contains_links(links),
+                      "Cannot remove an element of another list");
+        self.unlink(links);
+        T::from_raw(links.container_of() as *const S as *mut S)
+    }
+
+    /// Unlinks `links` without checking that it is an element of this list
+    ///
+    /// # Safety
+    ///
+    /// `links` must currently be an element of this list.
+    pub(crate) unsafe fn unlink(&mut self, links: &L) {
+        if self.length == 1 {
+            self.head = Rawlink::none();
+        } else if self.head == Rawlink::some(links) {
+            self.head = links.get_next();
+        }
+        self.delete(links);
+    }
+
+    /// Returns the last element, or `None` if the list is empty
+    pub(crate) fn back_node(&self) -> Option<&S> {
+        self.tail().resolve().map(|tail| unsafe { tail.container_of() })
+    }
+
+    /// Returns the first element whose value satisfies `pred`
+    pub(crate) fn find_node<F>(&self, mut pred: F) -> Option<&S>
+        where F: FnMut(&P) -> bool
+    {
+        let mut cur = self.head;
+        for _ in 0..self.length {
+            let node = cur.resolve().unwrap();
+            let elt = unsafe { node.container_of() };
+            if pred(elt.get_val()) {
+                return Some(elt);
+            }
+            cur = node.get_next();
+        }
+        None
+    }
+
+    /// Walks the list to check whether `links` is one of its elements
+    pub(crate) fn contains_links(&self, links: &L) -> bool {
+        let mut cur = self.head;
+        for _ in 0..self.length {
+            let node = cur.resolve().unwrap();
+            if ptr::eq(node, links) {
+                return true;
+            }
+            cur = node.get_next();
+        }
+        false
     }
 
     /// Splits the list into two at the given index. Returns everything after the given index,
@@ -775,6 +1101,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     ///
     /// define_list_element!(MyI32 = i32 : MyLink);
     ///
+    /// # #[cfg(feature = "alloc")]
     /// # fn main() {
     /// let mut d = LinkedList::new();
     ///
@@ -787,20 +1114,21 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
     /// assert_eq!(splitted.pop_front(), Some(Box::new(MyI32::new(1))));
     /// assert_eq!(splitted.pop_front(), None);
     /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
     /// ```
     pub fn split_off(&mut self, at: usize) -> LinkedList<P, T, S, L> {
         let len = self.len();
         assert!(at <= len, "Cannot split off at a nonexistent index");
         if at == 0 {
-            return mem::replace(self, LinkedList::new());
+            return mem::take(self);
         } else if at == len {
             return LinkedList::new();
         }
 
         // Below, we iterate towards the `i-1`th node, either from the start or the end,
         // depending on which would be faster.
-        let mut split_node = if at - 1 <= len - 1 - (at - 1) {
-            let mut iter = self.iter_mut();
+        let split_node = if at - 1 <= len - 1 - (at - 1) {
+            let mut iter = self.iter();
             // instead of skipping using .skip() (which creates a new struct),
             // we skip manually so we can access the head field without
             // depending on implementation details of Skip
@@ -810,26 +1138,35 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
             iter.head
         } else {
             // better off starting from the end
-            let mut iter = self.iter_mut();
+            let mut iter = self.iter();
             for _ in 0..len - 1 - (at - 1) {
                 iter.next_back();
             }
             iter.tail
         };
 
-        let mut pre_split = split_node.resolve_mut().unwrap();
-        let mut post_split = pre_split.get_next_mut().resolve_mut().unwrap();
-        let mut head = self.head.resolve_mut().unwrap();
-        let mut tail = head.get_prev_mut().resolve_mut().unwrap();
+        let pre_split = split_node.resolve().unwrap();
+        let post_split = pre_split.get_next().resolve().unwrap();
+        self.split_between(pre_split, post_split, at)
+    }
+
+    /// Cuts the list in between `prev` and `next`, which must be adjacent
+    /// elements. `self` keeps the run starting at its head and ending at
+    /// `prev`, of length `at`, while the remainder is returned.
+    fn split_between(&mut self, prev: &L, next: &L, at: usize)
+                     -> LinkedList<P, T, S, L> {
+        let len = self.len();
+        let head = self.head.resolve().unwrap();
+        let tail = head.get_prev().resolve().unwrap();
 
-        *head.get_prev_mut() = Rawlink::some(pre_split);
-        *pre_split.get_next_mut() = Rawlink::some(head);
-        *post_split.get_prev_mut() = Rawlink::some(tail);
-        *tail.get_next_mut() = Rawlink::some(post_split);
+        head.set_prev(Rawlink::some(prev));
+        prev.set_next(Rawlink::some(head));
+        next.set_prev(Rawlink::some(tail));
+        tail.set_next(Rawlink::some(next));
 
         self.length = at;
         LinkedList {
-            head: Rawlink::some(post_split),
+            head: Rawlink::some(next),
             length: len - at,
             _marker: PhantomData,
             _marker2: PhantomData,
@@ -840,7 +1177,7 @@ impl<P, T, S, L> LinkedList<P, T, S, L>
 }
 
 impl<'a, P, T, S, L> LinkedList<P, T, S, L>
-    where T: OwningPointer<Target=S> + 'a,
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
           S: Node<P, L> + 'a,
           L: Linkable<Container=T::Target> + 'a
 {
@@ -853,17 +1190,88 @@ impl<'a, P, T, S, L> LinkedList<P, T, S, L>
         let tail = if self.length == 0 {
             Rawlink::none()
         } else {
-            *self.head.resolve().unwrap().get_prev()
+            self.head.resolve().unwrap().get_prev()
         };
         IterMut {
             nelem: self.length,
             head: self.head,
-            tail: tail,
+            tail,
             list: self
         }
     }
 }
 
+impl<'a, P, T, S, L> LinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
+{
+    /// Provides a cursor at the front element.
+    ///
+    /// The cursor is pointing to the "ghost" non-element if the list is empty.
+    #[inline]
+    pub fn cursor_front(&'a self) -> Cursor<'a, P, T, S, L> {
+        Cursor { index: 0, current: self.head, list: self }
+    }
+
+    /// Provides a cursor at the back element.
+    ///
+    /// The cursor is pointing to the "ghost" non-element if the list is empty.
+    #[inline]
+    pub fn cursor_back(&'a self) -> Cursor<'a, P, T, S, L> {
+        Cursor { index: self.length.saturating_sub(1), current: self.tail(),
+                 list: self }
+    }
+
+    /// Provides a cursor with editing operations at the front element.
+    ///
+    /// The cursor is pointing to the "ghost" non-element if the list is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// # use std::default::Default;
+    /// use intrusive_containers::LinkedList;
+    ///
+    /// define_list_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut d = LinkedList::new();
+    /// d.push_back(Box::new(MyI32::new(1)));
+    /// d.push_back(Box::new(MyI32::new(2)));
+    /// d.push_back(Box::new(MyI32::new(3)));
+    ///
+    /// {
+    ///     let mut cursor = d.cursor_front_mut();
+    ///     cursor.move_next();
+    ///     assert_eq!(cursor.remove_current(), Some(Box::new(MyI32::new(2))));
+    ///     assert_eq!(cursor.current(), Some(&mut 3));
+    ///     cursor.insert_before(Box::new(MyI32::new(4)));
+    /// }
+    ///
+    /// let v: Vec<_> = d.iter().cloned().collect();
+    /// assert_eq!(v, [1, 4, 3]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    #[inline]
+    pub fn cursor_front_mut(&'a mut self) -> CursorMut<'a, P, T, S, L> {
+        CursorMut { index: 0, current: self.head, list: self }
+    }
+
+    /// Provides a cursor with editing operations at the back element.
+    ///
+    /// The cursor is pointing to the "ghost" non-element if the list is empty.
+    #[inline]
+    pub fn cursor_back_mut(&'a mut self) -> CursorMut<'a, P, T, S, L> {
+        CursorMut { index: self.length.saturating_sub(1), current: self.tail(),
+                    list: self }
+    }
+}
+
 impl<P, T, S, L> Default for LinkedList<P, T, S, L>
     where T: OwningPointer<Target=S>,
           S: Node<P, L>,
@@ -875,25 +1283,23 @@ impl<P, T, S, L> Default for LinkedList<P, T, S, L>
     }
 }
 
-impl<P, T, S, L> Clone for LinkedList<P, T, S, L>
-    where T: OwningPointer<Target=S> + Clone,
-          S: Node<P, L>,
-          L: Linkable<Container=T::Target>
+// Cloning a `Box` clones its element, whose links are not on any list. A
+// shared pointer such as `Rc` would be cloned into the element already on this
+// list, so only lists of boxes can be cloned.
+#[cfg(feature="alloc")]
+impl<P, S, L> Clone for LinkedList<P, Box<S>, S, L>
+    where S: Node<P, L> + Clone,
+          L: Linkable<Container=S>
 {
-    fn clone(&self) -> LinkedList<P, T, S, L> {
-        let mut other: LinkedList<P, T, S, L> = Default::default();
+    fn clone(&self) -> LinkedList<P, Box<S>, S, L> {
+        let mut other = LinkedList::new();
         let mut head = self.head;
         let mut nelem = self.length;
         while nelem > 0 {
-            let h = head.resolve_mut().unwrap();
+            let h = head.resolve().unwrap();
             nelem -= 1;
-            head = *h.get_next();
-            let t = unsafe {T::from_raw(h.container_of_mut() as *mut S)};
-            let mut new_t = t.clone();
-            new_t.get_next_mut().take();
-            new_t.get_prev_mut().take();
-            other.push_back(new_t);
-            unsafe {t.take()};
+            head = h.get_next();
+            other.push_back(Box::new(unsafe { h.container_of() }.clone()));
         }
         other
     }
@@ -906,11 +1312,11 @@ impl<P, T, S, L> fmt::Debug for LinkedList<P, T, S, L>
           L: Linkable<Container=T::Target>
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        try!(write!(f, "["));
+        write!(f, "[")?;
 
         for (i, e) in self.iter().enumerate() {
-            if i != 0 { try!(write!(f, ", ")); }
-            try!(write!(f, "{:?}", e));
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
         }
 
         write!(f, "]")
@@ -926,11 +1332,15 @@ impl<P, T, S, L> Drop for LinkedList<P, T, S, L>
             if self.length == 0 {
                 return;
             }
-            let head = self.head.resolve_mut().unwrap();
-            self.head = *head.get_next();
-            head.get_next_mut().take();
-            head.get_prev_mut().take();
+            let head = self.head.resolve().unwrap();
+            self.head = head.get_next();
+            head.set_next(Rawlink::none());
+            head.set_prev(Rawlink::none());
             self.length -= 1;
+            // hand back the reference held by the list
+            drop(unsafe {
+                T::from_raw(head.container_of() as *const S as *mut S)
+            });
         }
     }
 }
@@ -979,8 +1389,10 @@ impl<P, T, S, L> IntoIterator for LinkedList<P, T, S, L>
     type Item = T;
     type IntoIter = IntoIter<P, T, S, L>;
 
+    /// Consumes the list into an iterator yielding elements by value.
+    #[inline]
     fn into_iter(self) -> IntoIter<P, T, S, L> {
-        self.into_iter()
+        IntoIter{list: self}
     }
 }
 
@@ -999,7 +1411,7 @@ impl<'a, P, T, S, L> IntoIterator for &'a LinkedList<P, T, S, L>
 }
 
 impl<'a, P, T, S, L> IntoIterator for &'a mut LinkedList<P, T, S, L>
-    where T: OwningPointer<Target=S> + 'a,
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
           P: 'a,
           S: Node<P, L> + 'a,
           L: Linkable<Container=T::Target> + 'a
@@ -1020,12 +1432,7 @@ impl<P, T, S, L> PartialEq for LinkedList<P, T, S, L>
 {
     fn eq(&self, other: &LinkedList<P, T, S, L>) -> bool {
         self.len() == other.len() &&
-            iter::order::eq(self.iter(), other.iter())
-    }
-
-    fn ne(&self, other: &LinkedList<P, T, S, L>) -> bool {
-        self.len() != other.len() ||
-            iter::order::ne(self.iter(), other.iter())
+            self.iter().eq(other.iter())
     }
 }
 
@@ -1043,7 +1450,7 @@ impl<P, T, S, L> PartialOrd for LinkedList<P, T, S, L>
           L: Linkable<Container=T::Target>
 {
     fn partial_cmp(&self, other: &LinkedList<P, T, S, L>) -> Option<Ordering> {
-        iter::order::partial_cmp(self.iter(), other.iter())
+        self.iter().partial_cmp(other.iter())
     }
 }
 
@@ -1054,17 +1461,41 @@ impl<P, T, S, L> Ord for LinkedList<P, T, S, L>
           L: Linkable<Container=T::Target>
 {
     fn cmp(&self, other: &LinkedList<P, T, S, L>) -> Ordering {
-        iter::order::cmp(self.iter(), other.iter())
+        self.iter().cmp(other.iter())
     }
 }
 
+unsafe impl<P, T, S, L> Send for LinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: Linkable<Container=T::Target>
+{}
+
 // Links impls
 
+unsafe impl<L: Linkable> Send for Links<L> {}
+
+impl<L: Linkable> fmt::Debug for Links<L>
+{
+    /// The links are not shown, since only the list the element is on may
+    /// follow them
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Links { .. }")
+    }
+}
+
+impl<L: Linkable> Clone for Links<L>
+{
+    /// A clone is not on any list, even if the original is
+    fn clone(&self) -> Links<L> {
+        Default::default()
+    }
+}
+
 impl<L: Linkable> Drop for Links<L>
 {
     fn drop(&mut self) {
-        assert!(self.next.resolve().is_none());
-        assert!(self.prev.resolve().is_none());
+        assert!(self.next.get().resolve().is_none());
+        assert!(self.prev.get().resolve().is_none());
     }
 }
 
@@ -1121,7 +1552,7 @@ impl<'a, P: 'a, T: Node<P, L> + 'a, L: Linkable<Container=T> + 'a> Iterator
         }
         let head = self.head.resolve().unwrap();
         self.nelem -= 1;
-        self.head = *head.get_next();
+        self.head = head.get_next();
         let ret = unsafe { head.container_of() }.get_val();
         Some(ret)
     }
@@ -1142,7 +1573,7 @@ impl<'a, P: 'a, T: Node<P, L> + 'a, L: Linkable<Container=T> + 'a>
         }
         let tail = self.tail.resolve().unwrap();
         self.nelem -= 1;
-        self.tail = *tail.get_prev();
+        self.tail = tail.get_prev();
         let ret = unsafe { tail.container_of() }.get_val();
         Some(ret)
     }
@@ -1154,7 +1585,7 @@ impl<'a, P: 'a, T: Node<P, L> + 'a, L: Linkable<Container=T> + 'a>
 // // IterMut impls
 
 impl<'a, P, T, S, L> Iterator for IterMut<'a, P, T, S, L>
-    where T: OwningPointer<Target=S> + 'a,
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
           P: 'a,
           S: Node<P, L> + 'a,
           L: Linkable<Container=T::Target> + 'a
@@ -1168,7 +1599,7 @@ impl<'a, P, T, S, L> Iterator for IterMut<'a, P, T, S, L>
         }
         let head = self.head.resolve_mut().unwrap();
         self.nelem -= 1;
-        self.head = *head.get_next();
+        self.head = head.get_next();
         let ret = unsafe { head.container_of_mut() }.get_val_mut();
         Some(ret)
     }
@@ -1180,7 +1611,7 @@ impl<'a, P, T, S, L> Iterator for IterMut<'a, P, T, S, L>
 }
 
 impl<'a, P, T, S, L> DoubleEndedIterator for IterMut<'a, P, T, S, L>
-    where T: OwningPointer<Target=S> + 'a,
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
           P: 'a,
           S: Node<P, L> + 'a,
           L: Linkable<Container=T::Target> + 'a
@@ -1192,21 +1623,21 @@ impl<'a, P, T, S, L> DoubleEndedIterator for IterMut<'a, P, T, S, L>
         }
         let tail = self.tail.resolve_mut().unwrap();
         self.nelem -= 1;
-        self.tail = *tail.get_prev();
+        self.tail = tail.get_prev();
         let ret = unsafe { tail.container_of_mut() }.get_val_mut();
         Some(ret)
     }
 }
 
 impl<'a, P, T, S, L> ExactSizeIterator for IterMut<'a, P, T, S, L>
-    where T: OwningPointer<Target=S> + 'a,
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
           P: 'a,
           S: Node<P, L> + 'a,
           L: Linkable<Container=T::Target> + 'a
 {}
 
 impl<'a, P, T, S, L> IterMut<'a, P, T, S, L>
-    where T: OwningPointer<Target=S> + 'a,
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
           P: 'a,
           S: Node<P, L> + 'a,
           L: Linkable<Container=T::Target> + 'a
@@ -1214,7 +1645,7 @@ impl<'a, P, T, S, L> IterMut<'a, P, T, S, L>
     /// Inserts `elt` just after the element most recently returned by `.next()`.
     /// The inserted element does not appear in the iteration.
     #[inline]
-    pub fn insert_next(&mut self, mut elt: T) {
+    pub fn insert_next(&mut self, elt: T) {
         // ensure links are not already being used
         elt.get_links().check_links();
 
@@ -1226,9 +1657,9 @@ impl<'a, P, T, S, L> IterMut<'a, P, T, S, L>
             return self.list.push_front(elt);
         }
 
-        let next = self.head.resolve_mut().unwrap();
-        let prev = next.get_prev_mut().resolve_mut().unwrap();
-        self.list.insert(elt.get_links_mut(), prev, next);
+        let next = self.head.resolve().unwrap();
+        let prev = next.get_prev().resolve().unwrap();
+        self.list.insert(elt.get_links(), prev, next);
         unsafe { elt.take() };
     }
 
@@ -1244,40 +1675,416 @@ impl<'a, P, T, S, L> IterMut<'a, P, T, S, L>
     }
 }
 
-// IntoIter impls
+// Cursor impls
 
-impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
-    where T: OwningPointer<Target=S>,
-          S: Node<P, L>,
-          L: Linkable<Container=T::Target>
+impl<'a, P, T, S, L> Cursor<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
 {
-    type Item = T;
+    /// Returns the cursor position index within the `LinkedList`.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the "ghost"
+    /// non-element.
+    #[inline]
+    pub fn index(&self) -> Option<usize> {
+        self.current.resolve().map(|_| self.index)
+    }
 
+    /// Moves the cursor to the next element of the `LinkedList`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will
+    /// move it to the first element of the `LinkedList`. If it is pointing to
+    /// the last element then this will move it to the "ghost" non-element.
     #[inline]
-    fn next(&mut self) -> Option<T> { self.list.pop_front() }
+    pub fn move_next(&mut self) {
+        let (current, index) = next_position(self.list, self.current,
+                                             self.index);
+        self.current = current;
+        self.index = index;
+    }
 
+    /// Moves the cursor to the previous element of the `LinkedList`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will
+    /// move it to the last element of the `LinkedList`. If it is pointing to
+    /// the first element then this will move it to the "ghost" non-element.
     #[inline]
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.list.length, Some(self.list.length))
+    pub fn move_prev(&mut self) {
+        let (current, index) = prev_position(self.list, self.current,
+                                             self.index);
+        self.current = current;
+        self.index = index;
     }
-}
 
-impl<P, T, S, L> DoubleEndedIterator for IntoIter<P, T, S, L>
-    where T: OwningPointer<Target=S>,
-          S: Node<P, L>,
-          L: Linkable<Container=T::Target>
-{
+    /// Returns a reference to the element that the cursor is currently
+    /// pointing to, or `None` at the "ghost" non-element.
     #[inline]
-    fn next_back(&mut self) -> Option<T> { self.list.pop_back() }
-}
+    pub fn current(&self) -> Option<&'a P> {
+        self.current.resolve().map(|cur| unsafe { cur.container_of() }.get_val())
+    }
 
-impl<P, T, S, L> Clone for IntoIter<P, T, S, L>
-    where T: OwningPointer<Target=S> + Clone,
+    /// Returns a reference to the next element, without moving the cursor.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the first element of the `LinkedList`. If it is pointing to the last
+    /// element then this returns `None`.
+    #[inline]
+    pub fn peek_next(&self) -> Option<&'a P> {
+        next_position(self.list, self.current, self.index).0.resolve()
+            .map(|next| unsafe { next.container_of() }.get_val())
+    }
+
+    /// Returns a reference to the previous element, without moving the cursor.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the last element of the `LinkedList`. If it is pointing to the first
+    /// element then this returns `None`.
+    #[inline]
+    pub fn peek_prev(&self) -> Option<&'a P> {
+        prev_position(self.list, self.current, self.index).0.resolve()
+            .map(|prev| unsafe { prev.container_of() }.get_val())
+    }
+}
+
+impl<'a, P, T, S, L> Clone for Cursor<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
+{
+    fn clone(&self) -> Cursor<'a, P, T, S, L> {
+        Cursor {
+            index: self.index,
+            current: self.current,
+            list: self.list
+        }
+    }
+}
+
+impl<'a, P, T, S, L> CursorMut<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: Linkable<Container=T::Target> + 'a
+{
+    /// Returns the cursor position index within the `LinkedList`.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the "ghost"
+    /// non-element.
+    #[inline]
+    pub fn index(&self) -> Option<usize> {
+        self.current.resolve().map(|_| self.index)
+    }
+
+    /// Moves the cursor to the next element of the `LinkedList`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will
+    /// move it to the first element of the `LinkedList`. If it is pointing to
+    /// the last element then this will move it to the "ghost" non-element.
+    #[inline]
+    pub fn move_next(&mut self) {
+        let (current, index) = next_position(self.list, self.current,
+                                             self.index);
+        self.current = current;
+        self.index = index;
+    }
+
+    /// Moves the cursor to the previous element of the `LinkedList`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will
+    /// move it to the last element of the `LinkedList`. If it is pointing to
+    /// the first element then this will move it to the "ghost" non-element.
+    #[inline]
+    pub fn move_prev(&mut self) {
+        let (current, index) = prev_position(self.list, self.current,
+                                             self.index);
+        self.current = current;
+        self.index = index;
+    }
+
+    /// Returns a mutable reference to the element that the cursor is
+    /// currently pointing to, or `None` at the "ghost" non-element.
+    #[inline]
+    pub fn current(&mut self) -> Option<&mut P> where T: DerefMut {
+        self.current.resolve_mut().map(|cur| {
+            unsafe { cur.container_of_mut() }.get_val_mut()
+        })
+    }
+
+    /// Returns a mutable reference to the next element, without moving the
+    /// cursor.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the first element of the `LinkedList`. If it is pointing to the last
+    /// element then this returns `None`.
+    #[inline]
+    pub fn peek_next(&mut self) -> Option<&mut P> where T: DerefMut {
+        next_position(self.list, self.current, self.index).0.resolve_mut()
+            .map(|next| unsafe { next.container_of_mut() }.get_val_mut())
+    }
+
+    /// Returns a mutable reference to the previous element, without moving
+    /// the cursor.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the last element of the `LinkedList`. If it is pointing to the first
+    /// element then this returns `None`.
+    #[inline]
+    pub fn peek_prev(&mut self) -> Option<&mut P> where T: DerefMut {
+        prev_position(self.list, self.current, self.index).0.resolve_mut()
+            .map(|prev| unsafe { prev.container_of_mut() }.get_val_mut())
+    }
+
+    /// Returns a read-only cursor pointing to the current element.
+    ///
+    /// The lifetime of the returned `Cursor` is bound to that of the
+    /// `CursorMut`, which means it cannot outlive the `CursorMut` and that the
+    /// `CursorMut` is frozen for the lifetime of the `Cursor`.
+    #[inline]
+    pub fn as_cursor<'b>(&'b self) -> Cursor<'b, P, T, S, L> {
+        Cursor { index: self.index, current: self.current, list: self.list }
+    }
+
+    /// Inserts a new element into the `LinkedList` after the current one.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the new
+    /// element is inserted at the front of the `LinkedList`.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn insert_after(&mut self, elt: T) {
+        match self.current.resolve() {
+            None => {
+                self.list.push_front(elt);
+                self.index = self.list.len();
+            }
+            Some(cur) => {
+                // ensure links are not already being used
+                elt.get_links().check_links();
+
+                let next = cur.get_next().resolve().unwrap();
+                self.list.insert(elt.get_links(), cur, next);
+                unsafe { elt.take() };
+            }
+        }
+    }
+
+    /// Inserts a new element into the `LinkedList` before the current one.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the new
+    /// element is inserted at the end of the `LinkedList`.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn insert_before(&mut self, elt: T) {
+        match self.current.resolve() {
+            None => self.list.push_back(elt),
+            Some(cur) => {
+                // ensure links are not already being used
+                elt.get_links().check_links();
+
+                let prev = cur.get_prev().resolve().unwrap();
+                self.list.insert(elt.get_links(), prev, cur);
+                if self.list.head == self.current {
+                    self.list.head = Rawlink::some(elt.get_links());
+                }
+                unsafe { elt.take() };
+            }
+        }
+        self.index += 1;
+    }
+
+    /// Removes the current element from the `LinkedList` and returns it.
+    ///
+    /// The cursor is moved to point to the next element in the list. If the
+    /// cursor is pointing at the "ghost" non-element then no element is
+    /// removed and `None` is returned.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let cur = self.current.resolve()?;
+        let next = next_position(self.list, self.current, self.index).0;
+        if self.list.len() == 1 {
+            self.list.head = Rawlink::none();
+        } else if self.list.head == self.current {
+            self.list.head = next;
+        }
+        self.list.delete(cur);
+        self.current = next;
+        if self.current.resolve().is_none() {
+            self.index = self.list.len();
+        }
+        Some(unsafe { T::from_raw(cur.container_of() as *const S as *mut S) })
+    }
+
+    /// Splits the list into two after the current element. This will return a
+    /// new list consisting of everything after the cursor, with the original
+    /// list retaining everything before.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the entire
+    /// contents of the `LinkedList` are moved.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn split_after(&mut self) -> LinkedList<P, T, S, L> {
+        let cur = match self.current.resolve() {
+            None => {
+                self.index = 0;
+                return mem::take(self.list);
+            }
+            Some(cur) => cur
+        };
+        match next_position(self.list, self.current, self.index).0
+            .resolve() {
+            None => LinkedList::new(),
+            Some(next) => self.list.split_between(cur, next, self.index + 1)
+        }
+    }
+
+    /// Splits the list into two before the current element. This will return
+    /// a new list consisting of everything before the cursor, with the
+    /// original list retaining everything after.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the entire
+    /// contents of the `LinkedList` are moved.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn split_before(&mut self) -> LinkedList<P, T, S, L> {
+        let cur = match self.current.resolve() {
+            None => {
+                self.index = 0;
+                return mem::take(self.list);
+            }
+            Some(cur) => cur
+        };
+        if self.index == 0 {
+            return LinkedList::new();
+        }
+        let prev = cur.get_prev().resolve().unwrap();
+        let mut after = self.list.split_between(prev, cur, self.index);
+        mem::swap(self.list, &mut after);
+        self.index = 0;
+        after
+    }
+
+    /// Inserts the elements from the given `LinkedList` after the current
+    /// one.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the new
+    /// elements are inserted at the start of the `LinkedList`.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn splice_after(&mut self, mut list: LinkedList<P, T, S, L>) {
+        match self.current.resolve() {
+            None => {
+                list.append(self.list);
+                mem::swap(self.list, &mut list);
+                self.index = self.list.len();
+            }
+            Some(cur) => {
+                let next = cur.get_next().resolve().unwrap();
+                self.list.splice(&mut list, cur, next);
+            }
+        }
+    }
+
+    /// Inserts the elements from the given `LinkedList` before the current
+    /// one.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the new
+    /// elements are inserted at the end of the `LinkedList`.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn splice_before(&mut self, mut list: LinkedList<P, T, S, L>) {
+        self.index += list.len();
+        match self.current.resolve() {
+            None => self.list.append(&mut list),
+            Some(cur) => {
+                if self.list.head == self.current {
+                    list.append(self.list);
+                    mem::swap(self.list, &mut list);
+                } else {
+                    let prev = cur.get_prev().resolve().unwrap();
+                    self.list.splice(&mut list, prev, cur);
+                }
+            }
+        }
+    }
+}
+
+/// Computes the position following `current` in `list`, passing through the
+/// "ghost" non-element between the tail and the head
+fn next_position<P, T, S, L>(list: &LinkedList<P, T, S, L>, current: Rawlink<L>,
+                             index: usize) -> (Rawlink<L>, usize)
+    where T: OwningPointer<Target=S>,
           S: Node<P, L>,
           L: Linkable<Container=T::Target>
 {
+    match current.resolve() {
+        None => (list.head, 0),
+        Some(cur) => {
+            if cur.get_next() == list.head {
+                (Rawlink::none(), list.len())
+            } else {
+                (cur.get_next(), index + 1)
+            }
+        }
+    }
+}
+
+/// Computes the position preceding `current` in `list`, passing through the
+/// "ghost" non-element between the head and the tail
+fn prev_position<P, T, S, L>(list: &LinkedList<P, T, S, L>, current: Rawlink<L>,
+                             index: usize) -> (Rawlink<L>, usize)
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>
+{
+    match current.resolve() {
+        None => (list.tail(), list.len().saturating_sub(1)),
+        Some(cur) => {
+            if current == list.head {
+                (Rawlink::none(), list.len())
+            } else {
+                (cur.get_prev(), index - 1)
+            }
+        }
+    }
+}
+
+// IntoIter impls
+
+impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.list.pop_front() }
+
     #[inline]
-    fn clone(&self) -> IntoIter<P, T, S, L> {
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.length, Some(self.list.length))
+    }
+}
+
+impl<P, T, S, L> DoubleEndedIterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<T> { self.list.pop_back() }
+}
+
+#[cfg(feature="alloc")]
+impl<P, S, L> Clone for IntoIter<P, Box<S>, S, L>
+    where S: Node<P, L> + Clone,
+          L: Linkable<Container=S>
+{
+    #[inline]
+    fn clone(&self) -> IntoIter<P, Box<S>, S, L> {
         IntoIter { list: self.list.clone() }
     }
 }
@@ -1291,12 +2098,30 @@ unsafe impl<'a, T> OwningPointer for &'a mut T {
     }
 
     #[inline]
-    unsafe fn take(self) {
-        forget(self);
+    unsafe fn take(self) {}
+}
+
+unsafe impl<'a, T> OwningPointer for Pin<&'a mut T> {
+    #[inline]
+    unsafe fn from_raw(raw: *mut T) -> Pin<&'a mut T> {
+        Pin::new_unchecked(&mut *raw)
     }
+
+    #[inline]
+    unsafe fn take(self) {}
+}
+
+unsafe impl<'a, T> OwningPointer for Pin<&'a T> {
+    #[inline]
+    unsafe fn from_raw(raw: *mut T) -> Pin<&'a T> {
+        Pin::new_unchecked(&*raw)
+    }
+
+    #[inline]
+    unsafe fn take(self) {}
 }
 
-#[cfg(any(test,not(feature="nostd")))]
+#[cfg(feature="alloc")]
 unsafe impl<T> OwningPointer for Box<T> {
     #[inline]
     unsafe fn from_raw(raw: *mut T) -> Box<T> {
@@ -1305,7 +2130,46 @@ unsafe impl<T> OwningPointer for Box<T> {
 
     #[inline]
     unsafe fn take(self) {
-        boxed::into_raw(self);
+        let _ = Box::into_raw(self);
+    }
+}
+
+#[cfg(feature="alloc")]
+unsafe impl<T> OwningPointer for Pin<Box<T>> {
+    #[inline]
+    unsafe fn from_raw(raw: *mut T) -> Pin<Box<T>> {
+        Pin::new_unchecked(Box::from_raw(raw))
+    }
+
+    #[inline]
+    unsafe fn take(self) {
+        let _ = Box::into_raw(Pin::into_inner_unchecked(self));
+    }
+}
+
+#[cfg(feature="alloc")]
+unsafe impl<T> OwningPointer for Rc<T> {
+    #[inline]
+    unsafe fn from_raw(raw: *mut T) -> Rc<T> {
+        Rc::from_raw(raw)
+    }
+
+    #[inline]
+    unsafe fn take(self) {
+        let _ = Rc::into_raw(self);
+    }
+}
+
+#[cfg(all(feature="alloc", target_has_atomic="ptr"))]
+unsafe impl<T> OwningPointer for Arc<T> {
+    #[inline]
+    unsafe fn from_raw(raw: *mut T) -> Arc<T> {
+        Arc::from_raw(raw)
+    }
+
+    #[inline]
+    unsafe fn take(self) {
+        let _ = Arc::into_raw(self);
     }
 }
 
@@ -1313,29 +2177,104 @@ unsafe impl<T> OwningPointer for Box<T> {
 // Tests //
 ///////////
 
+// The tests below only use elements on the stack, so they also run without the
+// `alloc` feature
 #[cfg(test)]
+mod no_alloc_tests {
+    use std::pin::{pin, Pin};
+    use std::vec::Vec;
+    use super::{LinkedList, Node, ListLinkOps};
+    use core::ptr;
+
+    define_list_element!(MyI32 = i32 : MyLink);
+    define_list_element!(Task = u32 : RunLink, AllLink);
+
+    #[test]
+    fn test_mut_ref() {
+        let mut m = MyI32::new(0);
+        let mut n = LinkedList::new();
+        n.push_front(&mut m);
+    }
+
+    #[test]
+    fn test_pin_stack() {
+        let mut a = pin!(MyI32::new(1));
+        let mut b = pin!(MyI32::new(2));
+        {
+            let mut m = LinkedList::new();
+            m.push_back(a.as_mut());
+            m.push_back(b.as_mut());
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), [1, 2]);
+            assert_eq!(m.iter().rev().cloned().collect::<Vec<_>>(), [2, 1]);
+            let popped = m.pop_back().unwrap();
+            m.push_front(popped);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), [2, 1]);
+        }
+        // dropping the list unlinked both elements
+        a.get_links().check_links();
+        b.get_links().check_links();
+    }
+
+    #[test]
+    fn test_multiple_links_stack() {
+        let tasks = pin!([Task::new(0), Task::new(1), Task::new(2)]);
+        let task = |i| unsafe { tasks.as_ref().map_unchecked(|tasks| &tasks[i]) };
+        let mut run: LinkedList<u32, Pin<&Task>, Task, RunLink> = LinkedList::new();
+        let mut all: LinkedList<u32, Pin<&Task>, Task, AllLink> = LinkedList::new();
+        for i in 0..3 {
+            all.push_back(task(i));
+        }
+        run.push_front(task(2));
+        run.push_front(task(0));
+        assert_eq!(all.iter().cloned().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(run.iter().cloned().collect::<Vec<_>>(), [0, 2]);
+
+        // taking a task off one list leaves it on the other
+        let removed = unsafe { run.remove(&task(2)) };
+        assert!(ptr::eq(&*removed, &*task(2)));
+        assert_eq!(run.len(), 1);
+        assert_eq!(all.len(), 3);
+        run.clear();
+        all.clear();
+    }
+}
+
+#[cfg(all(test, feature="alloc"))]
 mod tests {
-    use std::prelude::v1::*;
-    use std::hash::{self, Hasher, SipHasher};
+    use std::boxed::Box;
+    use std::vec;
+    use std::vec::Vec;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
     use std::fmt;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
     use std::thread;
-    use super::{LinkedList, OwningPointer, Node, Linkable};
+    use super::{LinkedList, OwningPointer, Node, Linkable, ListLinkOps};
+    use core::ptr;
     use rand;
 
     define_list_element!(MyI32 = i32 : MyLink);
 
+    fn hash<H: Hash>(t: &H) -> u64 {
+        let mut s = DefaultHasher::new();
+        t.hash(&mut s);
+        s.finish()
+    }
+
     pub fn check_links<P, T, S, L>(list: &LinkedList<P, T, S, L>)
         where T: OwningPointer<Target=S>,
               S: Node<P, L>,
               L: Linkable<Container=S> + fmt::Debug,
     {
         let mut len = 0;
-        let mut head: &L;
+        let head: &L;
         let mut prev_links: &L;
         let mut link_ptr: &L;
         match list.head.resolve() {
             None => { assert_eq!(0, list.length); return }
-            Some(ref links) => {
+            Some(links) => {
                 head = links;
                 link_ptr = links;
                 prev_links = links.get_prev().resolve().unwrap();
@@ -1353,7 +2292,7 @@ mod tests {
                 None => panic!("unset next link"),
                 Some(next) => {
                     len += 1;
-                    if next as *const L == head as *const L {
+                    if ptr::eq(next, head) {
                         break;
                     }
                     prev_links = link_ptr;
@@ -1413,13 +2352,75 @@ mod tests {
         check_links(&n);
         check_links(&m);
         assert_eq!(m, n);
+        // the elements are cloned along with the list
+        assert!(!ptr::eq(m.front().unwrap(), n.front().unwrap()));
     }
 
     #[test]
-    fn test_mut_ref() {
-        let mut m = MyI32::new(0);
-        let mut n = LinkedList::new();
-        n.push_front(&mut m);
+    fn test_rc() {
+        let elts: Vec<_> = (0..3).map(|i| Rc::new(MyI32::new(i))).collect();
+        let mut m = LinkedList::new();
+        for e in elts.iter() {
+            m.push_back(e.clone());
+        }
+        check_links(&m);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), [0, 1, 2]);
+
+        // the list gives its reference back when an element is removed
+        let one = unsafe { m.remove(&elts[1]) };
+        assert!(Rc::ptr_eq(&one, &elts[1]));
+        drop(one);
+        assert_eq!(Rc::strong_count(&elts[1]), 1);
+        {
+            let mut cursor = m.cursor_back_mut();
+            let two = cursor.remove_current().unwrap();
+            assert!(Rc::ptr_eq(&two, &elts[2]));
+        }
+        assert_eq!(Rc::strong_count(&elts[2]), 1);
+        check_links(&m);
+
+        // and when it is dropped
+        drop(m);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    // Elements are not Sync, so the Arcs never leave this thread
+    #[allow(clippy::arc_with_non_send_sync)]
+    #[test]
+    fn test_arc() {
+        let a = Arc::new(MyI32::new(1));
+        let b = Arc::new(MyI32::new(2));
+        let mut m = LinkedList::new();
+        m.push_front(a.clone());
+        m.push_front(b.clone());
+        assert_eq!(Arc::strong_count(&a), 2);
+        assert_eq!(m.front(), Some(&2));
+        assert_eq!(m.back(), Some(&1));
+        let popped = m.pop_back().unwrap();
+        assert!(Arc::ptr_eq(&popped, &a));
+        drop(popped);
+        assert_eq!(Arc::strong_count(&a), 1);
+        m.clear();
+        assert_eq!(Arc::strong_count(&b), 1);
+    }
+
+    #[test]
+    fn test_pin_box() {
+        let mut m = LinkedList::new();
+        m.push_back(Box::pin(MyI32::new(1)));
+        m.push_back(Box::pin(MyI32::new(2)));
+        m.push_front(Box::pin(MyI32::new(0)));
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), [0, 1, 2]);
+        let front: Pin<Box<MyI32>> = m.pop_front().unwrap();
+        assert_eq!(*front, MyI32::new(0));
+        {
+            let mut cursor = m.cursor_front_mut();
+            cursor.insert_after(front);
+        }
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), [1, 0, 2]);
     }
 
     #[cfg(test)]
@@ -1489,7 +2490,7 @@ mod tests {
         m.append(&mut n);
         check_links(&m);
         let mut sum = v;
-        sum.push_all(&u);
+        sum.extend_from_slice(&u);
         assert_eq!(sum.len(), m.len());
         for elt in sum {
             assert_eq!(m.pop_front(), Some(elt))
@@ -1618,7 +2619,7 @@ mod tests {
             assert_eq!((6 - i) as i32, *elt);
         }
         let mut n = LinkedList::new();
-        assert_eq!(n.iter().rev().next(), None);
+        assert_eq!(n.iter().next_back(), None);
         n.push_front(Box::new(MyI32::new(4)));
         let mut it = n.iter().rev();
         assert_eq!(it.size_hint(), (1, Some(1)));
@@ -1710,6 +2711,261 @@ mod tests {
                     Box::new(MyI32::new(1))]);
     }
 
+    #[test]
+    fn test_remove() {
+        let v = vec![Box::new(MyI32::new(0)), Box::new(MyI32::new(1)),
+                     Box::new(MyI32::new(2)), Box::new(MyI32::new(3))];
+        let ptrs: Vec<*const MyI32> = v.iter().map(|e| &**e as *const _)
+            .collect();
+        let mut m: LinkedList<i32, Box<MyI32>, MyI32, MyLink> =
+            v.into_iter().collect();
+
+        // middle
+        assert_eq!(unsafe { m.remove(&*ptrs[2]) }, Box::new(MyI32::new(2)));
+        check_links(&m);
+        // head
+        assert_eq!(unsafe { m.remove(&*ptrs[0]) }, Box::new(MyI32::new(0)));
+        check_links(&m);
+        assert_eq!(m.front(), Some(&1));
+        // tail
+        assert_eq!(unsafe { m.remove(&*ptrs[3]) }, Box::new(MyI32::new(3)));
+        check_links(&m);
+        assert_eq!(m.back(), Some(&1));
+        // singleton
+        assert_eq!(unsafe { m.remove(&*ptrs[1]) }, Box::new(MyI32::new(1)));
+        check_links(&m);
+        assert!(m.is_empty());
+    }
+
+    // the ownership check of `remove` is only made in debug builds
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn test_remove_foreign() {
+        let e = Box::new(MyI32::new(1));
+        let ptr: *const MyI32 = &*e;
+        let mut m = LinkedList::new();
+        let mut n = LinkedList::new();
+        m.push_back(Box::new(MyI32::new(0)));
+        n.push_back(e);
+        unsafe { m.remove(&*ptr) };
+    }
+
+    define_list_element!(Task = i32 : RunLink, TimerLink, AllLink);
+
+    #[test]
+    fn test_multiple_links() {
+        assert!(RunLink::offset() != TimerLink::offset());
+        assert!(TimerLink::offset() != AllLink::offset());
+        assert!(RunLink::offset() != AllLink::offset());
+
+        let tasks: Vec<_> = (0..4).map(|i| Rc::new(Task::new(i))).collect();
+        let mut all: LinkedList<i32, Rc<Task>, Task, AllLink> =
+            LinkedList::new();
+        let mut run: LinkedList<i32, Rc<Task>, Task, RunLink> =
+            LinkedList::new();
+        let mut timer: LinkedList<i32, Rc<Task>, Task, TimerLink> =
+            LinkedList::new();
+        for t in tasks.iter() {
+            all.push_back(t.clone());
+        }
+        for t in tasks.iter().rev() {
+            run.push_back(t.clone());
+        }
+        for t in [&tasks[0], &tasks[2]] {
+            timer.push_back(t.clone());
+        }
+        check_links(&all);
+        check_links(&run);
+        check_links(&timer);
+        assert_eq!(all.iter().cloned().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(run.iter().cloned().collect::<Vec<_>>(), [3, 2, 1, 0]);
+        assert_eq!(timer.iter().cloned().collect::<Vec<_>>(), [0, 2]);
+
+        // unlinking from one list leaves the others untouched
+        unsafe { run.remove(&tasks[2]) };
+        unsafe { timer.remove(&tasks[2]) };
+        check_links(&all);
+        check_links(&run);
+        check_links(&timer);
+        assert_eq!(all.iter().cloned().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(run.iter().cloned().collect::<Vec<_>>(), [3, 1, 0]);
+        assert_eq!(timer.iter().cloned().collect::<Vec<_>>(), [0]);
+
+        while run.pop_front().is_some() {}
+        while timer.pop_front().is_some() {}
+        while all.pop_front().is_some() {}
+        assert!(tasks.iter().all(|t| Rc::strong_count(t) == 1));
+    }
+
+    #[test]
+    fn test_cursor_move_peek() {
+        let m = generate_test();
+        let mut cursor = m.cursor_front();
+        assert_eq!(cursor.current(), Some(&0));
+        assert_eq!(cursor.peek_next(), Some(&1));
+        assert_eq!(cursor.peek_prev(), None);
+        assert_eq!(cursor.index(), Some(0));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&0));
+        assert_eq!(cursor.peek_prev(), Some(&6));
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&1));
+        assert_eq!(cursor.peek_next(), Some(&2));
+        assert_eq!(cursor.peek_prev(), Some(&0));
+        assert_eq!(cursor.index(), Some(1));
+
+        let mut cursor = m.cursor_back();
+        assert_eq!(cursor.current(), Some(&6));
+        assert_eq!(cursor.index(), Some(6));
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&0));
+        assert_eq!(cursor.index(), Some(0));
+
+        let n: LinkedList<i32, Box<MyI32>, MyI32, MyLink> = LinkedList::new();
+        let mut cursor = n.cursor_front();
+        assert_eq!(cursor.current(), None);
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.peek_prev(), None);
+    }
+
+    #[test]
+    fn test_cursor_mut_insert() {
+        let mut m = list_from(&[Box::new(MyI32::new(1)),
+                                Box::new(MyI32::new(2)),
+                                Box::new(MyI32::new(3))]);
+        {
+            let mut cursor = m.cursor_front_mut();
+            cursor.insert_before(Box::new(MyI32::new(7)));
+            cursor.insert_after(Box::new(MyI32::new(8)));
+            assert_eq!(cursor.index(), Some(1));
+            cursor.move_prev();
+            cursor.move_prev();
+            assert_eq!(cursor.current(), None);
+            cursor.insert_after(Box::new(MyI32::new(9)));
+            cursor.insert_before(Box::new(MyI32::new(10)));
+            assert_eq!(cursor.index(), None);
+            cursor.move_next();
+            assert_eq!(cursor.current(), Some(&mut 9));
+            *cursor.peek_next().unwrap() = 11;
+        }
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(),
+                   [9, 11, 1, 8, 2, 3, 10]);
+    }
+
+    #[test]
+    fn test_cursor_remove() {
+        let mut m = generate_test();
+        {
+            let mut cursor = m.cursor_front_mut();
+            assert_eq!(cursor.remove_current(), Some(Box::new(MyI32::new(0))));
+            assert_eq!(cursor.current(), Some(&mut 1));
+            assert_eq!(cursor.index(), Some(0));
+            cursor.move_next();
+            cursor.move_next();
+            assert_eq!(cursor.remove_current(), Some(Box::new(MyI32::new(3))));
+            assert_eq!(cursor.current(), Some(&mut 4));
+            assert_eq!(cursor.index(), Some(2));
+            cursor.move_prev();
+            cursor.move_prev();
+            cursor.move_prev();
+            assert_eq!(cursor.remove_current(), None);
+            cursor.move_prev();
+            assert_eq!(cursor.remove_current(), Some(Box::new(MyI32::new(6))));
+            assert_eq!(cursor.current(), None);
+        }
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), [1, 2, 4, 5]);
+
+        let mut n = list_from(&[Box::new(MyI32::new(1))]);
+        {
+            let mut cursor = n.cursor_back_mut();
+            assert_eq!(cursor.remove_current(), Some(Box::new(MyI32::new(1))));
+            assert_eq!(cursor.current(), None);
+            assert_eq!(cursor.remove_current(), None);
+        }
+        check_links(&n);
+        assert!(n.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_split() {
+        let mut m = generate_test();
+        let (before, after) = {
+            let mut cursor = m.cursor_front_mut();
+            cursor.move_next();
+            cursor.move_next();
+            let before = cursor.split_before();
+            assert_eq!(cursor.index(), Some(0));
+            cursor.move_next();
+            let after = cursor.split_after();
+            assert_eq!(cursor.index(), Some(1));
+            cursor.move_next();
+            assert_eq!(cursor.current(), None);
+            (before, after)
+        };
+        check_links(&m);
+        check_links(&before);
+        check_links(&after);
+        assert_eq!(before.iter().cloned().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(after.iter().cloned().collect::<Vec<_>>(), [4, 5, 6]);
+
+        // splitting at the ends or at the ghost
+        {
+            let mut cursor = m.cursor_front_mut();
+            assert!(cursor.split_before().is_empty());
+            cursor.move_next();
+            assert!(cursor.split_after().is_empty());
+            cursor.move_next();
+            let all = cursor.split_after();
+            assert_eq!(all.iter().cloned().collect::<Vec<_>>(), [2, 3]);
+        }
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_splice() {
+        let mut m = list_from(&[Box::new(MyI32::new(1)),
+                                Box::new(MyI32::new(2))]);
+        {
+            let mut cursor = m.cursor_front_mut();
+            cursor.splice_after(list_from(&[Box::new(MyI32::new(3)),
+                                            Box::new(MyI32::new(4))]));
+            cursor.splice_before(list_from(&[Box::new(MyI32::new(5)),
+                                             Box::new(MyI32::new(6))]));
+            assert_eq!(cursor.index(), Some(2));
+            assert_eq!(cursor.current(), Some(&mut 1));
+            cursor.splice_before(LinkedList::new());
+            cursor.splice_after(LinkedList::new());
+            cursor.move_prev();
+            cursor.move_prev();
+            cursor.move_prev();
+            assert_eq!(cursor.current(), None);
+            cursor.splice_after(list_from(&[Box::new(MyI32::new(7))]));
+            cursor.splice_before(list_from(&[Box::new(MyI32::new(8))]));
+            assert_eq!(cursor.index(), None);
+            cursor.move_prev();
+            cursor.splice_after(list_from(&[Box::new(MyI32::new(9))]));
+            cursor.move_next();
+            cursor.splice_before(list_from(&[Box::new(MyI32::new(10))]));
+        }
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(),
+                   [7, 5, 6, 1, 3, 4, 2, 8, 10, 9]);
+    }
+
     #[test]
     fn test_mut_rev_iter() {
         let mut m = generate_test();
@@ -1717,7 +2973,7 @@ mod tests {
             assert_eq!((6 - i) as i32, *elt);
         }
         let mut n = LinkedList::new();
-        assert!(n.iter_mut().rev().next().is_none());
+        assert!(n.iter_mut().next_back().is_none());
         n.push_front(Box::new(MyI32::new(4)));
         let mut it = n.iter_mut().rev();
         assert!(it.next().is_some());
@@ -1759,7 +3015,7 @@ mod tests {
       let mut x = LinkedList::new();
       let mut y = LinkedList::new();
 
-      assert!(hash::hash::<_, SipHasher>(&x) == hash::hash::<_, SipHasher>(&y));
+      assert!(hash(&x) == hash(&y));
 
       x.push_back(Box::new(MyI32::new(1)));
       x.push_back(Box::new(MyI32::new(2)));
@@ -1769,7 +3025,7 @@ mod tests {
       y.push_front(Box::new(MyI32::new(2)));
       y.push_front(Box::new(MyI32::new(1)));
 
-      assert!(hash::hash::<_, SipHasher>(&x) == hash::hash::<_, SipHasher>(&y));
+      assert!(hash(&x) == hash(&y));
     }
 
     #[test]
@@ -1815,7 +3071,7 @@ mod tests {
                     m.push_front(Box::new(MyI32::new(-i)));
                     v.insert(0, Box::new(MyI32::new(-i)));
                 }
-                3 | 5 | _ => {
+                _ => {
                     m.push_back(Box::new(MyI32::new(i)));
                     v.push(Box::new(MyI32::new(i)));
                 }
@@ -1825,9 +3081,9 @@ mod tests {
         check_links(&m);
 
         let mut i = 0;
-        for (ref a, ref b) in m.into_iter().zip(v.iter()) {
+        for (a, b) in m.into_iter().zip(v.iter()) {
             i += 1;
-            assert_eq!(a, *b);
+            assert_eq!(&a, b);
         }
         assert_eq!(i, v.len());
     }
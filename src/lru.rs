@@ -0,0 +1,433 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive least-recently-used cache.
+//!
+//! The `LruCache` keeps its elements on a `LinkedList` ordered from the most
+//! to the least recently used, and indexes them by key in an
+//! `IntrusiveHashMap`. Its elements therefore need two list links: one for
+//! the recency list and one for the buckets of the index, which
+//! `define_list_element!(Entry = Value : LruLink, IndexLink)` provides.
+//!
+//! The cache owns its elements through the recency list. It never evicts
+//! anything by itself: the caller decides when to call `evict_lru`, for
+//! example whenever `len` exceeds some capacity.
+use core::borrow::BorrowMut;
+use core::fmt;
+use core::hash::BuildHasher;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use super::hash_map::{Bucket, IntrusiveHashMap, KeyAdapter};
+use super::linked_list::{self, LinkedList, Linkable, Node, OwningPointer};
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// A non-owning pointer to an element, used by the index of an `LruCache`
+pub struct Unowned<S> {
+    ptr: NonNull<S>
+}
+
+/// A bucket of the index of an `LruCache`
+pub type IndexBucket<P, S, I> = Bucket<P, Unowned<S>, S, I>;
+
+/// An intrusive least-recently-used cache
+///
+/// `R` is the link used by the recency list and `I` the one used by the
+/// index, which finds elements by the key `A` gives, hashed by hashers built
+/// by `H`, in buckets held by `B`.
+pub struct LruCache<P, T, S, R, I, A, H, B>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, R> + Node<P, I>,
+          R: Linkable<Container=S>,
+          I: Linkable<Container=S>,
+          B: BorrowMut<[IndexBucket<P, S, I>]>
+{
+    // dropped first, so that no element is dropped while still indexed
+    index: IntrusiveHashMap<P, Unowned<S>, S, I, A, H, B>,
+    recency: LinkedList<P, T, S, R>
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// Unowned impls
+
+impl<S> Deref for Unowned<S> {
+    type Target = S;
+
+    #[inline]
+    fn deref(&self) -> &S {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+unsafe impl<S> OwningPointer for Unowned<S> {
+    #[inline]
+    unsafe fn from_raw(raw: *mut S) -> Unowned<S> {
+        Unowned { ptr: NonNull::new_unchecked(raw) }
+    }
+
+    #[inline]
+    unsafe fn take(self) {}
+}
+
+// An `Unowned` points to an element of the recency list of the cache holding
+// it, and moves between threads along with that cache
+unsafe impl<S: Send> Send for Unowned<S> {}
+
+// LruCache impls
+
+impl<P, T, S, R, I, A, H, B> LruCache<P, T, S, R, I, A, H, B>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, R> + Node<P, I>,
+          R: Linkable<Container=S>,
+          I: Linkable<Container=S>,
+          A: KeyAdapter<P>,
+          H: BuildHasher,
+          B: BorrowMut<[IndexBucket<P, S, I>]>
+{
+    /// Creates an empty `LruCache` whose index uses `buckets` as its bucket
+    /// array
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buckets` is empty or if any bucket holds elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use std::collections::hash_map::RandomState;
+    /// use intrusive_containers::hash_map::KeyAdapter;
+    /// use intrusive_containers::lru::{IndexBucket, LruCache};
+    ///
+    /// define_list_element!(Page = (u64, u8) : LruLink, IndexLink);
+    ///
+    /// struct ByAddr;
+    ///
+    /// impl KeyAdapter<(u64, u8)> for ByAddr {
+    ///     type Key = u64;
+    ///     fn key<'a>(&self, val: &'a (u64, u8)) -> &'a u64 {
+    ///         &val.0
+    ///     }
+    /// }
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut buckets: [IndexBucket<_, Page, IndexLink>; 8] = Default::default();
+    /// let mut cache: LruCache<_, Box<Page>, _, LruLink, _, _, _, _> =
+    ///     LruCache::new(&mut buckets[..], ByAddr, RandomState::new());
+    /// cache.insert(Box::new(Page::new((0x1000, 1))));
+    /// cache.insert(Box::new(Page::new((0x2000, 2))));
+    /// assert_eq!(cache.get(&0x1000), Some(&(0x1000, 1)));
+    ///
+    /// // 0x1000 was used last, so 0x2000 is evicted first
+    /// let evicted = cache.evict_lru().unwrap();
+    /// assert_eq!(evicted.0.val, (0x2000, 2));
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn new(buckets: B, adapter: A, hasher: H)
+               -> LruCache<P, T, S, R, I, A, H, B> {
+        LruCache {
+            index: IntrusiveHashMap::new(buckets, adapter, hasher),
+            recency: LinkedList::new()
+        }
+    }
+
+    /// Returns `true` if the `LruCache` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.recency.is_empty()
+    }
+
+    /// Returns the number of elements in the `LruCache`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.recency.len()
+    }
+
+    /// Removes all elements from the `LruCache`.
+    ///
+    /// This operation should compute in O(n) time.
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.recency.clear();
+    }
+
+    /// Provides an iterator over the elements, from the most to the least
+    /// recently used.
+    #[inline]
+    pub fn iter(&self) -> linked_list::Iter<'_, P, S, R> {
+        self.recency.iter()
+    }
+
+    /// Inserts an element as the most recently used. If an element with the
+    /// same key was already in the cache, it is removed and returned.
+    ///
+    /// This operation should compute in O(1) expected time.
+    pub fn insert(&mut self, elt: T) -> Option<T> {
+        let raw = &*elt as *const S as *mut S;
+        let old = self.index.insert(unsafe { Unowned::from_raw(raw) });
+        self.recency.push_front(elt);
+        old.map(|old| {
+            // every element of the index is on the recency list
+            let old: &S = &old;
+            unsafe {
+                self.recency.unlink(Node::<P, R>::get_links(old));
+                T::from_raw(old as *const S as *mut S)
+            }
+        })
+    }
+
+    /// Provides a reference to the element whose key is `key` and marks it
+    /// as the most recently used, or returns `None` if there is none.
+    ///
+    /// This operation should compute in O(1) expected time.
+    pub fn get(&mut self, key: &A::Key) -> Option<&P> {
+        let elt = self.index.find_node(key)? as *const S;
+        unsafe {
+            self.touch(&*elt);
+            Some(Node::<P, R>::get_val(&*elt))
+        }
+    }
+
+    /// Provides a reference to the element whose key is `key`, without
+    /// changing its recency, or `None` if there is none.
+    ///
+    /// This operation should compute in O(1) expected time.
+    #[inline]
+    pub fn peek(&self, key: &A::Key) -> Option<&P> {
+        self.index.find(key)
+    }
+
+    /// Provides a reference to the least recently used element, or `None` if
+    /// the cache is empty.
+    #[inline]
+    pub fn peek_lru(&self) -> Option<&P> {
+        self.recency.back()
+    }
+
+    /// Marks `elt` as the most recently used element.
+    ///
+    /// This operation should compute in O(1) time.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this cache.
+    pub unsafe fn touch(&mut self, elt: &S) {
+        let links = Node::<P, R>::get_links(elt);
+        self.recency.unlink(links);
+        self.recency.push_front(T::from_raw(elt as *const S as *mut S));
+    }
+
+    /// Removes the least recently used element and returns it, or `None` if
+    /// the cache is empty.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn evict_lru(&mut self) -> Option<T> {
+        // unindexed first, so that the element is still owned by the cache
+        // should the key adapter or the hasher panic
+        let lru = self.recency.back_node()? as *const S;
+        self.index.remove(unsafe { &*lru });
+        self.recency.pop_back()
+    }
+
+    /// Removes the element whose key is `key` and returns it, or `None` if
+    /// there is none.
+    ///
+    /// This operation should compute in O(1) expected time.
+    pub fn remove_key(&mut self, key: &A::Key) -> Option<T> {
+        self.index.remove_key(key).map(|elt| {
+            // every element of the index is on the recency list
+            let elt: &S = &elt;
+            unsafe {
+                self.recency.unlink(Node::<P, R>::get_links(elt));
+                T::from_raw(elt as *const S as *mut S)
+            }
+        })
+    }
+
+    /// Unlinks `elt` from the cache and returns ownership of it.
+    ///
+    /// This operation should compute in O(1) expected time.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this cache.
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        self.index.remove(elt);
+        self.recency.unlink(Node::<P, R>::get_links(elt));
+        T::from_raw(elt as *const S as *mut S)
+    }
+}
+
+impl<P, T, S, R, I, A, H, B> fmt::Debug for LruCache<P, T, S, R, I, A, H, B>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, R> + Node<P, I>,
+          R: Linkable<Container=S>,
+          I: Linkable<Container=S>,
+          B: BorrowMut<[IndexBucket<P, S, I>]>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.recency.fmt(f)
+    }
+}
+
+unsafe impl<P, T, S, R, I, A, H, B> Send for LruCache<P, T, S, R, I, A, H, B>
+    where T: OwningPointer<Target=S> + Send,
+          S: Node<P, R> + Node<P, I>,
+          R: Linkable<Container=S>,
+          I: Linkable<Container=S>,
+          A: Send,
+          H: Send,
+          B: BorrowMut<[IndexBucket<P, S, I>]> + Send
+{}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::collections::hash_map::RandomState;
+    use std::rc::Rc;
+    use std::thread;
+    use std::vec::Vec;
+    use super::{IndexBucket, LruCache};
+    use super::super::hash_map::KeyAdapter;
+
+    define_list_element!(Entry = (u32, u32) : LruLink, IndexLink);
+
+    struct ByFirst;
+
+    impl KeyAdapter<(u32, u32)> for ByFirst {
+        type Key = u32;
+
+        fn key<'a>(&self, val: &'a (u32, u32)) -> &'a u32 {
+            &val.0
+        }
+    }
+
+    type Buckets = Box<[IndexBucket<(u32, u32), Entry, IndexLink>]>;
+
+    type Cache<T> = LruCache<(u32, u32), T, Entry, LruLink, IndexLink,
+                             ByFirst, RandomState, Buckets>;
+
+    fn cache<T>() -> Cache<T>
+        where T: super::OwningPointer<Target=Entry>
+    {
+        let buckets: Buckets = (0..8).map(|_| Default::default()).collect();
+        LruCache::new(buckets, ByFirst, RandomState::new())
+    }
+
+    fn keys<T>(cache: &Cache<T>) -> Vec<u32>
+        where T: super::OwningPointer<Target=Entry>
+    {
+        cache.iter().map(|v| v.0).collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut c = cache();
+        assert!(c.is_empty());
+        assert_eq!(c.get(&1), None);
+        assert!(c.evict_lru().is_none());
+        for i in 0..5 {
+            assert!(c.insert(Box::new(Entry::new((i, i)))).is_none());
+        }
+        assert_eq!(c.len(), 5);
+        assert_eq!(keys(&c), [4, 3, 2, 1, 0]);
+        assert_eq!(c.get(&1), Some(&(1, 1)));
+        assert_eq!(keys(&c), [1, 4, 3, 2, 0]);
+        assert_eq!(c.peek(&2), Some(&(2, 2)));
+        assert_eq!(c.peek_lru(), Some(&(0, 0)));
+        assert_eq!(keys(&c), [1, 4, 3, 2, 0]);
+
+        let old = c.insert(Box::new(Entry::new((3, 30)))).unwrap();
+        assert_eq!(old.0.val, (3, 3));
+        assert_eq!(keys(&c), [3, 1, 4, 2, 0]);
+        assert_eq!(c.peek(&3), Some(&(3, 30)));
+
+        assert_eq!(c.evict_lru().unwrap().0.val, (0, 0));
+        assert_eq!(c.evict_lru().unwrap().0.val, (2, 2));
+        assert_eq!(c.remove_key(&1).unwrap().0.val, (1, 1));
+        assert_eq!(c.remove_key(&1), None);
+        assert_eq!(c.peek(&1), None);
+        assert_eq!(keys(&c), [3, 4]);
+        c.clear();
+        assert!(c.is_empty());
+        assert_eq!(c.peek(&3), None);
+    }
+
+    #[test]
+    fn test_send() {
+        let mut c = cache();
+        c.insert(Box::new(Entry::new((1, 10))));
+        c.insert(Box::new(Entry::new((2, 20))));
+        thread::spawn(move || {
+            assert_eq!(c.get(&1), Some(&(1, 10)));
+            assert_eq!(keys(&c), [1, 2]);
+        }).join().ok().unwrap();
+    }
+
+    #[test]
+    fn test_touch_remove() {
+        let mut c = cache();
+        let elts: Vec<*const Entry> = (0..4).map(|i| {
+            let e = Box::new(Entry::new((i, 0)));
+            let ptr: *const Entry = &*e;
+            c.insert(e);
+            ptr
+        }).collect();
+        unsafe { c.touch(&*elts[0]) };
+        assert_eq!(keys(&c), [0, 3, 2, 1]);
+        unsafe { c.touch(&*elts[0]) };
+        assert_eq!(keys(&c), [0, 3, 2, 1]);
+        unsafe { c.touch(&*elts[1]) };
+        assert_eq!(keys(&c), [1, 0, 3, 2]);
+        let e = unsafe { c.remove(&*elts[3]) };
+        assert_eq!(e.0.val, (3, 0));
+        assert_eq!(keys(&c), [1, 0, 2]);
+        assert_eq!(c.peek(&3), None);
+    }
+
+    #[test]
+    fn test_rc() {
+        let elts: Vec<_> = (0..6).map(|i| Rc::new(Entry::new((i, 0)))).collect();
+        let mut c = cache();
+        for e in elts.iter() {
+            c.insert(e.clone());
+        }
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        c.get(&0);
+        drop(c.evict_lru());
+        assert_eq!(Rc::strong_count(&elts[1]), 1);
+        assert_eq!(Rc::strong_count(&elts[0]), 2);
+        drop(c);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+}
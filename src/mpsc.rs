@@ -0,0 +1,600 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive lock-free multi-producer, single-consumer queue.
+//!
+//! The `MpscQueue` is Dmitry Vyukov's intrusive MPSC queue. Any number of
+//! threads may push elements, each push being a single atomic swap, while a
+//! single thread at a time pops them in FIFO order.
+//!
+//! The queue holds a stub node, linked into the queue whenever it runs empty,
+//! so it must not move once used: `push` and `pop` take the queue pinned, for
+//! example with `Arc::pin` or `core::pin::pin!`.
+//!
+//! A pop can observe the queue in an inconsistent state, when a producer has
+//! swapped itself in as the last element but has not linked it to the
+//! previous one yet. `pop` then returns `Pop::Inconsistent`, and the element
+//! becomes visible as soon as the producer completes its push; `pop_spin`
+//! waits for it.
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hint;
+use core::marker::{PhantomData, PhantomPinned};
+use core::pin::Pin;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use super::linked_list::OwningPointer;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be pushed onto an `MpscQueue`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_mpsc_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: MpscLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be pushed onto an `MpscQueue`
+///
+/// # Safety
+///
+/// The trait is unsafe because `offset()` must be the offset of the link
+/// within its `Container`.
+pub unsafe trait MpscLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &MpscLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut MpscLinks<Self>;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be pushed onto an `MpscQueue`
+///
+/// `define_mpsc_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it on a queue.
+#[macro_export]
+macro_rules! define_mpsc_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_mpsc_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_mpsc_link!($link = $elt);
+        impl_mpsc_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_mpsc_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_mpsc_link!($link = $elt);
+        impl_mpsc_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_mpsc_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::mpsc::MpscLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::mpsc::MpscLinks<$link>);
+    );
+}
+
+/// Implements `MpscLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_mpsc_link {
+    ($link:ident = $elt:ident) => (
+        impl_mpsc_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::mpsc::MpscLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::mpsc::MpscLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::mpsc::MpscLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_mpsc_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_mpsc_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::mpsc::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive lock-free multi-producer, single-consumer queue
+pub struct MpscQueue<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: MpscLinkable<Container=T::Target>
+{
+    /// The last element pushed, null standing for the stub
+    head: AtomicPtr<L>,
+    /// The next element to pop, only used by the consumer, null standing for
+    /// the stub
+    tail: UnsafeCell<*mut L>,
+    stub: L,
+    _pin: PhantomPinned,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+#[derive(Default, Debug)]
+pub struct MpscLinks<L>
+{
+    next: AtomicPtr<L>,
+    queued: AtomicBool,
+    _pin: PhantomPinned
+}
+
+/// The result of popping from an `MpscQueue`
+#[derive(Debug, PartialEq, Eq)]
+pub enum Pop<T> {
+    /// The first element of the queue
+    Data(T),
+    /// The queue is empty
+    Empty,
+    /// A producer is in the middle of a push, and the elements it is pushing
+    /// and every one pushed after it are not reachable yet
+    Inconsistent
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// MpscQueue impls
+
+impl<P, T, S, L> MpscQueue<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: MpscLinkable<Container=T::Target>
+{
+    /// Creates an empty `MpscQueue`
+    #[inline]
+    pub fn new() -> MpscQueue<P, T, S, L> {
+        MpscQueue { head: AtomicPtr::new(ptr::null_mut()),
+                    tail: UnsafeCell::new(ptr::null_mut()),
+                    stub: L::default(), _pin: PhantomPinned,
+                    _marker: PhantomData, _marker2: PhantomData,
+                    _marker3: PhantomData}
+    }
+
+    fn stub(&self) -> *mut L {
+        &self.stub as *const L as *mut L
+    }
+
+    /// Links `links` last in the queue
+    ///
+    /// The swap on `head` is what orders the producers: it needs Acquire to
+    /// see the previous element initialized, and Release so that the next
+    /// producer, which writes to our `next`, sees it reset. The store to
+    /// the previous element's `next` is Release, so that the consumer, which
+    /// loads it with Acquire, sees the element initialized.
+    fn push_links(&self, links: *mut L) {
+        unsafe { &*links }.get_links().next.store(ptr::null_mut(),
+                                                  Ordering::Relaxed);
+        let mut prev = self.head.swap(links, Ordering::AcqRel);
+        if prev.is_null() {
+            prev = self.stub();
+        }
+        unsafe { &*prev }.get_links().next.store(links, Ordering::Release);
+    }
+
+    /// Pushes an element at the back of the queue.
+    ///
+    /// This operation is lock-free and may be called from any number of
+    /// threads at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element is already on a queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use intrusive_containers::mpsc::{MpscQueue, Pop};
+    ///
+    /// define_mpsc_element!(Wakeup = u32 : WakeupLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let queue: std::pin::Pin<Arc<MpscQueue<_, Box<Wakeup>, _, _>>> =
+    ///     Arc::pin(MpscQueue::new());
+    /// let producers: Vec<_> = (0..4).map(|i| {
+    ///     let queue = queue.clone();
+    ///     thread::spawn(move || queue.as_ref().push(Box::new(Wakeup::new(i))))
+    /// }).collect();
+    /// for p in producers {
+    ///     p.join().unwrap();
+    /// }
+    ///
+    /// let mut woken = Vec::new();
+    /// while let Pop::Data(w) = unsafe { queue.as_ref().pop() } {
+    ///     woken.push(w.0.val);
+    /// }
+    /// woken.sort();
+    /// assert_eq!(woken, [0, 1, 2, 3]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn push(self: Pin<&Self>, elt: T) {
+        let links = elt.get_links();
+        assert!(!links.get_links().queued.swap(true, Ordering::Relaxed),
+                "Cannot push an element which is already on a queue");
+        self.push_links(links as *const L as *mut L);
+        unsafe { elt.take() };
+    }
+
+    /// Hands `links`, which the consumer just unlinked, back to its owner
+    unsafe fn release(links: *mut L) -> T {
+        let links = &*links;
+        links.get_links().next.store(ptr::null_mut(), Ordering::Relaxed);
+        links.get_links().queued.store(false, Ordering::Relaxed);
+        T::from_raw(links.container_of() as *const S as *mut S)
+    }
+
+    /// Pops the element at the front of the queue.
+    ///
+    /// This operation is wait-free. It returns `Pop::Inconsistent` when a
+    /// concurrent push has not completed, in which case the caller may retry
+    /// later.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because only one thread may pop at a
+    /// time.
+    pub unsafe fn pop(self: Pin<&Self>) -> Pop<T> {
+        let stub = self.stub();
+        let mut tail = *self.tail.get();
+        if tail.is_null() {
+            tail = stub;
+        }
+        let mut next = (*tail).get_links().next.load(Ordering::Acquire);
+        if tail == stub {
+            if next.is_null() {
+                let head = self.head.load(Ordering::Acquire);
+                return if head.is_null() || head == stub {
+                    Pop::Empty
+                } else {
+                    Pop::Inconsistent
+                };
+            }
+            // skip over the stub
+            *self.tail.get() = next;
+            tail = next;
+            next = (*tail).get_links().next.load(Ordering::Acquire);
+        }
+        if !next.is_null() {
+            *self.tail.get() = next;
+            return Pop::Data(Self::release(tail));
+        }
+        if tail != self.head.load(Ordering::Acquire) {
+            return Pop::Inconsistent;
+        }
+        // `tail` is the last element: push the stub behind it so that it
+        // can be unlinked
+        self.push_links(stub);
+        next = (*tail).get_links().next.load(Ordering::Acquire);
+        if !next.is_null() {
+            *self.tail.get() = next;
+            return Pop::Data(Self::release(tail));
+        }
+        Pop::Inconsistent
+    }
+
+    /// Pops the element at the front of the queue, or returns `None` if the
+    /// queue is empty. Spins while a concurrent push has not completed.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because only one thread may pop at a
+    /// time.
+    pub unsafe fn pop_spin(self: Pin<&Self>) -> Option<T> {
+        loop {
+            match self.pop() {
+                Pop::Data(elt) => return Some(elt),
+                Pop::Empty => return None,
+                Pop::Inconsistent => hint::spin_loop(),
+            }
+        }
+    }
+}
+
+impl<P, T, S, L> Default for MpscQueue<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: MpscLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> MpscQueue<P, T, S, L> {
+        MpscQueue::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for MpscQueue<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: MpscLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("MpscQueue { .. }")
+    }
+}
+
+impl<P, T, S, L> Drop for MpscQueue<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: MpscLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        // no producer is left, so the queue is consistent; walk it from the
+        // tail, skipping the stub, and hand back every element
+        let stub = &self.stub as *const L as *mut L;
+        let mut cur = *self.tail.get_mut();
+        if cur.is_null() {
+            cur = stub;
+        }
+        while !cur.is_null() {
+            let links = unsafe { &*cur };
+            let next = links.get_links().next.load(Ordering::Acquire);
+            links.get_links().next.store(ptr::null_mut(), Ordering::Relaxed);
+            if cur != stub {
+                links.get_links().queued.store(false, Ordering::Relaxed);
+                drop(unsafe {
+                    T::from_raw(links.container_of() as *const S as *mut S)
+                });
+            }
+            cur = next;
+        }
+    }
+}
+
+// Elements are handed from the producers to the consumer, which may be
+// different threads
+unsafe impl<P, T, S, L> Send for MpscQueue<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: MpscLinkable<Container=T::Target>
+{}
+
+unsafe impl<P, T, S, L> Sync for MpscQueue<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: MpscLinkable<Container=T::Target>
+{}
+
+// MpscLinks impls
+
+impl<L> Clone for MpscLinks<L>
+{
+    /// A clone is not on any queue, even if the original is
+    fn clone(&self) -> MpscLinks<L> {
+        MpscLinks {
+            next: AtomicPtr::new(ptr::null_mut()),
+            queued: AtomicBool::new(false),
+            _pin: PhantomPinned
+        }
+    }
+}
+
+impl<L> Drop for MpscLinks<L>
+{
+    fn drop(&mut self) {
+        assert!(!*self.queued.get_mut());
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::vec::Vec;
+    use super::{MpscQueue, MpscLinkable, Node, Pop};
+
+    define_mpsc_element!(MyI32 = i32 : MyLink);
+
+    type Queue = MpscQueue<i32, Box<MyI32>, MyI32, MyLink>;
+
+    fn val<T: core::ops::Deref<Target=MyI32>>(pop: Pop<T>) -> Option<i32> {
+        match pop {
+            Pop::Data(elt) => Some(*elt.get_val()),
+            Pop::Empty => None,
+            Pop::Inconsistent => panic!("inconsistent queue"),
+        }
+    }
+
+    #[test]
+    fn test_basic() {
+        let q = pin!(Queue::new());
+        let q = q.as_ref();
+        assert_eq!(unsafe { q.pop() }, Pop::Empty);
+        q.push(Box::new(MyI32::new(1)));
+        assert_eq!(val(unsafe { q.pop() }), Some(1));
+        assert_eq!(unsafe { q.pop() }, Pop::Empty);
+        for i in 2..6 {
+            q.push(Box::new(MyI32::new(i)));
+        }
+        assert_eq!(val(unsafe { q.pop() }), Some(2));
+        assert_eq!(val(unsafe { q.pop() }), Some(3));
+        q.push(Box::new(MyI32::new(6)));
+        let rest: Vec<_> = core::iter::from_fn(|| unsafe { q.pop_spin() })
+            .map(|e| *e.get_val()).collect();
+        assert_eq!(rest, [4, 5, 6]);
+        // leave some elements for the destructor
+        q.push(Box::new(MyI32::new(7)));
+        q.push(Box::new(MyI32::new(8)));
+    }
+
+    #[test]
+    fn test_inconsistent() {
+        let q = pin!(Queue::new());
+        let q = q.as_ref();
+        q.push(Box::new(MyI32::new(1)));
+        // start a push by hand, stopping after the swap
+        let elt = Box::into_raw(Box::new(MyI32::new(2)));
+        let links = unsafe { &*elt }.get_links() as *const MyLink as *mut MyLink;
+        unsafe { &*links }.get_links().queued.store(true, Ordering::Relaxed);
+        let prev = q.head.swap(links, Ordering::AcqRel);
+        assert_eq!(unsafe { q.pop() }, Pop::Inconsistent);
+        // complete the push
+        unsafe { &*prev }.get_links().next.store(links, Ordering::Release);
+        assert_eq!(val(unsafe { q.pop() }), Some(1));
+        assert_eq!(val(unsafe { q.pop() }), Some(2));
+        assert_eq!(unsafe { q.pop() }, Pop::Empty);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_twice() {
+        let q = pin!(MpscQueue::<i32, Arc<MyI32>, MyI32, MyLink>::new());
+        let elt = Arc::new(MyI32::new(1));
+        q.as_ref().push(elt.clone());
+        q.as_ref().push(elt);
+    }
+
+    #[test]
+    fn test_threads() {
+        let q: core::pin::Pin<Arc<MpscQueue<_, Arc<MyI32>, _, MyLink>>> =
+            Arc::pin(MpscQueue::new());
+        let elts: Vec<_> = (0..400).map(|i| Arc::new(MyI32::new(i))).collect();
+        let producers: Vec<_> = elts.chunks(100).map(|chunk| {
+            let q = q.clone();
+            let chunk = chunk.to_vec();
+            thread::spawn(move || {
+                for e in chunk {
+                    q.as_ref().push(e);
+                }
+            })
+        }).collect();
+        let mut seen = Vec::new();
+        while seen.len() < 400 {
+            if let Pop::Data(e) = unsafe { q.as_ref().pop() } {
+                seen.push(*e.get_val());
+            }
+        }
+        for p in producers {
+            p.join().unwrap();
+        }
+        assert_eq!(unsafe { q.as_ref().pop() }, Pop::Empty);
+        // each producer's elements come out in order
+        for k in 0..4 {
+            let mine: Vec<_> = seen.iter().filter(|&&v| v / 100 == k).collect();
+            assert!(mine.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(mine.len(), 100);
+        }
+        assert!(elts.iter().all(|e| Arc::strong_count(e) == 1));
+    }
+}
@@ -0,0 +1,1158 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive radix tree keyed by `u64`.
+//!
+//! The `RadixTree` maps sparse integer keys, such as page offsets or process
+//! ids, to elements. Each interior node holds `RADIX_SLOTS` slots and consumes
+//! `RADIX_BITS` bits of the key, and the tree is only as tall as its largest
+//! key requires, so lookup, insertion and removal take O(log k) time for a
+//! largest key k. Elements are the leaves of the tree and carry their own
+//! link, while interior nodes are taken from a `NodePool` supplied by the
+//! caller and given back to it as soon as they empty.
+//!
+//! Every slot also has `TAGS` tag bits, which are propagated up the tree so
+//! that `next_tagged` finds the next tagged element without visiting untagged
+//! subtrees, as a page cache does to find dirty pages.
+use core::cell::Cell;
+use core::fmt;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ptr::{self, NonNull};
+use super::linked_list::OwningPointer;
+#[cfg(feature="alloc")]
+use alloc::boxed::Box;
+
+/// The number of key bits consumed by each level of the tree
+pub const RADIX_BITS: u32 = 6;
+
+/// The number of slots of an interior node
+pub const RADIX_SLOTS: usize = 1 << RADIX_BITS;
+
+/// The number of tags each slot carries
+pub const TAGS: usize = 3;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into a `RadixTree`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_radix_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: RadixLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into a `RadixTree`
+///
+/// # Safety
+///
+/// The trait is unsafe because any implementation must impl Drop to check
+/// that the element is not in a tree, and `offset()` must be the offset of
+/// the link within its `Container`.
+pub unsafe trait RadixLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &RadixLinks;
+    fn get_links_mut(&mut self) -> &mut RadixLinks;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+}
+
+/// A source of interior nodes for a `RadixTree`
+///
+/// # Safety
+///
+/// `alloc` must return memory for a `RadixNode`, which the tree initializes,
+/// and which must stay valid and unused by anything else until it is given
+/// back to `free`.
+pub unsafe trait NodePool {
+    /// Returns a node, or `None` if the pool is exhausted
+    fn alloc(&mut self) -> Option<NonNull<RadixNode>>;
+
+    /// Gives back a node returned by `alloc`
+    ///
+    /// # Safety
+    ///
+    /// `node` must have been returned by `alloc` on this pool, and not have
+    /// been freed since.
+    unsafe fn free(&mut self, node: NonNull<RadixNode>);
+}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into a `RadixTree`
+///
+/// `define_radix_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it in a tree.
+#[macro_export]
+macro_rules! define_radix_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_radix_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_radix_link!($link = $elt);
+        impl_radix_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_radix_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_radix_link!($link = $elt);
+        impl_radix_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_radix_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::radix_tree::RadixLinks);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::radix_tree::RadixLinks);
+    );
+}
+
+/// Implements `RadixLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_radix_link {
+    ($link:ident = $elt:ident) => (
+        impl_radix_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::radix_tree::RadixLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::radix_tree::RadixLinks {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::radix_tree::RadixLinks {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_radix_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_radix_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::radix_tree::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive radix tree keyed by `u64`
+///
+/// `N` is the pool interior nodes are taken from.
+pub struct RadixTree<P, T, S, L, N>
+    where T: OwningPointer<Target=S>,
+          L: RadixLinkable<Container=T::Target>,
+          N: NodePool
+{
+    length: usize,
+    root: *mut RadixNode,
+    pool: N,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>,
+    _marker4: PhantomData<L>
+}
+
+/// An interior node of a `RadixTree`
+///
+/// The slots of nodes at the bottom of the tree, whose shift is `0`, point to
+/// the links of elements, those of other nodes point to nodes one level down.
+pub struct RadixNode {
+    /// The position of the key bits this node consumes
+    shift: u32,
+    /// The slot of the parent holding this node
+    offset: u32,
+    parent: *mut RadixNode,
+    occupied: u64,
+    tags: [u64; TAGS],
+    slots: [*mut (); RADIX_SLOTS]
+}
+
+#[derive(Default, Debug)]
+pub struct RadixLinks
+{
+    /// The bottom node holding the element, null when not in a tree
+    parent: Cell<*mut RadixNode>,
+    key: Cell<u64>,
+    _pin: PhantomPinned
+}
+
+/// A `NodePool` handing out the nodes of a slice
+pub struct SlicePool<'a> {
+    free: *mut RadixNode,
+    available: usize,
+    _marker: PhantomData<&'a mut [RadixNode]>
+}
+
+/// A `NodePool` allocating nodes on the heap
+#[cfg(feature="alloc")]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct BoxPool;
+
+/// An iterator over the keys and values of a `RadixTree`, in key order
+pub struct Iter<'a, P, T, S, L, N>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: 'a,
+          L: RadixLinkable<Container=T::Target> + 'a,
+          N: NodePool + 'a
+{
+    tree: &'a RadixTree<P, T, S, L, N>,
+    next: Option<u64>,
+    tag: Option<usize>
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+/// Returns the mask of the `bits` lowest bits
+#[inline]
+fn low_mask(bits: u32) -> u64 {
+    if bits >= u64::BITS { !0 } else { (1 << bits) - 1 }
+}
+
+// RadixTree impls
+
+impl<P, T, S, L, N> RadixTree<P, T, S, L, N>
+    where T: OwningPointer<Target=S>,
+          L: RadixLinkable<Container=T::Target>,
+          N: NodePool
+{
+    fn alloc_node(&mut self, shift: u32) -> Option<*mut RadixNode> {
+        let node = self.pool.alloc()?.as_ptr();
+        unsafe {
+            node.write(RadixNode::new());
+            (*node).shift = shift;
+        }
+        Some(node)
+    }
+
+    fn free_node(&mut self, node: *mut RadixNode) {
+        unsafe { self.pool.free(NonNull::new_unchecked(node)) }
+    }
+
+    /// Returns the bottom node and slot holding `key`
+    fn find_slot(&self, key: u64) -> Option<(*mut RadixNode, usize)> {
+        let mut node = self.root;
+        if node.is_null() || !unsafe { &*node }.covers(key) {
+            return None;
+        }
+        loop {
+            let n = unsafe { &*node };
+            let slot = n.slot(key);
+            if n.occupied & (1 << slot) == 0 {
+                return None;
+            }
+            if n.shift == 0 {
+                return Some((node, slot));
+            }
+            node = n.slots[slot] as *mut RadixNode;
+        }
+    }
+
+    /// Returns the first key from `from` on whose slot is occupied, or tagged
+    /// with `tag`, along with its bottom node and slot
+    fn search(&self, from: u64, tag: Option<usize>)
+              -> Option<(u64, *mut RadixNode, usize)> {
+        let bitmap = |n: &RadixNode| match tag {
+            None => n.occupied,
+            Some(tag) => n.tags[tag],
+        };
+        let mut node = self.root;
+        if node.is_null() || !unsafe { &*node }.covers(from) {
+            return None;
+        }
+        let mut key = from;
+        loop {
+            let n = unsafe { &*node };
+            let off = n.slot(key);
+            let bits = bitmap(n) & (!0 << off);
+            if bits != 0 {
+                let slot = bits.trailing_zeros() as usize;
+                if slot != off {
+                    key = (key & !low_mask(n.shift + RADIX_BITS))
+                        | ((slot as u64) << n.shift);
+                }
+                if n.shift == 0 {
+                    return Some((key, node, slot));
+                }
+                node = n.slots[slot] as *mut RadixNode;
+                continue;
+            }
+            // this subtree is exhausted, carry on from the next slot up
+            loop {
+                let n = unsafe { &*node };
+                if n.parent.is_null() {
+                    return None;
+                }
+                let next = n.offset as usize + 1;
+                node = n.parent;
+                let parent = unsafe { &*node };
+                if next < parent.capacity() {
+                    key = (key & !low_mask(parent.shift + RADIX_BITS))
+                        | ((next as u64) << parent.shift);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn set_tag_at(mut node: *mut RadixNode, mut slot: usize, tag: usize) {
+        loop {
+            let n = unsafe { &mut *node };
+            if n.tags[tag] & (1 << slot) != 0 {
+                // the ancestors are already tagged
+                return;
+            }
+            n.tags[tag] |= 1 << slot;
+            if n.parent.is_null() {
+                return;
+            }
+            slot = n.offset as usize;
+            node = n.parent;
+        }
+    }
+
+    fn clear_tag_at(mut node: *mut RadixNode, mut slot: usize, tag: usize) {
+        loop {
+            let n = unsafe { &mut *node };
+            n.tags[tag] &= !(1 << slot);
+            if n.tags[tag] != 0 || n.parent.is_null() {
+                return;
+            }
+            slot = n.offset as usize;
+            node = n.parent;
+        }
+    }
+
+    /// Frees `node` and its ancestors as long as they are empty, then lowers
+    /// the root as long as only its first slot is occupied
+    fn prune(&mut self, mut node: *mut RadixNode) {
+        loop {
+            let n = unsafe { &*node };
+            if n.occupied != 0 {
+                break;
+            }
+            let (parent, offset) = (n.parent, n.offset);
+            self.free_node(node);
+            if parent.is_null() {
+                self.root = ptr::null_mut();
+                return;
+            }
+            let p = unsafe { &mut *parent };
+            p.occupied &= !(1 << offset);
+            p.slots[offset as usize] = ptr::null_mut();
+            node = parent;
+        }
+        loop {
+            let root = unsafe { &*self.root };
+            if root.shift == 0 || root.occupied != 1 {
+                return;
+            }
+            let child = root.slots[0] as *mut RadixNode;
+            self.free_node(self.root);
+            unsafe { (*child).parent = ptr::null_mut() };
+            self.root = child;
+        }
+    }
+
+    /// Unlinks the element in `slot` of the bottom node `node`
+    fn remove_slot(&mut self, node: *mut RadixNode, slot: usize) -> T {
+        for tag in 0..TAGS {
+            Self::clear_tag_at(node, slot, tag);
+        }
+        let links = unsafe {
+            let n = &mut *node;
+            n.occupied &= !(1 << slot);
+            &*(ptr::replace(&mut n.slots[slot], ptr::null_mut()) as *const L)
+        };
+        links.get_links().parent.set(ptr::null_mut());
+        self.length -= 1;
+        self.prune(node);
+        unsafe { T::from_raw(links.container_of() as *const S as *mut S) }
+    }
+
+    /// Removes all elements from the tree, giving its nodes back to the pool.
+    ///
+    /// This operation should compute in O(n) time.
+    pub fn clear(&mut self) {
+        while let Some((_, node, slot)) = self.search(0, None) {
+            drop(self.remove_slot(node, slot));
+        }
+    }
+}
+
+impl<P, T, S, L, N> RadixTree<P, T, S, L, N>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: RadixLinkable<Container=T::Target>,
+          N: NodePool
+{
+    /// Creates an empty `RadixTree` taking its nodes from `pool`
+    #[inline]
+    pub fn new(pool: N) -> RadixTree<P, T, S, L, N> {
+        RadixTree { length: 0, root: ptr::null_mut(), pool,
+                    _marker: PhantomData, _marker2: PhantomData,
+                    _marker3: PhantomData, _marker4: PhantomData }
+    }
+
+    /// Returns the pool the tree takes its nodes from
+    #[inline]
+    pub fn pool(&self) -> &N {
+        &self.pool
+    }
+
+    /// Returns the number of elements in the tree
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns `true` if the tree is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the value of the element in `slot` of the bottom node `node`
+    fn val<'a>(node: *mut RadixNode, slot: usize) -> &'a P
+        where S: 'a, L: 'a
+    {
+        unsafe {
+            (*((*node).slots[slot] as *const L)).container_of().get_val()
+        }
+    }
+
+    /// Returns the value of the element with key `key`, if any.
+    #[inline]
+    pub fn get(&self, key: u64) -> Option<&P> {
+        self.find_slot(key).map(|(node, slot)| Self::val(node, slot))
+    }
+
+    /// Returns `true` if the tree holds an element with key `key`.
+    #[inline]
+    pub fn contains_key(&self, key: u64) -> bool {
+        self.find_slot(key).is_some()
+    }
+
+    /// Inserts an element with key `key`, returning the element it replaces,
+    /// if any. The tags of the key are cleared when an element is replaced.
+    ///
+    /// The tree grows by as many nodes as `key` requires, and the element is
+    /// handed back as an error if the pool runs out of them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element is already in a tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::radix_tree::{RadixNode, RadixTree, SlicePool};
+    ///
+    /// define_radix_element!(Process = &'static str : PidLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut nodes: [RadixNode; 8] = Default::default();
+    /// let mut pids: RadixTree<_, Box<Process>, _, _, _> =
+    ///     RadixTree::new(SlicePool::new(&mut nodes));
+    /// assert!(pids.insert(1, Box::new(Process::new("init"))).unwrap().is_none());
+    /// assert!(pids.insert(4242, Box::new(Process::new("sh"))).unwrap().is_none());
+    ///
+    /// assert_eq!(pids.get(4242), Some(&"sh"));
+    /// assert_eq!(pids.next(2), Some((4242, &"sh")));
+    /// let old = pids.insert(1, Box::new(Process::new("systemd"))).unwrap();
+    /// assert_eq!(old.unwrap().0.val, "init");
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn insert(&mut self, key: u64, elt: T) -> Result<Option<T>, T> {
+        let links = elt.get_links();
+        assert!(links.get_links().parent.get().is_null(),
+                "Cannot insert an element which is already in a tree");
+        if self.root.is_null() {
+            let mut shift = 0;
+            while shift + RADIX_BITS < u64::BITS && key >> (shift + RADIX_BITS) != 0 {
+                shift += RADIX_BITS;
+            }
+            match self.alloc_node(shift) {
+                Some(node) => self.root = node,
+                None => return Err(elt),
+            }
+        }
+        // grow the tree until it covers `key`
+        while !unsafe { &*self.root }.covers(key) {
+            let shift = unsafe { &*self.root }.shift + RADIX_BITS;
+            let Some(node) = self.alloc_node(shift) else {
+                self.prune(self.root);
+                return Err(elt);
+            };
+            unsafe {
+                let (n, root) = (&mut *node, &mut *self.root);
+                n.slots[0] = self.root as *mut ();
+                n.occupied = 1;
+                for tag in 0..TAGS {
+                    n.tags[tag] = (root.tags[tag] != 0) as u64;
+                }
+                root.parent = node;
+                root.offset = 0;
+            }
+            self.root = node;
+        }
+        // and walk down to the bottom, adding the missing nodes
+        let mut node = self.root;
+        while unsafe { &*node }.shift > 0 {
+            let n = unsafe { &mut *node };
+            let slot = n.slot(key);
+            if n.occupied & (1 << slot) == 0 {
+                let Some(child) = self.alloc_node(n.shift - RADIX_BITS) else {
+                    self.prune(node);
+                    return Err(elt);
+                };
+                unsafe {
+                    (*child).parent = node;
+                    (*child).offset = slot as u32;
+                }
+                n.slots[slot] = child as *mut ();
+                n.occupied |= 1 << slot;
+            }
+            node = n.slots[slot] as *mut RadixNode;
+        }
+        let slot = unsafe { &*node }.slot(key);
+        let old = if unsafe { &*node }.occupied & (1 << slot) != 0 {
+            for tag in 0..TAGS {
+                Self::clear_tag_at(node, slot, tag);
+            }
+            let old = unsafe { &*((*node).slots[slot] as *const L) };
+            old.get_links().parent.set(ptr::null_mut());
+            self.length -= 1;
+            Some(unsafe { T::from_raw(old.container_of() as *const S as *mut S) })
+        } else {
+            None
+        };
+        unsafe {
+            (*node).slots[slot] = links as *const L as *mut ();
+            (*node).occupied |= 1 << slot;
+        }
+        links.get_links().parent.set(node);
+        links.get_links().key.set(key);
+        self.length += 1;
+        unsafe { elt.take() };
+        Ok(old)
+    }
+
+    /// Removes the element with key `key` and returns it, if any.
+    pub fn remove_key(&mut self, key: u64) -> Option<T> {
+        self.find_slot(key).map(|(node, slot)| self.remove_slot(node, slot))
+    }
+
+    /// Removes `elt` from the tree and returns it.
+    ///
+    /// This operation should compute in O(log k) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in this tree.
+    pub fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links().get_links();
+        let node = links.parent.get();
+        let mut top = node;
+        while !top.is_null() && !unsafe { &*top }.parent.is_null() {
+            top = unsafe { &*top }.parent;
+        }
+        assert!(!node.is_null() && top == self.root,
+                "Cannot remove an element of another tree");
+        let slot = unsafe { &*node }.slot(links.key.get());
+        self.remove_slot(node, slot)
+    }
+
+    /// Returns the key and value of the first element whose key is at least
+    /// `from`, if any.
+    #[inline]
+    pub fn next(&self, from: u64) -> Option<(u64, &P)> {
+        self.search(from, None).map(|(key, node, slot)| (key, Self::val(node, slot)))
+    }
+
+    /// Returns an iterator over the keys and values of the tree, in key order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, T, S, L, N> {
+        Iter { tree: self, next: Some(0), tag: None }
+    }
+
+    /// Sets tag `tag` on the element with key `key`. Returns `false` if there
+    /// is no such element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` is not below `TAGS`.
+    pub fn set_tag(&mut self, key: u64, tag: usize) -> bool {
+        assert!(tag < TAGS);
+        self.find_slot(key).map(|(node, slot)| Self::set_tag_at(node, slot, tag))
+            .is_some()
+    }
+
+    /// Clears tag `tag` of the element with key `key`. Returns `false` if
+    /// there is no such element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` is not below `TAGS`.
+    pub fn clear_tag(&mut self, key: u64, tag: usize) -> bool {
+        assert!(tag < TAGS);
+        self.find_slot(key).map(|(node, slot)| Self::clear_tag_at(node, slot, tag))
+            .is_some()
+    }
+
+    /// Returns `true` if the element with key `key` is tagged with `tag`.
+    #[inline]
+    pub fn get_tag(&self, key: u64, tag: usize) -> bool {
+        self.find_slot(key)
+            .is_some_and(|(node, slot)| unsafe { &*node }.tags[tag] & (1 << slot) != 0)
+    }
+
+    /// Returns `true` if any element is tagged with `tag`.
+    #[inline]
+    pub fn any_tagged(&self, tag: usize) -> bool {
+        !self.root.is_null() && unsafe { &*self.root }.tags[tag] != 0
+    }
+
+    /// Returns the key and value of the first element tagged with `tag` whose
+    /// key is at least `from`, if any.
+    ///
+    /// Untagged subtrees are skipped, so this operation should compute in
+    /// O(log k) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// # #[cfg(feature = "alloc")]
+    /// use intrusive_containers::radix_tree::{BoxPool, RadixTree};
+    ///
+    /// define_radix_element!(Page = u32 : PageLink);
+    ///
+    /// const DIRTY: usize = 0;
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut cache: RadixTree<_, Box<Page>, _, _, _> = RadixTree::new(BoxPool);
+    /// for index in 0..1000 {
+    ///     cache.insert(index, Box::new(Page::new(index as u32))).unwrap();
+    /// }
+    /// cache.set_tag(10, DIRTY);
+    /// cache.set_tag(700, DIRTY);
+    ///
+    /// assert_eq!(cache.next_tagged(0, DIRTY), Some((10, &10)));
+    /// assert_eq!(cache.next_tagged(11, DIRTY), Some((700, &700)));
+    /// cache.clear_tag(700, DIRTY);
+    /// assert_eq!(cache.next_tagged(11, DIRTY), None);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    #[inline]
+    pub fn next_tagged(&self, from: u64, tag: usize) -> Option<(u64, &P)> {
+        self.search(from, Some(tag))
+            .map(|(key, node, slot)| (key, Self::val(node, slot)))
+    }
+
+    /// Returns an iterator over the keys and values of the elements tagged
+    /// with `tag`, in key order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` is not below `TAGS`.
+    #[inline]
+    pub fn iter_tagged(&self, tag: usize) -> Iter<'_, P, T, S, L, N> {
+        assert!(tag < TAGS);
+        Iter { tree: self, next: Some(0), tag: Some(tag) }
+    }
+}
+
+impl<P, T, S, L, N> fmt::Debug for RadixTree<P, T, S, L, N>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: RadixLinkable<Container=T::Target>,
+          N: NodePool
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<P, T, S, L, N> Drop for RadixTree<P, T, S, L, N>
+    where T: OwningPointer<Target=S>,
+          L: RadixLinkable<Container=T::Target>,
+          N: NodePool
+{
+    fn drop(&mut self) {
+        self.clear()
+    }
+}
+
+unsafe impl<P, T, S, L, N> Send for RadixTree<P, T, S, L, N>
+    where T: OwningPointer<Target=S> + Send,
+          L: RadixLinkable<Container=T::Target>,
+          N: NodePool + Send
+{}
+
+// RadixNode impls
+
+impl RadixNode {
+    /// Creates an unused node, to be handed out by a `NodePool`
+    pub const fn new() -> RadixNode {
+        RadixNode { shift: 0, offset: 0, parent: ptr::null_mut(), occupied: 0,
+                    tags: [0; TAGS], slots: [ptr::null_mut(); RADIX_SLOTS] }
+    }
+
+    /// Returns `true` if keys up to `key` fall under this node, which is the
+    /// root
+    #[inline]
+    fn covers(&self, key: u64) -> bool {
+        self.shift + RADIX_BITS >= u64::BITS || key >> (self.shift + RADIX_BITS) == 0
+    }
+
+    /// Returns the slot `key` falls in
+    #[inline]
+    fn slot(&self, key: u64) -> usize {
+        ((key >> self.shift) & (RADIX_SLOTS as u64 - 1)) as usize
+    }
+
+    /// Returns the number of slots in use at this level, which is less than
+    /// `RADIX_SLOTS` for the topmost level
+    #[inline]
+    fn capacity(&self) -> usize {
+        1 << (u64::BITS - self.shift).min(RADIX_BITS)
+    }
+}
+
+impl Default for RadixNode {
+    fn default() -> RadixNode {
+        RadixNode::new()
+    }
+}
+
+impl fmt::Debug for RadixNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RadixNode")
+            .field("shift", &self.shift)
+            .field("occupied", &self.occupied)
+            .field("tags", &self.tags)
+            .finish()
+    }
+}
+
+// RadixLinks impls
+
+impl Clone for RadixLinks
+{
+    /// A clone is not in any tree, even if the original is
+    fn clone(&self) -> RadixLinks {
+        Default::default()
+    }
+}
+
+impl Drop for RadixLinks
+{
+    fn drop(&mut self) {
+        assert!(self.parent.get().is_null());
+    }
+}
+
+unsafe impl Send for RadixLinks {}
+
+// SlicePool impls
+
+impl<'a> SlicePool<'a> {
+    /// Creates a pool handing out the nodes of `nodes`
+    pub fn new(nodes: &'a mut [RadixNode]) -> SlicePool<'a> {
+        let mut pool = SlicePool { free: ptr::null_mut(), available: 0,
+                                   _marker: PhantomData };
+        for node in nodes {
+            unsafe { pool.free(NonNull::from(node)) };
+        }
+        pool
+    }
+
+    /// Returns the number of nodes left in the pool
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.available
+    }
+}
+
+unsafe impl<'a> NodePool for SlicePool<'a> {
+    fn alloc(&mut self) -> Option<NonNull<RadixNode>> {
+        let node = NonNull::new(self.free)?;
+        self.free = unsafe { node.as_ref() }.parent;
+        self.available -= 1;
+        Some(node)
+    }
+
+    unsafe fn free(&mut self, mut node: NonNull<RadixNode>) {
+        // free nodes are chained through their parent pointer
+        node.as_mut().parent = self.free;
+        self.free = node.as_ptr();
+        self.available += 1;
+    }
+}
+
+impl<'a> fmt::Debug for SlicePool<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SlicePool").field("available", &self.available).finish()
+    }
+}
+
+// The pool has exclusive use of its nodes
+unsafe impl<'a> Send for SlicePool<'a> {}
+
+// BoxPool impls
+
+#[cfg(feature="alloc")]
+unsafe impl NodePool for BoxPool {
+    fn alloc(&mut self) -> Option<NonNull<RadixNode>> {
+        Some(NonNull::from(Box::leak(Box::new(RadixNode::new()))))
+    }
+
+    unsafe fn free(&mut self, node: NonNull<RadixNode>) {
+        drop(Box::from_raw(node.as_ptr()))
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, S, L, N> Iterator for Iter<'a, P, T, S, L, N>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: RadixLinkable<Container=T::Target> + 'a,
+          N: NodePool + 'a
+{
+    type Item = (u64, &'a P);
+
+    #[inline]
+    fn next(&mut self) -> Option<(u64, &'a P)> {
+        let tree = self.tree;
+        let (key, node, slot) = tree.search(self.next?, self.tag)?;
+        self.next = key.checked_add(1);
+        Some((key, RadixTree::<P, T, S, L, N>::val(node, slot)))
+    }
+}
+
+impl<'a, P, T, S, L, N> IntoIterator for &'a RadixTree<P, T, S, L, N>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: RadixLinkable<Container=T::Target> + 'a,
+          N: NodePool + 'a
+{
+    type Item = (u64, &'a P);
+    type IntoIter = Iter<'a, P, T, S, L, N>;
+
+    fn into_iter(self) -> Iter<'a, P, T, S, L, N> {
+        self.iter()
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(test)]
+mod no_alloc_tests {
+    use std::vec::Vec;
+    use super::{RadixNode, RadixTree, SlicePool};
+
+    define_radix_element!(MyU64 = u64 : MyLink);
+
+    #[test]
+    fn test_slice_pool() {
+        let mut nodes: [RadixNode; 32] = Default::default();
+        let mut elts = [1, 64, 4096, 1 << 40].map(MyU64::new);
+        let mut t = RadixTree::new(SlicePool::new(&mut nodes));
+        for elt in elts.iter_mut() {
+            let key = elt.0.val;
+            assert!(t.insert(key, elt).unwrap().is_none());
+        }
+        assert_eq!(t.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+                   [1, 64, 4096, 1 << 40]);
+        assert_eq!(t.get(4096), Some(&4096));
+        assert_eq!(t.remove_key(64).map(|elt| elt.0.val), Some(64));
+        t.clear();
+        assert_eq!(t.pool().available(), 32);
+    }
+
+    #[test]
+    fn test_slice_pool_exhausted() {
+        let mut nodes: [RadixNode; 1] = Default::default();
+        let mut elts = [0, u64::MAX].map(MyU64::new);
+        let [a, b] = &mut elts;
+        let mut t = RadixTree::new(SlicePool::new(&mut nodes));
+        assert!(t.insert(0, a).unwrap().is_none());
+        // a second key needs more nodes than the pool holds
+        let b = t.insert(u64::MAX, b).unwrap_err();
+        assert_eq!(b.0.val, u64::MAX);
+        assert_eq!(t.len(), 1);
+    }
+}
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::collections::BTreeMap;
+    use std::vec::Vec;
+    use rand::{thread_rng, Rng};
+    use super::{BoxPool, RadixNode, RadixTree, SlicePool, TAGS};
+
+    define_radix_element!(MyU64 = u64 : MyLink);
+
+    fn elt(v: u64) -> Box<MyU64> {
+        Box::new(MyU64::new(v))
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut t: RadixTree<_, Box<MyU64>, _, _, _> = RadixTree::new(BoxPool);
+        assert!(t.is_empty());
+        assert!(t.get(0).is_none());
+        assert!(t.next(0).is_none());
+        for k in [5, 0, 64, 4096, 1 << 40, u64::MAX] {
+            assert!(t.insert(k, elt(k)).unwrap().is_none());
+        }
+        assert_eq!(t.len(), 6);
+        for k in [5, 0, 64, 4096, 1 << 40, u64::MAX] {
+            assert_eq!(t.get(k), Some(&k));
+        }
+        assert!(t.get(6).is_none());
+        assert!(!t.contains_key(1 << 41));
+        assert_eq!(t.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+                   [0, 5, 64, 4096, 1 << 40, u64::MAX]);
+        assert_eq!(t.next(65), Some((4096, &4096)));
+        assert_eq!(t.next((1 << 40) + 1), Some((u64::MAX, &u64::MAX)));
+        let old = t.insert(64, elt(1)).unwrap();
+        assert_eq!(old.map(|e| e.0.val), Some(64));
+        assert_eq!(t.get(64), Some(&1));
+        assert_eq!(t.remove_key(4096).map(|e| e.0.val), Some(4096));
+        assert!(t.remove_key(4096).is_none());
+        assert_eq!(t.len(), 5);
+    }
+
+    #[test]
+    fn test_remove_gives_nodes_back() {
+        let mut nodes: [RadixNode; 16] = Default::default();
+        let mut t: RadixTree<_, Box<MyU64>, _, _, _> =
+            RadixTree::new(SlicePool::new(&mut nodes));
+        let a = elt(3);
+        let pa: *const MyU64 = &*a;
+        t.insert(3, a).unwrap();
+        assert_eq!(t.pool().available(), 15);
+        // a key 2^20 needs four levels, the three upper ones of which are new
+        // and the three lower ones on a new path
+        let b = elt(1 << 20);
+        let pb: *const MyU64 = &*b;
+        t.insert(1 << 20, b).unwrap();
+        assert_eq!(t.pool().available(), 9);
+        assert_eq!(t.remove(unsafe { &*pb }).0.val, 1 << 20);
+        // the root shrinks back to a single node
+        assert_eq!(t.pool().available(), 15);
+        assert_eq!(t.remove(unsafe { &*pa }).0.val, 3);
+        assert_eq!(t.pool().available(), 16);
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_pool_exhausted() {
+        let mut nodes: [RadixNode; 3] = Default::default();
+        let mut t: RadixTree<_, Box<MyU64>, _, _, _> =
+            RadixTree::new(SlicePool::new(&mut nodes));
+        t.insert(1, elt(1)).unwrap();
+        t.insert(100, elt(100)).unwrap();
+        assert_eq!(t.pool().available(), 0);
+        // a third level does not fit
+        let e = t.insert(1 << 12, elt(7)).unwrap_err();
+        assert_eq!(e.0.val, 7);
+        assert_eq!(t.pool().available(), 0);
+        assert_eq!(t.iter().map(|(k, _)| k).collect::<Vec<_>>(), [1, 100]);
+        t.clear();
+        assert_eq!(t.pool().available(), 3);
+        // nor does a fourth one in an empty tree, and the first three are
+        // given back
+        let e = t.insert(1 << 18, elt(7)).unwrap_err();
+        assert_eq!(e.0.val, 7);
+        assert_eq!(t.pool().available(), 3);
+        assert!(t.is_empty());
+        t.insert(1 << 12, elt(7)).unwrap();
+        assert_eq!(t.get(1 << 12), Some(&7));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_other_tree() {
+        let mut t1: RadixTree<_, Box<MyU64>, _, _, _> = RadixTree::new(BoxPool);
+        let mut t2: RadixTree<_, Box<MyU64>, _, _, _> = RadixTree::new(BoxPool);
+        let a = elt(1);
+        let pa: *const MyU64 = &*a;
+        t1.insert(1, a).unwrap();
+        t2.insert(1, elt(1)).unwrap();
+        t2.remove(unsafe { &*pa });
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut t: RadixTree<_, Box<MyU64>, _, _, _> = RadixTree::new(BoxPool);
+        for k in 0..5000 {
+            t.insert(k * 3, elt(k * 3)).unwrap();
+        }
+        assert!(!t.set_tag(1, 0));
+        assert!(!t.any_tagged(0));
+        for k in [30, 3000, 9000] {
+            assert!(t.set_tag(k, 0));
+        }
+        t.set_tag(3000, 2);
+        assert!(t.get_tag(3000, 0) && t.get_tag(3000, 2) && !t.get_tag(3000, 1));
+        assert_eq!(t.iter_tagged(0).map(|(k, _)| k).collect::<Vec<_>>(),
+                   [30, 3000, 9000]);
+        assert_eq!(t.next_tagged(31, 0), Some((3000, &3000)));
+        assert!(t.clear_tag(3000, 0));
+        assert_eq!(t.next_tagged(31, 0), Some((9000, &9000)));
+        // removing an element clears its tags
+        t.remove_key(9000);
+        assert_eq!(t.next_tagged(31, 0), None);
+        assert_eq!(t.next_tagged(0, 2), Some((3000, &3000)));
+        t.insert(3000, elt(1)).unwrap();
+        assert!((0..TAGS).all(|tag| !t.get_tag(3000, tag)));
+        assert!(!t.any_tagged(2));
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = thread_rng();
+        let mut t: RadixTree<_, Box<MyU64>, _, _, _> = RadixTree::new(BoxPool);
+        let mut m = BTreeMap::new();
+        for _ in 0..2000 {
+            let key = match rng.gen_range(0..3) {
+                0 => rng.gen_range(0..256),
+                1 => rng.gen_range(0..1 << 20),
+                _ => rng.gen(),
+            };
+            if rng.gen_bool(0.6) {
+                let old = t.insert(key, elt(key)).unwrap();
+                assert_eq!(old.is_some(), m.insert(key, key % 7 == 0).is_some());
+                if key % 7 == 0 {
+                    t.set_tag(key, 1);
+                }
+            } else {
+                assert_eq!(t.remove_key(key).is_some(), m.remove(&key).is_some());
+            }
+            let from = rng.gen();
+            assert_eq!(t.next(from).map(|(k, _)| k),
+                       m.range(from..).next().map(|(&k, _)| k));
+            assert_eq!(t.next_tagged(from, 1).map(|(k, _)| k),
+                       m.range(from..).find(|(_, &tagged)| tagged).map(|(&k, _)| k));
+        }
+        assert_eq!(t.len(), m.len());
+        assert!(t.iter().map(|(k, _)| k).eq(m.keys().cloned()));
+    }
+}
@@ -22,20 +22,17 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-#[cfg(all(feature="nostd",not(test)))]
-use core::prelude::*;
-use std::mem;
-use std::ptr;
+use core::mem;
+use core::ptr;
 
-#[allow(raw_pointer_derive)]
+/// Like the raw pointer it wraps, a `Rawlink` is neither `Send` nor `Sync`:
+/// the structures holding them state when it is safe to share or send them.
 #[derive(Debug)]
 pub struct Rawlink<T> {
     p: *mut T
 }
 
 impl<T> Copy for Rawlink<T> {}
-unsafe impl<T:'static+Send> Send for Rawlink<T> {}
-unsafe impl<T:Send+Sync> Sync for Rawlink<T> {}
 
 /// Rawlink is a type like Option<T> but for holding a raw pointer
 impl<T> Rawlink<T> {
@@ -45,29 +42,37 @@ impl<T> Rawlink<T> {
     }
 
     /// Like Option::Some for Rawlink
-    pub fn some(n: &mut T) -> Rawlink<T> {
-        Rawlink{p: n}
+    pub fn some(n: &T) -> Rawlink<T> {
+        Rawlink{p: n as *const T as *mut T}
+    }
+
+    /// Like `Rawlink::some`, or `Rawlink::none` if `p` is null
+    pub fn from_ptr(p: *mut T) -> Rawlink<T> {
+        Rawlink{p}
+    }
+
+    /// The pointer wrapped by the `Rawlink`, null for `Rawlink::none()`
+    pub fn as_ptr(&self) -> *mut T {
+        self.p
     }
 
     /// Convert the `Rawlink` into an Option value
     pub fn resolve<'a>(&self) -> Option<&'a T> {
         unsafe {
-            mem::transmute(self.p.as_ref())
+            self.p.as_ref()
         }
     }
 
     /// Convert the `Rawlink` into an Option value
     pub fn resolve_mut<'a>(&mut self) -> Option<&'a mut T> {
-        if self.p.is_null() {
-            None
-        } else {
-            Some(unsafe { mem::transmute(self.p) })
+        unsafe {
+            self.p.as_mut()
         }
     }
 
     /// Return the `Rawlink` and replace with `Rawlink::none()`
     pub fn take(&mut self) -> Rawlink<T> {
-        mem::replace(self, Rawlink::none())
+        mem::take(self)
     }
 }
 
@@ -81,7 +86,7 @@ impl<T> PartialEq for Rawlink<T> {
 impl<T> Clone for Rawlink<T> {
     #[inline]
     fn clone(&self) -> Rawlink<T> {
-        Rawlink{p: self.p}
+        *self
     }
 }
 
@@ -0,0 +1,1342 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive red-black tree.
+//!
+//! The `RBTree` keeps its elements sorted by value and supports lookup,
+//! insertion and removal in O(log n) time. Elements with equal values are
+//! allowed and are kept in insertion order.
+use core::borrow::Borrow;
+use core::cell::Cell;
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ptr;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into an `RBTree`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_rbtree_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: RBLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into an `RBTree`
+///
+/// The root of a tree is its own parent, so that an element is in a tree
+/// exactly when its parent link is set.
+///
+/// # Safety
+///
+/// The trait is unsafe because `offset()` must be the offset of the link
+/// within its `Container`.
+pub unsafe trait RBLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &RBLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut RBLinks<Self>;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+}
+
+/// Accessors for the links of a `RBLinkable`
+///
+/// They are private to the crate, so that the links of an element are only
+/// followed or changed by the tree it is in.
+pub(crate) trait RBLinkOps : RBLinkable
+{
+    fn get_parent(&self) -> Rawlink<Self> {
+        self.get_links().parent.get()
+    }
+    fn set_parent(&self, parent: Rawlink<Self>) {
+        self.get_links().parent.set(parent)
+    }
+    fn get_left(&self) -> Rawlink<Self> {
+        self.get_links().left.get()
+    }
+    fn set_left(&self, left: Rawlink<Self>) {
+        self.get_links().left.set(left)
+    }
+    fn get_right(&self) -> Rawlink<Self> {
+        self.get_links().right.get()
+    }
+    fn set_right(&self, right: Rawlink<Self>) {
+        self.get_links().right.set(right)
+    }
+    fn get_color(&self) -> Color {
+        self.get_links().color.get()
+    }
+    fn set_color(&self, color: Color) {
+        self.get_links().color.set(color)
+    }
+    fn check_links(&self) {
+        assert!(self.get_parent().resolve().is_none());
+        assert!(self.get_left().resolve().is_none());
+        assert!(self.get_right().resolve().is_none());
+    }
+}
+
+impl<L: RBLinkable> RBLinkOps for L {}
+
+/// The balancing of a red-black tree, shared by `RBTree` and `AugTree`
+///
+/// It is implemented by the tree, whose root it is given. A tree keeping
+/// something about each subtree, such as the summary of an `AugTree`,
+/// recomputes it in `update`, which is called on every node whose children
+/// were changed by a rotation or a removal.
+pub(crate) trait Balance<L: RBLinkable>
+{
+    /// Recomputes what the tree keeps about the subtree at `node` from what
+    /// it keeps about its children
+    fn update(_node: &L) {}
+
+    /// Updates the nodes on the path from `node` to the root
+    fn update_to_root(mut node: Option<&L>) {
+        while let Some(n) = node {
+            Self::update(n);
+            node = parent(n);
+        }
+    }
+
+    /// Replaces the subtree rooted at `old` with the one rooted at `new`
+    fn transplant(root: &mut Rawlink<L>, old: &L, new: Option<&L>) {
+        match parent(old) {
+            None => {
+                *root = link_of(new);
+                if let Some(n) = new {
+                    n.set_parent(Rawlink::some(n));
+                }
+            }
+            Some(p) => {
+                if same(left(p), Some(old)) {
+                    p.set_left(link_of(new));
+                } else {
+                    p.set_right(link_of(new));
+                }
+                if let Some(n) = new {
+                    n.set_parent(Rawlink::some(p));
+                }
+            }
+        }
+    }
+
+    // A rotation keeps the elements of the rotated subtree, so only the two
+    // nodes which swap places need to be updated
+
+    fn rotate_left(root: &mut Rawlink<L>, x: &L) {
+        let y = right(x).unwrap();
+        x.set_right(y.get_left());
+        if let Some(b) = left(y) {
+            b.set_parent(Rawlink::some(x));
+        }
+        Self::transplant(root, x, Some(y));
+        y.set_left(Rawlink::some(x));
+        x.set_parent(Rawlink::some(y));
+        Self::update(x);
+        Self::update(y);
+    }
+
+    fn rotate_right(root: &mut Rawlink<L>, x: &L) {
+        let y = left(x).unwrap();
+        x.set_left(y.get_right());
+        if let Some(b) = right(y) {
+            b.set_parent(Rawlink::some(x));
+        }
+        Self::transplant(root, x, Some(y));
+        y.set_right(Rawlink::some(x));
+        x.set_parent(Rawlink::some(y));
+        Self::update(x);
+        Self::update(y);
+    }
+
+    /// Restores the red-black properties after `node` was linked in red
+    fn insert_fixup(root: &mut Rawlink<L>, mut node: &L) {
+        while let Some(mut p) = parent(node) {
+            if p.get_color() == Color::Black {
+                break;
+            }
+            // a red node is never the root, so the grandparent exists
+            let g = parent(p).unwrap();
+            if same(left(g), Some(p)) {
+                let uncle = right(g);
+                if is_red(uncle) {
+                    p.set_color(Color::Black);
+                    uncle.unwrap().set_color(Color::Black);
+                    g.set_color(Color::Red);
+                    node = g;
+                    continue;
+                }
+                if same(right(p), Some(node)) {
+                    Self::rotate_left(root, p);
+                    p = node;
+                }
+                p.set_color(Color::Black);
+                g.set_color(Color::Red);
+                Self::rotate_right(root, g);
+            } else {
+                let uncle = left(g);
+                if is_red(uncle) {
+                    p.set_color(Color::Black);
+                    uncle.unwrap().set_color(Color::Black);
+                    g.set_color(Color::Red);
+                    node = g;
+                    continue;
+                }
+                if same(left(p), Some(node)) {
+                    Self::rotate_right(root, p);
+                    p = node;
+                }
+                p.set_color(Color::Black);
+                g.set_color(Color::Red);
+                Self::rotate_left(root, g);
+            }
+            break;
+        }
+        root.resolve().unwrap().set_color(Color::Black);
+    }
+
+    /// Unlinks `node` from the tree and rebalances it
+    fn unlink(root: &mut Rawlink<L>, node: &L) {
+        let mut color = node.get_color();
+        let child;
+        let child_parent;
+        match (left(node), right(node)) {
+            (None, r) => {
+                child = r;
+                child_parent = parent(node);
+                Self::transplant(root, node, r);
+            }
+            (l, None) => {
+                child = l;
+                child_parent = parent(node);
+                Self::transplant(root, node, l);
+            }
+            (Some(l), Some(r)) => {
+                let next = minimum(r);
+                color = next.get_color();
+                child = right(next);
+                if ptr::eq(r, next) {
+                    child_parent = Some(next);
+                } else {
+                    child_parent = parent(next);
+                    Self::transplant(root, next, child);
+                    next.set_right(Rawlink::some(r));
+                    r.set_parent(Rawlink::some(next));
+                }
+                Self::transplant(root, node, Some(next));
+                next.set_left(Rawlink::some(l));
+                l.set_parent(Rawlink::some(next));
+                next.set_color(node.get_color());
+            }
+        }
+        node.set_parent(Rawlink::none());
+        node.set_left(Rawlink::none());
+        node.set_right(Rawlink::none());
+        node.set_color(Color::Red);
+
+        // every subtree which lost `node` lies on the path from where the
+        // tree was changed, which passes through any successor moved up
+        Self::update_to_root(child_parent);
+        if color == Color::Black {
+            Self::delete_fixup(root, child, child_parent);
+        }
+    }
+
+    /// Restores the red-black properties after a black node was removed above
+    /// `node`, whose parent is `node_parent`
+    fn delete_fixup<'a>(root: &mut Rawlink<L>, mut node: Option<&'a L>,
+                        mut node_parent: Option<&'a L>) {
+        while let Some(p) = node_parent {
+            if is_red(node) {
+                break;
+            }
+            // the sibling exists since `node` is short one black node
+            if same(left(p), node) {
+                let mut sibling = right(p).unwrap();
+                if sibling.get_color() == Color::Red {
+                    sibling.set_color(Color::Black);
+                    p.set_color(Color::Red);
+                    Self::rotate_left(root, p);
+                    sibling = right(p).unwrap();
+                }
+                if !is_red(left(sibling)) && !is_red(right(sibling)) {
+                    sibling.set_color(Color::Red);
+                    node = Some(p);
+                    node_parent = parent(p);
+                    continue;
+                }
+                if !is_red(right(sibling)) {
+                    left(sibling).unwrap().set_color(Color::Black);
+                    sibling.set_color(Color::Red);
+                    Self::rotate_right(root, sibling);
+                    sibling = right(p).unwrap();
+                }
+                sibling.set_color(p.get_color());
+                p.set_color(Color::Black);
+                right(sibling).unwrap().set_color(Color::Black);
+                Self::rotate_left(root, p);
+            } else {
+                let mut sibling = left(p).unwrap();
+                if sibling.get_color() == Color::Red {
+                    sibling.set_color(Color::Black);
+                    p.set_color(Color::Red);
+                    Self::rotate_right(root, p);
+                    sibling = left(p).unwrap();
+                }
+                if !is_red(left(sibling)) && !is_red(right(sibling)) {
+                    sibling.set_color(Color::Red);
+                    node = Some(p);
+                    node_parent = parent(p);
+                    continue;
+                }
+                if !is_red(left(sibling)) {
+                    right(sibling).unwrap().set_color(Color::Black);
+                    sibling.set_color(Color::Red);
+                    Self::rotate_left(root, sibling);
+                    sibling = left(p).unwrap();
+                }
+                sibling.set_color(p.get_color());
+                p.set_color(Color::Black);
+                left(sibling).unwrap().set_color(Color::Black);
+                Self::rotate_right(root, p);
+            }
+            node = root.resolve();
+            break;
+        }
+        if let Some(n) = node {
+            n.set_color(Color::Black);
+        }
+    }
+}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into an `RBTree`
+///
+/// `define_rbtree_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it in a tree. The
+/// tree is ordered by the `i32`.
+#[macro_export]
+macro_rules! define_rbtree_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_rbtree_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_rbtree_link!($link = $elt);
+        impl_rbtree_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_rbtree_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_rbtree_link!($link = $elt);
+        impl_rbtree_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_rbtree_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::rbtree::RBLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::rbtree::RBLinks<$link>);
+    );
+}
+
+/// Implements `RBLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_rbtree_link {
+    ($link:ident = $elt:ident) => (
+        impl_rbtree_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::rbtree::RBLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::rbtree::RBLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::rbtree::RBLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_rbtree_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_rbtree_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::rbtree::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive red-black tree
+pub struct RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: RBLinkable<Container=T::Target>
+{
+    length: usize,
+    root: Rawlink<L>,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+/// The color of a node in an `RBTree`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Color {
+    #[default]
+    Red,
+    Black
+}
+
+#[derive(Default, Debug)]
+pub struct RBLinks<L: RBLinkable>
+{
+    parent: Cell<Rawlink<L>>,
+    left: Cell<Rawlink<L>>,
+    right: Cell<Rawlink<L>>,
+    color: Cell<Color>,
+    _pin: PhantomPinned
+}
+
+/// An in-order iterator over references to the items of an `RBTree`
+pub struct Iter<'a, P: 'a, T, L: RBLinkable<Container=T>> {
+    head: Rawlink<L>,
+    tail: Rawlink<L>,
+    nelem: usize,
+    _marker: PhantomData<&'a P>
+}
+
+pub struct IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: RBLinkable<Container=T::Target>
+{
+    tree: RBTree<P, T, S, L>
+}
+
+//////////////////////
+// Tree Navigation  //
+//////////////////////
+
+pub(crate) fn parent<L: RBLinkable>(node: &L) -> Option<&L> {
+    node.get_parent().resolve().filter(|&p| !ptr::eq(p, node))
+}
+
+pub(crate) fn left<L: RBLinkable>(node: &L) -> Option<&L> {
+    node.get_left().resolve()
+}
+
+pub(crate) fn right<L: RBLinkable>(node: &L) -> Option<&L> {
+    node.get_right().resolve()
+}
+
+pub(crate) fn is_red<L: RBLinkable>(node: Option<&L>) -> bool {
+    node.is_some_and(|n| n.get_color() == Color::Red)
+}
+
+pub(crate) fn same<L>(a: Option<&L>, b: Option<&L>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => ptr::eq(a, b),
+        (None, None) => true,
+        _ => false
+    }
+}
+
+pub(crate) fn minimum<L: RBLinkable>(mut node: &L) -> &L {
+    while let Some(l) = left(node) {
+        node = l;
+    }
+    node
+}
+
+pub(crate) fn maximum<L: RBLinkable>(mut node: &L) -> &L {
+    while let Some(r) = right(node) {
+        node = r;
+    }
+    node
+}
+
+pub(crate) fn successor<L: RBLinkable>(node: &L) -> Option<&L> {
+    if let Some(r) = right(node) {
+        return Some(minimum(r));
+    }
+    let mut node = node;
+    while let Some(p) = parent(node) {
+        if same(left(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+pub(crate) fn predecessor<L: RBLinkable>(node: &L) -> Option<&L> {
+    if let Some(l) = left(node) {
+        return Some(maximum(l));
+    }
+    let mut node = node;
+    while let Some(p) = parent(node) {
+        if same(right(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+pub(crate) fn link_of<L: RBLinkable>(node: Option<&L>) -> Rawlink<L> {
+    node.map_or(Rawlink::none(), Rawlink::some)
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// RBTree impls
+
+impl<P, T, S, L> RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    /// Creates an empty `RBTree`
+    #[inline]
+    pub fn new() -> RBTree<P, T, S, L> {
+        RBTree { length: 0, root: Rawlink::none(),
+                 _marker: PhantomData, _marker2: PhantomData,
+                 _marker3: PhantomData}
+    }
+
+    /// Provides a double-ended iterator over the elements in order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        let root = self.root.resolve();
+        Iter{nelem: self.length,
+             head: link_of(root.map(minimum)),
+             tail: link_of(root.map(maximum)),
+             _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `RBTree` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of elements in the `RBTree`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all elements from the `RBTree`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = RBTree::new()
+    }
+
+    /// Provides a reference to the smallest element, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    #[inline]
+    pub fn first(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { minimum(root).container_of() }.get_val()
+        })
+    }
+
+    /// Provides a reference to the largest element, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    #[inline]
+    pub fn last(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { maximum(root).container_of() }.get_val()
+        })
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let node = minimum(root);
+            self.delete(node);
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Removes the largest element and returns it, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let node = maximum(root);
+            self.delete(node);
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Unlinks `elt` from the tree and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) time. In debug builds the
+    /// parent links are followed to check that `elt` belongs to this tree.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this tree. Removing an element of another tree would leave
+    /// that tree with an incorrect length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::RBTree;
+    ///
+    /// define_rbtree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t = RBTree::new();
+    /// let two = Box::new(MyI32::new(2));
+    /// let ptr: *const MyI32 = &*two;
+    /// t.insert(Box::new(MyI32::new(1)));
+    /// t.insert(two);
+    /// t.insert(Box::new(MyI32::new(3)));
+    ///
+    /// assert_eq!(unsafe { t.remove(&*ptr) }, Box::new(MyI32::new(2)));
+    /// assert_eq!(t.iter().cloned().collect::<Vec<_>>(), [1, 3]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links();
+        assert!(links.get_parent().resolve().is_some(),
+                "Cannot remove an element which is not in a tree");
+        debug_assert!(self.contains_links(links),
+                      "Cannot remove an element of another tree");
+        self.delete(links);
+        T::from_raw(links.container_of() as *const S as *mut S)
+    }
+
+    /// Climbs to the root from `links` to check it is in this tree
+    fn contains_links(&self, links: &L) -> bool {
+        let mut node = links;
+        while let Some(p) = parent(node) {
+            node = p;
+        }
+        same(self.root.resolve(), Some(node))
+    }
+
+    /// Unlinks `node` from the tree
+    fn delete(&mut self, node: &L) {
+        Self::unlink(&mut self.root, node);
+        self.length -= 1;
+    }
+}
+
+impl<P, T, S, L> RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    /// Inserts an element into the tree.
+    ///
+    /// An element equal to some already in the tree is placed after them.
+    ///
+    /// This operation should compute in O(log n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::RBTree;
+    ///
+    /// define_rbtree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t = RBTree::new();
+    /// t.insert(Box::new(MyI32::new(3)));
+    /// t.insert(Box::new(MyI32::new(1)));
+    /// t.insert(Box::new(MyI32::new(2)));
+    /// assert_eq!(t.iter().cloned().collect::<Vec<_>>(), [1, 2, 3]);
+    /// assert_eq!(t.iter().rev().cloned().collect::<Vec<_>>(), [3, 2, 1]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn insert(&mut self, elt: T) {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        let val = elt.get_val();
+        let links = elt.get_links();
+        let mut cur = self.root.resolve();
+        let mut parent = None;
+        let mut go_left = false;
+        while let Some(node) = cur {
+            parent = Some(node);
+            go_left = val < unsafe { node.container_of() }.get_val();
+            cur = if go_left { left(node) } else { right(node) };
+        }
+        match parent {
+            None => {
+                links.set_parent(Rawlink::some(links));
+                self.root = Rawlink::some(links);
+            }
+            Some(p) => {
+                links.set_parent(Rawlink::some(p));
+                if go_left {
+                    p.set_left(Rawlink::some(links));
+                } else {
+                    p.set_right(Rawlink::some(links));
+                }
+            }
+        }
+        links.set_color(Color::Red);
+        self.length += 1;
+        Self::insert_fixup(&mut self.root, links);
+        unsafe { elt.take() };
+    }
+
+    /// Provides a reference to an element equal to `key`, or `None` if there
+    /// is none.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn find<Q>(&self, key: &Q) -> Option<&P>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        self.lower_bound(key).filter(|val| (*val).borrow() == key)
+    }
+
+    /// Provides a reference to the first element which is not less than
+    /// `key`, or `None` if there is none.
+    ///
+    /// This operation should compute in O(log n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::RBTree;
+    ///
+    /// define_rbtree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let t: RBTree<_, _, _, MyLink> =
+    ///     [10, 20, 30].iter().map(|&i| Box::new(MyI32::new(i))).collect();
+    /// assert_eq!(t.lower_bound(&20), Some(&20));
+    /// assert_eq!(t.upper_bound(&20), Some(&30));
+    /// assert_eq!(t.lower_bound(&15), Some(&20));
+    /// assert_eq!(t.upper_bound(&30), None);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn lower_bound<Q>(&self, key: &Q) -> Option<&P>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        self.bound(|val| val.borrow() >= key)
+    }
+
+    /// Provides a reference to the first element which is greater than `key`,
+    /// or `None` if there is none.
+    ///
+    /// This operation should compute in O(log n) time.
+    pub fn upper_bound<Q>(&self, key: &Q) -> Option<&P>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        self.bound(|val| val.borrow() > key)
+    }
+
+    /// Finds the first element for which `past` holds, given that it holds
+    /// for every element after it
+    fn bound<F: Fn(&P) -> bool>(&self, past: F) -> Option<&P> {
+        let mut cur = self.root.resolve();
+        let mut found = None;
+        while let Some(node) = cur {
+            let val = unsafe { node.container_of() }.get_val();
+            if past(val) {
+                found = Some(val);
+                cur = left(node);
+            } else {
+                cur = right(node);
+            }
+        }
+        found
+    }
+}
+
+impl<P, T, S, L> Balance<L> for RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: RBLinkable<Container=T::Target>
+{
+    /// Nothing is kept about subtrees
+    #[inline]
+    fn update_to_root(_node: Option<&L>) {}
+}
+
+impl<P, T, S, L> Default for RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> RBTree<P, T, S, L> {
+        RBTree::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl<P, T, S, L> Drop for RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: RBLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        // unlink the leaves one at a time, without rebalancing
+        let mut cur = self.root.take().resolve();
+        while let Some(node) = cur {
+            if let Some(l) = left(node) {
+                cur = Some(l);
+                continue;
+            }
+            if let Some(r) = right(node) {
+                cur = Some(r);
+                continue;
+            }
+            cur = parent(node);
+            if let Some(p) = cur {
+                if same(left(p), Some(node)) {
+                    p.set_left(Rawlink::none());
+                } else {
+                    p.set_right(Rawlink::none());
+                }
+            }
+            node.set_parent(Rawlink::none());
+            // hand back the reference held by the tree
+            drop(unsafe {
+                T::from_raw(node.container_of() as *const S as *mut S)
+            });
+        }
+        self.length = 0;
+    }
+}
+
+impl<P, T, S, L> Extend<T> for RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.insert(elt); }
+    }
+}
+
+impl<P, T, S, L> FromIterator<T> for RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> RBTree<P, T, S, L> {
+        let mut ret = RBTree::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L> IntoIterator for RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L>;
+
+    /// Consumes the tree into an in-order iterator yielding elements by value.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L> {
+        IntoIter{tree: self}
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: RBLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+unsafe impl<P, T, S, L> Send for RBTree<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: RBLinkable<Container=T::Target>
+{}
+
+// RBLinks impls
+
+unsafe impl<L: RBLinkable> Send for RBLinks<L> {}
+
+impl<L: RBLinkable> Clone for RBLinks<L>
+{
+    /// A clone is not in any tree, even if the original is
+    fn clone(&self) -> RBLinks<L> {
+        Default::default()
+    }
+}
+
+impl<L: RBLinkable> Drop for RBLinks<L>
+{
+    fn drop(&mut self) {
+        assert!(self.parent.get().resolve().is_none());
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, L: RBLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            head: self.head,
+            tail: self.tail,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: RBLinkable<Container=T> + 'a> Iterator
+    for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve().unwrap();
+        self.nelem -= 1;
+        self.head = link_of(successor(head));
+        let ret = unsafe { head.container_of() }.get_val();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: RBLinkable<Container=T> + 'a>
+    DoubleEndedIterator for Iter<'a, P, T, L>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let tail = self.tail.resolve().unwrap();
+        self.nelem -= 1;
+        self.tail = link_of(predecessor(tail));
+        let ret = unsafe { tail.container_of() }.get_val();
+        Some(ret)
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: RBLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+// IntoIter impls
+
+impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.tree.pop_first() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.tree.length, Some(self.tree.length))
+    }
+}
+
+impl<P, T, S, L> DoubleEndedIterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: RBLinkable<Container=T::Target>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<T> { self.tree.pop_last() }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(test)]
+mod no_alloc_tests {
+    use std::vec::Vec;
+    use super::RBTree;
+
+    define_rbtree_element!(MyI32 = i32 : MyLink);
+
+    #[test]
+    fn test_stack_elements() {
+        let mut elts = [3, 1, 4, 1, 5, 9, 2, 6].map(MyI32::new);
+        let mut tree = RBTree::new();
+        for elt in elts.iter_mut() {
+            tree.insert(elt);
+        }
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(),
+                   [1, 1, 2, 3, 4, 5, 6, 9]);
+        assert_eq!(tree.find(&5), Some(&5));
+        assert_eq!(tree.lower_bound(&7), Some(&9));
+        assert_eq!(tree.pop_first().map(|elt| elt.0.val), Some(1));
+        assert_eq!(tree.pop_last().map(|elt| elt.0.val), Some(9));
+        assert_eq!(tree.len(), 6);
+    }
+}
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::vec::Vec;
+    use core::ptr;
+    use rand::{self, Rng};
+    use super::{RBTree, OwningPointer, Node, RBLinkable, RBLinkOps, Color};
+    use super::{left, right, parent};
+
+    define_rbtree_element!(MyI32 = i32 : MyLink);
+
+    type Tree = RBTree<i32, Box<MyI32>, MyI32, MyLink>;
+
+    /// Checks the parent links, ordering and red-black properties of the
+    /// subtree at `node`, returning its size and black height
+    fn check_node<P, S, L>(node: &L) -> (usize, usize)
+        where P: Ord,
+              S: Node<P, L>,
+              L: RBLinkable<Container=S>
+    {
+        let val = unsafe { node.container_of() }.get_val();
+        let mut size = 1;
+        let mut heights = [0; 2];
+        for (i, child) in [left(node), right(node)].iter().enumerate() {
+            if let Some(child) = *child {
+                assert!(ptr::eq(parent(child).unwrap(), node));
+                let child_val = unsafe { child.container_of() }.get_val();
+                if i == 0 {
+                    assert!(child_val <= val);
+                } else {
+                    assert!(child_val >= val);
+                }
+                if node.get_color() == Color::Red {
+                    assert_eq!(child.get_color(), Color::Black);
+                }
+                let (s, h) = check_node(child);
+                size += s;
+                heights[i] = h;
+            }
+        }
+        assert_eq!(heights[0], heights[1]);
+        let black = (node.get_color() == Color::Black) as usize;
+        (size, heights[0] + black)
+    }
+
+    pub fn check_links<P, T, S, L>(tree: &RBTree<P, T, S, L>)
+        where T: OwningPointer<Target=S>,
+              P: Ord,
+              S: Node<P, L>,
+              L: RBLinkable<Container=S>,
+    {
+        match tree.root.resolve() {
+            None => assert_eq!(tree.length, 0),
+            Some(root) => {
+                assert!(ptr::eq(root.get_parent().resolve().unwrap(), root));
+                assert_eq!(root.get_color(), Color::Black);
+                assert_eq!(check_node(root).0, tree.length);
+            }
+        }
+    }
+
+    fn tree_from(v: &[i32]) -> Tree {
+        let tree: Tree = v.iter().map(|&i| Box::new(MyI32::new(i))).collect();
+        check_links(&tree);
+        tree
+    }
+
+    fn to_vec(tree: &Tree) -> Vec<i32> {
+        tree.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut t = Tree::new();
+        assert!(t.is_empty());
+        assert_eq!(t.first(), None);
+        assert_eq!(t.pop_first(), None);
+        t.insert(Box::new(MyI32::new(2)));
+        t.insert(Box::new(MyI32::new(1)));
+        t.insert(Box::new(MyI32::new(3)));
+        check_links(&t);
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.first(), Some(&1));
+        assert_eq!(t.last(), Some(&3));
+        assert_eq!(t.pop_first(), Some(Box::new(MyI32::new(1))));
+        assert_eq!(t.pop_last(), Some(Box::new(MyI32::new(3))));
+        check_links(&t);
+        assert_eq!(to_vec(&t), [2]);
+        t.clear();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_iter() {
+        let t = tree_from(&[5, 3, 8, 1, 4, 7, 9, 2, 6]);
+        assert_eq!(to_vec(&t), [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(t.iter().rev().cloned().collect::<Vec<_>>(),
+                   [9, 8, 7, 6, 5, 4, 3, 2, 1]);
+        let mut it = t.iter();
+        assert_eq!(it.len(), 9);
+        assert_eq!(it.next(), Some(&1));
+        assert_eq!(it.next_back(), Some(&9));
+        assert_eq!(it.len(), 7);
+        assert_eq!(it.cloned().collect::<Vec<_>>(), [2, 3, 4, 5, 6, 7, 8]);
+        let v: Vec<_> = t.into_iter().rev().map(|e| *e.get_val()).collect();
+        assert_eq!(v, [9, 8, 7, 6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_find() {
+        let t = tree_from(&[10, 20, 20, 30]);
+        assert_eq!(t.find(&20), Some(&20));
+        assert_eq!(t.find(&25), None);
+        assert_eq!(t.lower_bound(&5), Some(&10));
+        assert_eq!(t.lower_bound(&20), Some(&20));
+        assert_eq!(t.upper_bound(&20), Some(&30));
+        assert_eq!(t.lower_bound(&31), None);
+        assert_eq!(t.upper_bound(&30), None);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let elts: Vec<_> = (0..6).map(|i| Rc::new(MyI32::new(i % 2))).collect();
+        let t: RBTree<_, _, _, MyLink> = elts.iter().cloned().collect();
+        check_links(&t);
+        // equal elements are kept in insertion order
+        let ptrs: Vec<*const i32> = t.iter().map(|v| v as *const i32).collect();
+        let expect: Vec<*const i32> = [0, 2, 4, 1, 3, 5].iter()
+            .map(|&i| elts[i].get_val() as *const i32).collect();
+        assert_eq!(ptrs, expect);
+        drop(t);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut elts: Vec<*const MyI32> = Vec::new();
+        let mut t = Tree::new();
+        for i in 0..32 {
+            let e = Box::new(MyI32::new(i));
+            elts.push(&*e);
+            t.insert(e);
+        }
+        for i in (0..32).step_by(3) {
+            let e = unsafe { t.remove(&*elts[i]) };
+            assert_eq!(*e.get_val(), i as i32);
+            check_links(&t);
+        }
+        assert_eq!(t.len(), 21);
+        assert!(t.iter().all(|&v| v % 3 != 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_unlinked() {
+        let mut t = Tree::new();
+        t.insert(Box::new(MyI32::new(1)));
+        let e = MyI32::new(2);
+        unsafe { t.remove(&e) };
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut t = Tree::new();
+            let mut v: Vec<i32> = Vec::new();
+            let mut elts: Vec<*const MyI32> = Vec::new();
+            for _ in 0..200 {
+                match rng.gen_range(0..4) {
+                    0 if !elts.is_empty() => {
+                        let i = rng.gen_range(0..elts.len());
+                        let e = unsafe { t.remove(&*elts.swap_remove(i)) };
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    _ => {
+                        let val = rng.gen_range(0..50);
+                        let e = Box::new(MyI32::new(val));
+                        elts.push(&*e);
+                        t.insert(e);
+                        v.push(val);
+                    }
+                }
+                check_links(&t);
+            }
+            v.sort();
+            assert_eq!(to_vec(&t), v);
+        }
+    }
+}
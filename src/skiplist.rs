@@ -0,0 +1,1228 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive skip list.
+//!
+//! The `SkipList` keeps its elements sorted by value. Each link holds an
+//! array of forward pointers whose size, the maximum level, is a const
+//! parameter of the link type. The number of levels an element takes part
+//! in is drawn from a `LevelGenerator` when it is inserted, which makes
+//! lookup, insertion and removal take O(log n) expected time. Elements with
+//! equal values are allowed.
+use core::cell::Cell;
+use core::cmp::Ordering;
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ops::Bound;
+use core::ptr;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+
+/// The maximum level of links declared without an explicit one
+pub const DEFAULT_LEVELS: usize = 16;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into a `SkipList`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_skiplist_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: SkipLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into a `SkipList`
+///
+/// An element is in a list exactly when its height, the number of levels it
+/// takes part in, is not zero.
+///
+/// # Safety
+///
+/// The trait is unsafe because the list relies on the links to find its
+/// elements, and to hand them back when it is dropped: `get_next` and
+/// `get_height` must return what `set_next` and `set_height` last stored, for
+/// any level below `LEVELS`, and the links must panic rather than be dropped
+/// with a height other than zero, as `SkipLinks` does. `offset()` must be the
+/// offset of the link within its `Container`.
+pub unsafe trait SkipLinkable : Default + Sized
+{
+    type Container;
+
+    /// The maximum level of the links, at least 1
+    const LEVELS: usize;
+
+    fn get_next(&self, level: usize) -> Link<Self>;
+    /// Sets the forward pointer at `level`
+    ///
+    /// # Safety
+    ///
+    /// Only the list the element is in may change its links.
+    unsafe fn set_next(&self, level: usize, next: Link<Self>);
+    fn get_height(&self) -> usize;
+    /// Sets the number of levels the element takes part in
+    ///
+    /// # Safety
+    ///
+    /// Only the list the element is in may change its links.
+    unsafe fn set_height(&self, height: usize);
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+    fn check_links(&self) {
+        assert_eq!(self.get_height(), 0);
+        for level in 0..Self::LEVELS {
+            assert!(self.get_next(level).0.resolve().is_none());
+        }
+    }
+}
+
+/// Chooses the number of levels of each element inserted into a `SkipList`
+///
+/// Closures returning a level implement this trait, which makes it easy to
+/// build deterministic lists in tests.
+pub trait LevelGenerator {
+    /// Returns a level between 1 and `max`, both included. Levels outside of
+    /// this range are clamped into it.
+    fn level(&mut self, max: usize) -> usize;
+}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into a `SkipList`
+///
+/// `define_skiplist_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it in a list. The
+/// list is ordered by the `i32`. The maximum level of the link defaults to
+/// `DEFAULT_LEVELS` and may be given in brackets, as in
+/// `define_skiplist_element!(MyI32 = i32 : MyLink[8])`.
+#[macro_export]
+macro_rules! define_skiplist_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        define_skiplist_element!($elt = $container
+                                 : $link[$crate::skiplist::DEFAULT_LEVELS]);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        define_skiplist_element!(pub $elt = $container
+                                 : $link[$crate::skiplist::DEFAULT_LEVELS]);
+    );
+    ($elt:ident = $container:ty : $link:ident [$levels:expr]) => (
+        declare_skiplist_link!($link[$levels]);
+        declare_list_elt!($elt = $container : $link);
+        impl_skiplist_link!($link[$levels] = $elt);
+        impl_skiplist_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident [$levels:expr]) => (
+        declare_skiplist_link!(pub $link[$levels]);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_skiplist_link!($link[$levels] = $elt);
+        impl_skiplist_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_skiplist_link {
+    ($link:ident [$levels:expr]) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::skiplist::SkipLinks<$link, {$levels}>);
+    );
+    (pub $link:ident [$levels:expr]) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::skiplist::SkipLinks<$link, {$levels}>);
+    );
+}
+
+/// Implements `SkipLinkable` for `$link`, which has `$levels` levels and is
+/// found at the field path `$field` (`link` by default) of the `NodeImpl`
+/// wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_skiplist_link {
+    ($link:ident [$levels:expr] = $elt:ident) => (
+        impl_skiplist_link!($link[$levels] = $elt : link);
+    );
+    ($link:ident [$levels:expr] = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::skiplist::SkipLinkable for $link {
+            type Container = $elt;
+
+            const LEVELS: usize = $levels;
+
+            #[inline]
+            fn get_next(&self, level: usize) -> $crate::skiplist::Link<$link> {
+                self.0.get_next(level)
+            }
+
+            #[inline]
+            unsafe fn set_next(&self, level: usize,
+                               next: $crate::skiplist::Link<$link>) {
+                unsafe { self.0.set_next(level, next) }
+            }
+
+            #[inline]
+            fn get_height(&self) -> usize {
+                self.0.get_height()
+            }
+
+            #[inline]
+            unsafe fn set_height(&self, height: usize) {
+                unsafe { self.0.set_height(height) }
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_skiplist_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_skiplist_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::skiplist::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// A forward pointer of a `SkipLinks`, which only the list its element is in
+/// can follow
+///
+/// Used by `impl_skiplist_link` to name the forward pointers.
+#[doc(hidden)]
+pub struct Link<L>(Rawlink<L>);
+
+/// An intrusive skip list
+///
+/// The list owns a set of head links, of the same type as those of its
+/// elements, from which every level starts.
+pub struct SkipList<P, T, S, L, G = XorShiftLevels>
+    where T: OwningPointer<Target=S>,
+          L: SkipLinkable<Container=T::Target>
+{
+    length: usize,
+    head: L,
+    generator: G,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+#[derive(Debug)]
+pub struct SkipLinks<L, const N: usize>
+{
+    next: [Cell<Rawlink<L>>; N],
+    height: Cell<usize>,
+    _pin: PhantomPinned
+}
+
+/// A `LevelGenerator` drawing levels from a xorshift pseudo-random number
+/// generator, each level being half as likely as the one below
+#[derive(Clone, Debug)]
+pub struct XorShiftLevels {
+    state: u64
+}
+
+/// An iterator over references to the items of a `SkipList`, in order
+pub struct Iter<'a, P: 'a, T, L: SkipLinkable<Container=T>> {
+    head: Rawlink<L>,
+    nelem: usize,
+    _marker: PhantomData<&'a P>
+}
+
+/// An iterator over references to the items of a `SkipList` within a range
+pub struct Range<'a, P: 'a, T, L: SkipLinkable<Container=T>> {
+    head: Rawlink<L>,
+    end: Rawlink<L>,
+    _marker: PhantomData<&'a P>
+}
+
+/// An iterator yielding the elements of a `SkipList` by value, in order
+pub struct IntoIter<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          L: SkipLinkable<Container=T::Target>
+{
+    list: SkipList<P, T, S, L, G>
+}
+
+////////////////
+// Navigation //
+////////////////
+
+fn next<L: SkipLinkable>(node: &L, level: usize) -> Option<&L> {
+    node.get_next(level).0.resolve()
+}
+
+fn set_next<L: SkipLinkable>(node: &L, level: usize, next: Option<&L>) {
+    unsafe { node.set_next(level, Link(link_of(next))) }
+}
+
+fn set_height<L: SkipLinkable>(node: &L, height: usize) {
+    unsafe { node.set_height(height) }
+}
+
+fn link_of<L: SkipLinkable>(node: Option<&L>) -> Rawlink<L> {
+    node.map_or(Rawlink::none(), Rawlink::some)
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// SkipList impls
+
+impl<P, T, S, L> SkipList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>
+{
+    /// Creates an empty `SkipList` drawing levels from a default
+    /// `XorShiftLevels`
+    #[inline]
+    pub fn new() -> SkipList<P, T, S, L> {
+        SkipList::with_generator(XorShiftLevels::default())
+    }
+}
+
+impl<P, T, S, L, G> SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>
+{
+    /// Creates an empty `SkipList` drawing levels from `generator`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the links have no level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SkipList;
+    ///
+    /// define_skiplist_element!(MyI32 = i32 : MyLink[4]);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// // every element takes part in two levels
+    /// let mut l = SkipList::with_generator(|| 2);
+    /// l.insert(Box::new(MyI32::new(2)));
+    /// l.insert(Box::new(MyI32::new(1)));
+    /// assert_eq!(l.iter().cloned().collect::<Vec<_>>(), [1, 2]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    #[inline]
+    pub fn with_generator(generator: G) -> SkipList<P, T, S, L, G> {
+        assert!(L::LEVELS > 0, "Skip list links need at least one level");
+        SkipList { length: 0, head: L::default(), generator,
+                   _marker: PhantomData, _marker2: PhantomData,
+                   _marker3: PhantomData}
+    }
+
+    /// Provides an iterator over the elements in order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        Iter{nelem: self.length, head: self.head.get_next(0).0,
+             _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `SkipList` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of elements in the `SkipList`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all elements from the `SkipList`.
+    ///
+    /// This operation should compute in O(n) time.
+    pub fn clear(&mut self) {
+        let mut cur = next(&self.head, 0);
+        for level in 0..L::LEVELS {
+            set_next(&self.head, level, None);
+        }
+        while let Some(node) = cur {
+            cur = next(node, 0);
+            for level in 0..node.get_height() {
+                set_next(node, level, None);
+            }
+            set_height(node, 0);
+            // hand back the reference held by the list
+            drop(unsafe {
+                T::from_raw(node.container_of() as *const S as *mut S)
+            });
+        }
+        self.length = 0;
+    }
+
+    /// Provides a reference to the smallest element, or `None` if the list is
+    /// empty.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn first(&self) -> Option<&P> {
+        next(&self.head, 0).map(|node| {
+            unsafe { node.container_of() }.get_val()
+        })
+    }
+
+    /// Provides a reference to the largest element, or `None` if the list is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    pub fn last(&self) -> Option<&P> {
+        let mut cur = &self.head;
+        for level in (0..L::LEVELS).rev() {
+            while let Some(n) = next(cur, level) {
+                cur = n;
+            }
+        }
+        if ptr::eq(cur, &self.head) {
+            None
+        } else {
+            Some(unsafe { cur.container_of() }.get_val())
+        }
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the list is
+    /// empty.
+    ///
+    /// This operation should compute in O(1) expected time.
+    pub fn pop_first(&mut self) -> Option<T> {
+        next(&self.head, 0).map(|node| {
+            for level in 0..node.get_height() {
+                set_next(&self.head, level, next(node, level));
+                set_next(node, level, None);
+            }
+            set_height(node, 0);
+            self.length -= 1;
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Returns the first node for which `pred` is false, `pred` being true
+    /// for the elements of some prefix of the list
+    fn search<F>(&self, pred: F) -> Option<&L>
+        where F: Fn(&P) -> bool
+    {
+        let mut cur = &self.head;
+        for level in (0..L::LEVELS).rev() {
+            while let Some(n) = next(cur, level) {
+                if !pred(unsafe { n.container_of() }.get_val()) {
+                    break;
+                }
+                cur = n;
+            }
+        }
+        next(cur, 0)
+    }
+}
+
+impl<P, T, S, L, G> SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>,
+          G: LevelGenerator
+{
+    /// Inserts an element into the list.
+    ///
+    /// An element equal to some already in the list is placed after them.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SkipList;
+    ///
+    /// define_skiplist_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut l = SkipList::new();
+    /// l.insert(Box::new(MyI32::new(3)));
+    /// l.insert(Box::new(MyI32::new(1)));
+    /// l.insert(Box::new(MyI32::new(2)));
+    /// assert_eq!(l.iter().cloned().collect::<Vec<_>>(), [1, 2, 3]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn insert(&mut self, elt: T) {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        let height = self.generator.level(L::LEVELS).clamp(1, L::LEVELS);
+        let val = elt.get_val();
+        let links = elt.get_links();
+        set_height(links, height);
+        let mut cur = &self.head;
+        for level in (0..L::LEVELS).rev() {
+            while let Some(n) = next(cur, level) {
+                if unsafe { n.container_of() }.get_val() > val {
+                    break;
+                }
+                cur = n;
+            }
+            if level < height {
+                set_next(links, level, next(cur, level));
+                set_next(cur, level, Some(links));
+            }
+        }
+        self.length += 1;
+        unsafe { elt.take() };
+    }
+
+    /// Unlinks `elt` from the list and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in this list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SkipList;
+    ///
+    /// define_skiplist_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut l = SkipList::new();
+    /// let two = Box::new(MyI32::new(2));
+    /// let ptr: *const MyI32 = &*two;
+    /// l.insert(Box::new(MyI32::new(1)));
+    /// l.insert(two);
+    /// l.insert(Box::new(MyI32::new(3)));
+    ///
+    /// assert_eq!(l.remove(unsafe { &*ptr }), Box::new(MyI32::new(2)));
+    /// assert_eq!(l.iter().cloned().collect::<Vec<_>>(), [1, 3]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links();
+        let height = links.get_height();
+        assert!(height > 0, "Cannot remove an element which is not in a list");
+        let val = elt.get_val();
+        let mut cur = &self.head;
+        for level in (0..L::LEVELS).rev() {
+            // above its height, stop before every element equal to `elt`
+            // so as not to skip over it
+            while let Some(n) = next(cur, level) {
+                if ptr::eq(n, links) {
+                    break;
+                }
+                match unsafe { n.container_of() }.get_val().cmp(val) {
+                    Ordering::Less => {}
+                    Ordering::Equal if level < height => {}
+                    _ => break,
+                }
+                cur = n;
+            }
+            // the highest level is checked before any link is changed
+            if level < height {
+                assert!(next(cur, level).is_some_and(|n| ptr::eq(n, links)),
+                        "Cannot remove an element which is not in this list");
+                set_next(cur, level, next(links, level));
+                set_next(links, level, None);
+            }
+        }
+        set_height(links, 0);
+        self.length -= 1;
+        unsafe { T::from_raw(links.container_of() as *const S as *mut S) }
+    }
+
+    /// Provides a reference to the first element equal to `key`, or `None`
+    /// if there is none.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    pub fn find(&self, key: &P) -> Option<&P> {
+        self.search(|val| val < key)
+            .map(|node| unsafe { node.container_of() }.get_val())
+            .filter(|&val| val == key)
+    }
+
+    /// Provides an iterator over the elements within `start` and `end`, in
+    /// order.
+    ///
+    /// This operation should compute in O(log n) expected time, plus time
+    /// proportional to the number of elements iterated over.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use std::ops::Bound::{Excluded, Included, Unbounded};
+    /// use intrusive_containers::SkipList;
+    ///
+    /// define_skiplist_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let l: SkipList<_, _, _, MyLink> =
+    ///     (0..8).map(|i| Box::new(MyI32::new(i))).collect();
+    /// let r: Vec<_> = l.range(Included(&2), Excluded(&5)).cloned().collect();
+    /// assert_eq!(r, [2, 3, 4]);
+    /// let r: Vec<_> = l.range(Excluded(&5), Unbounded).cloned().collect();
+    /// assert_eq!(r, [6, 7]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn range(&self, start: Bound<&P>, end: Bound<&P>) -> Range<'_, P, S, L> {
+        let head = match start {
+            Bound::Included(k) => self.search(|val| val < k),
+            Bound::Excluded(k) => self.search(|val| val <= k),
+            Bound::Unbounded => next(&self.head, 0),
+        };
+        let end = match end {
+            Bound::Included(k) => self.search(|val| val <= k),
+            Bound::Excluded(k) => self.search(|val| val < k),
+            Bound::Unbounded => None,
+        };
+        // an empty range may end before it starts
+        let empty = match (head, end) {
+            (Some(h), Some(e)) => unsafe {
+                h.container_of().get_val() > e.container_of().get_val()
+            },
+            _ => false,
+        };
+        if empty {
+            Range{head: Rawlink::none(), end: Rawlink::none(),
+                  _marker: PhantomData}
+        } else {
+            Range{head: link_of(head), end: link_of(end),
+                  _marker: PhantomData}
+        }
+    }
+}
+
+impl<P, T, S, L, G> Default for SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>,
+          G: Default
+{
+    #[inline]
+    fn default() -> SkipList<P, T, S, L, G> {
+        SkipList::with_generator(G::default())
+    }
+}
+
+impl<P, T, S, L, G> fmt::Debug for SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+impl<P, T, S, L, G> Drop for SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          L: SkipLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        let mut cur = next(&self.head, 0);
+        while let Some(node) = cur {
+            cur = next(node, 0);
+            for level in 0..node.get_height() {
+                set_next(node, level, None);
+            }
+            set_height(node, 0);
+            // hand back the reference held by the list
+            drop(unsafe {
+                T::from_raw(node.container_of() as *const S as *mut S)
+            });
+        }
+        self.length = 0;
+    }
+}
+
+impl<P, T, S, L, G> Extend<T> for SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>,
+          G: LevelGenerator
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.insert(elt); }
+    }
+}
+
+impl<P, T, S, L, G> FromIterator<T> for SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>,
+          G: LevelGenerator + Default
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> SkipList<P, T, S, L, G> {
+        let mut ret = SkipList::default();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L, G> IntoIterator for SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L, G>;
+
+    /// Consumes the list into an iterator yielding elements by value, in
+    /// order.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L, G> {
+        IntoIter{list: self}
+    }
+}
+
+impl<'a, P, T, S, L, G> IntoIterator for &'a SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SkipLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+unsafe impl<P, T, S, L, G> Send for SkipList<P, T, S, L, G>
+    where T: OwningPointer<Target=S> + Send,
+          L: SkipLinkable<Container=T::Target>,
+          G: Send
+{}
+
+// SkipLinks impls
+
+unsafe impl<L, const N: usize> Send for SkipLinks<L, N> {}
+
+impl<L, const N: usize> SkipLinks<L, N> {
+    #[inline]
+    pub fn get_next(&self, level: usize) -> Link<L> {
+        Link(self.next[level].get())
+    }
+
+    /// Sets the forward pointer at `level`
+    ///
+    /// # Safety
+    ///
+    /// Only the list the element is in may change its links.
+    #[inline]
+    pub unsafe fn set_next(&self, level: usize, next: Link<L>) {
+        self.next[level].set(next.0)
+    }
+
+    #[inline]
+    pub fn get_height(&self) -> usize {
+        self.height.get()
+    }
+
+    /// Sets the number of levels the element takes part in
+    ///
+    /// # Safety
+    ///
+    /// Only the list the element is in may change its links.
+    #[inline]
+    pub unsafe fn set_height(&self, height: usize) {
+        self.height.set(height)
+    }
+}
+
+impl<L, const N: usize> Default for SkipLinks<L, N>
+{
+    fn default() -> SkipLinks<L, N> {
+        SkipLinks {
+            next: core::array::from_fn(|_| Cell::new(Rawlink::none())),
+            height: Cell::new(0),
+            _pin: PhantomPinned
+        }
+    }
+}
+
+impl<L, const N: usize> Clone for SkipLinks<L, N>
+{
+    /// A clone is not in any list, even if the original is
+    fn clone(&self) -> SkipLinks<L, N> {
+        Default::default()
+    }
+}
+
+impl<L, const N: usize> Drop for SkipLinks<L, N>
+{
+    fn drop(&mut self) {
+        assert_eq!(self.height.get(), 0);
+    }
+}
+
+// Link impls
+
+impl<L> Clone for Link<L> {
+    #[inline]
+    fn clone(&self) -> Link<L> {
+        *self
+    }
+}
+
+impl<L> Copy for Link<L> {}
+
+// XorShiftLevels impls
+
+impl XorShiftLevels {
+    /// Creates a generator from `seed`. A zero seed is replaced by a fixed
+    /// non-zero one.
+    pub fn new(seed: u64) -> XorShiftLevels {
+        XorShiftLevels {
+            state: if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed }
+        }
+    }
+}
+
+impl Default for XorShiftLevels {
+    fn default() -> XorShiftLevels {
+        XorShiftLevels::new(0)
+    }
+}
+
+impl LevelGenerator for XorShiftLevels {
+    fn level(&mut self, max: usize) -> usize {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        (x.trailing_ones() as usize + 1).min(max)
+    }
+}
+
+impl<F: FnMut() -> usize> LevelGenerator for F {
+    #[inline]
+    fn level(&mut self, max: usize) -> usize {
+        self().clamp(1, max)
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, L: SkipLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            head: self.head,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SkipLinkable<Container=T> + 'a> Iterator
+    for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve().unwrap();
+        self.nelem -= 1;
+        self.head = head.get_next(0).0;
+        let ret = unsafe { head.container_of() }.get_val();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SkipLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+// Range impls
+
+impl<'a, P, T, L: SkipLinkable<Container=T>> Clone for Range<'a, P, T, L> {
+    fn clone(&self) -> Range<'a, P, T, L> {
+        Range {
+            head: self.head,
+            end: self.end,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SkipLinkable<Container=T> + 'a> Iterator
+    for Range<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.head == self.end {
+            return None;
+        }
+        let head = self.head.resolve()?;
+        self.head = head.get_next(0).0;
+        let ret = unsafe { head.container_of() }.get_val();
+        Some(ret)
+    }
+}
+
+// IntoIter impls
+
+impl<P, T, S, L, G> Iterator for IntoIter<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SkipLinkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.list.pop_first() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.length, Some(self.list.length))
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::thread;
+    use std::vec::Vec;
+    use core::ops::Bound::{Excluded, Included, Unbounded};
+    use rand::{self, Rng};
+    use super::{SkipList, OwningPointer, Node, SkipLinkable, LevelGenerator};
+    use super::next;
+
+    define_skiplist_element!(MyI32 = i32 : MyLink[6]);
+
+    type List = SkipList<i32, Box<MyI32>, MyI32, MyLink>;
+
+    /// Checks that every level is sorted and a sublist of the level below
+    pub fn check_links<P, T, S, L, G>(list: &SkipList<P, T, S, L, G>)
+        where T: OwningPointer<Target=S>,
+              P: Ord,
+              S: Node<P, L>,
+              L: SkipLinkable<Container=S>,
+    {
+        assert_eq!(list.head.get_height(), 0);
+        let mut below: Vec<*const L> = Vec::new();
+        for level in 0..L::LEVELS {
+            let mut nodes: Vec<*const L> = Vec::new();
+            let mut cur = next(&list.head, level);
+            while let Some(n) = cur {
+                assert!(n.get_height() > level);
+                if let Some(m) = next(n, level) {
+                    assert!(unsafe { n.container_of() }.get_val() <=
+                            unsafe { m.container_of() }.get_val());
+                }
+                nodes.push(n);
+                cur = next(n, level);
+            }
+            if level == 0 {
+                assert_eq!(nodes.len(), list.length);
+            } else {
+                let mut it = below.iter();
+                assert!(nodes.iter().all(|n| it.any(|m| m == n)));
+                let expected = below.iter()
+                    .filter(|&&n| unsafe { &*n }.get_height() > level)
+                    .count();
+                assert_eq!(nodes.len(), expected);
+            }
+            below = nodes;
+        }
+    }
+
+    fn list_from(v: &[i32]) -> List {
+        let list: List = v.iter().map(|&i| Box::new(MyI32::new(i))).collect();
+        check_links(&list);
+        list
+    }
+
+    fn to_vec(list: &List) -> Vec<i32> {
+        list.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut l = List::new();
+        assert!(l.is_empty());
+        assert_eq!(l.first(), None);
+        assert_eq!(l.last(), None);
+        assert_eq!(l.pop_first(), None);
+        l.insert(Box::new(MyI32::new(2)));
+        l.insert(Box::new(MyI32::new(1)));
+        l.insert(Box::new(MyI32::new(3)));
+        check_links(&l);
+        assert_eq!(l.len(), 3);
+        assert_eq!(l.first(), Some(&1));
+        assert_eq!(l.last(), Some(&3));
+        assert_eq!(l.find(&2), Some(&2));
+        assert_eq!(l.find(&4), None);
+        assert_eq!(l.pop_first(), Some(Box::new(MyI32::new(1))));
+        check_links(&l);
+        assert_eq!(to_vec(&l), [2, 3]);
+        l.clear();
+        check_links(&l);
+        assert!(l.is_empty());
+    }
+
+    #[test]
+    fn test_send() {
+        let mut l = List::new();
+        l.insert(Box::new(MyI32::new(2)));
+        l.insert(Box::new(MyI32::new(1)));
+        thread::spawn(move || {
+            check_links(&l);
+            assert_eq!(to_vec(&l), [1, 2]);
+        }).join().ok().unwrap();
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let mut levels = [1, 3, 2, 6, 1, 9, 0].iter().cycle().cloned();
+        let mut l = SkipList::with_generator(move || levels.next().unwrap());
+        for i in [5, 3, 8, 1, 4, 7, 9, 2, 6].iter() {
+            l.insert(Box::new(MyI32::new(*i)));
+        }
+        check_links(&l);
+        let heights: Vec<usize> = {
+            let mut v = Vec::new();
+            let mut cur = next(&l.head, 0);
+            while let Some(n) = cur {
+                v.push(n.get_height());
+                cur = next(n, 0);
+            }
+            v
+        };
+        // values 5, 3, 8, 1, 4, 7, 9, 2, 6 got levels 1, 3, 2, 6, 1, 6, 1, 1, 3
+        assert_eq!(heights, [6, 1, 3, 1, 1, 3, 6, 2, 1]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut l = List::new();
+        let elts: Vec<*const MyI32> = [2, 1, 2, 3, 2].iter().map(|&i| {
+            let e = Box::new(MyI32::new(i));
+            let ptr: *const MyI32 = &*e;
+            l.insert(e);
+            ptr
+        }).collect();
+        let e = l.remove(unsafe { &*elts[2] });
+        assert!(std::ptr::eq(&*e, elts[2]));
+        check_links(&l);
+        assert_eq!(to_vec(&l), [1, 2, 2, 3]);
+        let e = l.remove(unsafe { &*elts[4] });
+        assert!(std::ptr::eq(&*e, elts[4]));
+        check_links(&l);
+        assert_eq!(to_vec(&l), [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_foreign() {
+        let mut l = list_from(&[1, 2, 3]);
+        let mut m = List::new();
+        let other = Box::new(MyI32::new(2));
+        let ptr: *const MyI32 = &*other;
+        m.insert(other);
+        l.remove(unsafe { &*ptr });
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_unlinked() {
+        let mut l = list_from(&[1, 5, 9]);
+        l.remove(&MyI32::new(5));
+    }
+
+    #[test]
+    fn test_range() {
+        let l = list_from(&[5, 3, 8, 1, 4, 7, 9, 2, 6, 4]);
+        let range = |s, e| l.range(s, e).cloned().collect::<Vec<_>>();
+        assert_eq!(range(Included(&4), Included(&6)), [4, 4, 5, 6]);
+        assert_eq!(range(Excluded(&4), Excluded(&6)), [5]);
+        assert_eq!(range(Unbounded, Excluded(&3)), [1, 2]);
+        assert_eq!(range(Excluded(&7), Unbounded), [8, 9]);
+        assert_eq!(range(Included(&10), Unbounded), []);
+        assert_eq!(range(Included(&6), Excluded(&2)), []);
+        assert_eq!(range(Unbounded, Unbounded).len(), 10);
+    }
+
+    #[test]
+    fn test_rc() {
+        let elts: Vec<_> = (0..6).map(|i| Rc::new(MyI32::new(i))).collect();
+        let mut l: SkipList<_, _, _, MyLink> = elts.iter().cloned().collect();
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        l.remove(&elts[3]);
+        assert_eq!(Rc::strong_count(&elts[3]), 1);
+        drop(l);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_fuzz() {
+        struct Random(rand::rngs::ThreadRng);
+
+        impl LevelGenerator for Random {
+            fn level(&mut self, max: usize) -> usize {
+                self.0.gen_range(1..=max)
+            }
+        }
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut l = SkipList::with_generator(Random(rand::thread_rng()));
+            let mut v: Vec<i32> = Vec::new();
+            let mut elts: Vec<*const MyI32> = Vec::new();
+            for _ in 0..200 {
+                match rng.gen_range(0..4) {
+                    0 if !elts.is_empty() => {
+                        let i = rng.gen_range(0..elts.len());
+                        let e: Box<MyI32> =
+                            l.remove(unsafe { &*elts.swap_remove(i) });
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    1 => {
+                        let key = rng.gen_range(0..50);
+                        assert_eq!(l.find(&key).is_some(), v.contains(&key));
+                    }
+                    _ => {
+                        let val = rng.gen_range(0..50);
+                        let e = Box::new(MyI32::new(val));
+                        elts.push(&*e);
+                        l.insert(e);
+                        v.push(val);
+                    }
+                }
+                check_links(&l);
+            }
+            v.sort();
+            assert_eq!(l.iter().cloned().collect::<Vec<_>>(), v);
+        }
+    }
+}
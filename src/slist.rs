@@ -0,0 +1,913 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive singly-linked list.
+//!
+//! The `SinglyLinkedList` allows elements to be pushed and popped at its front
+//! only, which makes it a stack. Its links hold a single pointer, saving a
+//! word per element compared to `LinkedList`.
+use core::cell::Cell;
+use core::fmt;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ops::DerefMut;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into a `SinglyLinkedList`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_slist_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: SLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into a `SinglyLinkedList`
+///
+/// The last element of a list links to itself, so that an element is on a
+/// list exactly when its link is set.
+///
+/// # Safety
+///
+/// The trait is unsafe because any implementation must impl Drop to call
+/// check_links(), and `offset()` must be the offset of the link within its
+/// `Container`.
+pub unsafe trait SLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &SLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut SLinks<Self>;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+    fn check_links(&self) {
+        assert!(self.get_next().resolve().is_none());
+    }
+}
+
+/// Accessors for the links of a `SLinkable`
+///
+/// They are private to the crate, so that the links of an element are only
+/// followed or changed by the list it is on.
+pub(crate) trait SListLinkOps : SLinkable
+{
+    fn get_next(&self) -> Rawlink<Self> {
+        self.get_links().next.get()
+    }
+    fn set_next(&self, next: Rawlink<Self>) {
+        self.get_links().next.set(next)
+    }
+}
+
+impl<L: SLinkable> SListLinkOps for L {}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into a `SinglyLinkedList`
+///
+/// `define_slist_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it on a list.
+#[macro_export]
+macro_rules! define_slist_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_slist_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_slist_link!($link = $elt);
+        impl_slist_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_slist_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_slist_link!($link = $elt);
+        impl_slist_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_slist_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::slist::SLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::slist::SLinks<$link>);
+    );
+}
+
+/// Implements `SLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_slist_link {
+    ($link:ident = $elt:ident) => (
+        impl_slist_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::slist::SLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::slist::SLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::slist::SLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_slist_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_slist_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::slist::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive singly-linked list
+pub struct SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SLinkable<Container=T::Target>
+{
+    length: usize,
+    head: Rawlink<L>,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+#[derive(Default, Debug)]
+pub struct SLinks<L: SLinkable>
+{
+    next: Cell<Rawlink<L>>,
+    _pin: PhantomPinned
+}
+
+/// An iterator over references to the items of a `SinglyLinkedList` or an
+/// `STailQ`
+pub struct Iter<'a, P: 'a, T, L: SLinkable<Container=T>> {
+    pub(crate) head: Rawlink<L>,
+    pub(crate) nelem: usize,
+    pub(crate) _marker: PhantomData<&'a P>
+}
+
+/// An iterator over mutable references to the items of a `SinglyLinkedList` or
+/// an `STailQ`
+pub struct IterMut<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{
+    pub(crate) head: Rawlink<L>,
+    pub(crate) nelem: usize,
+    pub(crate) _marker: PhantomData<&'a mut T>,
+    pub(crate) _marker2: PhantomData<&'a mut S>,
+    pub(crate) _marker3: PhantomData<&'a mut P>,
+}
+
+pub struct IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SLinkable<Container=T::Target>
+{
+    list: SinglyLinkedList<P, T, S, L>
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// SinglyLinkedList impls
+
+impl<P, T, S, L> SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    /// Creates an empty `SinglyLinkedList`
+    #[inline]
+    pub fn new() -> SinglyLinkedList<P, T, S, L> {
+        SinglyLinkedList { length: 0, head: Rawlink::none(),
+                           _marker: PhantomData, _marker2: PhantomData,
+                           _marker3: PhantomData}
+    }
+
+    /// Moves all elements from `other` to the end of the list.
+    ///
+    /// This reuses all the nodes from `other` and moves them into `self`. After
+    /// this operation, `other` becomes empty.
+    ///
+    /// This operation should compute in O(n) time, where n is the length of
+    /// `self`, since the list has to be walked to find its last element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SinglyLinkedList;
+    ///
+    /// define_slist_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut a = SinglyLinkedList::new();
+    /// let mut b = SinglyLinkedList::new();
+    /// a.push_front(Box::new(MyI32::new(2)));
+    /// a.push_front(Box::new(MyI32::new(1)));
+    /// b.push_front(Box::new(MyI32::new(4)));
+    /// b.push_front(Box::new(MyI32::new(3)));
+    ///
+    /// a.append(&mut b);
+    ///
+    /// assert_eq!(a.iter().cloned().collect::<Vec<_>>(), [1, 2, 3, 4]);
+    /// assert!(b.is_empty());
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn append(&mut self, other: &mut SinglyLinkedList<P, T, S, L>) {
+        match self.tail() {
+            None => {
+                self.head = other.head.take();
+            }
+            Some(tail) => {
+                if let Some(other_head) = other.head.take().resolve() {
+                    tail.set_next(Rawlink::some(other_head));
+                }
+            }
+        }
+        self.length += other.length;
+        other.length = 0;
+    }
+
+    /// Provides a forward iterator.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        Iter{nelem: self.length, head: self.head, _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `SinglyLinkedList` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the length of the `SinglyLinkedList`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all elements from the `SinglyLinkedList`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = SinglyLinkedList::new()
+    }
+
+    /// Provides a reference to the front element, or `None` if the list is
+    /// empty.
+    #[inline]
+    pub fn front(&self) -> Option<&P> {
+        self.head.resolve().map(|head| unsafe{head.container_of()}.get_val())
+    }
+
+    /// Provides a mutable reference to the front element, or `None` if the list
+    /// is empty.
+    #[inline]
+    pub fn front_mut(&mut self) -> Option<&mut P> where T: DerefMut {
+        self.head.resolve_mut().map(|head| {
+            unsafe {head.container_of_mut()}.get_val_mut()
+        })
+    }
+
+    /// Adds an element first in the list.
+    ///
+    /// This operation should compute in O(1) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SinglyLinkedList;
+    ///
+    /// define_slist_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut d = SinglyLinkedList::new();
+    /// assert_eq!(d.pop_front(), None);
+    ///
+    /// d.push_front(Box::new(MyI32::new(1)));
+    /// d.push_front(Box::new(MyI32::new(3)));
+    /// assert_eq!(d.pop_front(), Some(Box::new(MyI32::new(3))));
+    /// assert_eq!(d.pop_front(), Some(Box::new(MyI32::new(1))));
+    /// assert_eq!(d.pop_front(), None);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn push_front(&mut self, elt: T) {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        match self.head.resolve() {
+            None => elt.get_links().set_next(Rawlink::some(elt.get_links())),
+            Some(head) => elt.get_links().set_next(Rawlink::some(head)),
+        }
+        self.head = Rawlink::some(elt.get_links());
+        self.length += 1;
+        unsafe { elt.take() };
+    }
+
+    /// Removes the first element and returns it, or `None` if the list is
+    /// empty.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.head.take().resolve().map(|head| {
+            if self.length > 1 {
+                self.head = head.get_next();
+            }
+            head.set_next(Rawlink::none());
+            self.length -= 1;
+            unsafe {
+                T::from_raw(head.container_of() as *const S as *mut S)
+            }
+        })
+    }
+
+    /// Reverses the order of the elements in the list.
+    ///
+    /// This operation should compute in O(n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SinglyLinkedList;
+    ///
+    /// define_slist_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut d = SinglyLinkedList::new();
+    /// d.push_front(Box::new(MyI32::new(3)));
+    /// d.push_front(Box::new(MyI32::new(2)));
+    /// d.push_front(Box::new(MyI32::new(1)));
+    ///
+    /// d.reverse();
+    /// assert_eq!(d.iter().cloned().collect::<Vec<_>>(), [3, 2, 1]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn reverse(&mut self) {
+        let mut prev: Option<&L> = None;
+        let mut cur = self.head;
+        for _ in 0..self.length {
+            let node = cur.resolve().unwrap();
+            cur = node.get_next();
+            // the old head becomes the last element, which links to itself
+            node.set_next(Rawlink::some(prev.unwrap_or(node)));
+            prev = Some(node);
+        }
+        self.head = prev.map_or(Rawlink::none(), Rawlink::some);
+    }
+
+    fn tail(&self) -> Option<&L> {
+        let mut cur = self.head.resolve()?;
+        for _ in 1..self.length {
+            cur = cur.get_next().resolve().unwrap();
+        }
+        Some(cur)
+    }
+}
+
+impl<'a, P, T, S, L> SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{
+    /// Provides a forward iterator with mutable references
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> IterMut<'a, P, T, S, L> {
+        IterMut {
+            nelem: self.length,
+            head: self.head,
+            _marker: PhantomData,
+            _marker2: PhantomData,
+            _marker3: PhantomData,
+        }
+    }
+}
+
+impl<P, T, S, L> Default for SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> SinglyLinkedList<P, T, S, L> {
+        SinglyLinkedList::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+impl<P, T, S, L> Drop for SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        while self.length > 0 {
+            let head = self.head.resolve().unwrap();
+            self.head = head.get_next();
+            head.set_next(Rawlink::none());
+            self.length -= 1;
+            // hand back the reference held by the list
+            drop(unsafe {
+                T::from_raw(head.container_of() as *const S as *mut S)
+            });
+        }
+    }
+}
+
+impl<P, T, S, L> IntoIterator for SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L>;
+
+    /// Consumes the list into an iterator yielding elements by value.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L> {
+        IntoIter{list: self}
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a mut SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a mut P;
+    type IntoIter = IterMut<'a, P, T, S, L>;
+
+    fn into_iter(self) -> IterMut<'a, P, T, S, L> {
+        self.iter_mut()
+    }
+}
+
+impl<P, T, S, L> PartialEq for SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: PartialEq,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    fn eq(&self, other: &SinglyLinkedList<P, T, S, L>) -> bool {
+        self.len() == other.len() &&
+            self.iter().eq(other.iter())
+    }
+}
+
+impl<P, T, S, L> Eq for SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Eq,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{}
+
+unsafe impl<P, T, S, L> Send for SinglyLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: SLinkable<Container=T::Target>
+{}
+
+// SLinks impls
+
+unsafe impl<L: SLinkable> Send for SLinks<L> {}
+
+impl<L: SLinkable> Clone for SLinks<L>
+{
+    /// A clone is not on any list, even if the original is
+    fn clone(&self) -> SLinks<L> {
+        Default::default()
+    }
+}
+
+impl<L: SLinkable> Drop for SLinks<L>
+{
+    fn drop(&mut self) {
+        assert!(self.next.get().resolve().is_none());
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, L: SLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            head: self.head,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SLinkable<Container=T> + 'a> Iterator
+    for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve().unwrap();
+        self.nelem -= 1;
+        self.head = head.get_next();
+        let ret = unsafe { head.container_of() }.get_val();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+// IterMut impls
+
+impl<'a, P, T, S, L> Iterator for IterMut<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a mut P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a mut P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve_mut().unwrap();
+        self.nelem -= 1;
+        self.head = head.get_next();
+        let ret = unsafe { head.container_of_mut() }.get_val_mut();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P, T, S, L> ExactSizeIterator for IterMut<'a, P, T, S, L>
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{}
+
+// IntoIter impls
+
+impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.list.pop_front() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.length, Some(self.list.length))
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(test)]
+mod no_alloc_tests {
+    use std::vec::Vec;
+    use super::SinglyLinkedList;
+
+    define_slist_element!(MyI32 = i32 : MyLink);
+
+    #[test]
+    fn test_stack_elements() {
+        let mut elts = [MyI32::new(0), MyI32::new(1), MyI32::new(2)];
+        let mut list = SinglyLinkedList::new();
+        for elt in elts.iter_mut() {
+            list.push_front(elt);
+        }
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), [2, 1, 0]);
+        list.reverse();
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), [0, 1, 2]);
+        *list.front_mut().unwrap() = 7;
+        assert_eq!(list.pop_front().map(|elt| elt.0.val), Some(7));
+        assert_eq!(list.len(), 2);
+    }
+}
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::vec::Vec;
+    use core::ptr;
+    use super::{SinglyLinkedList, OwningPointer, Node, SLinkable, SListLinkOps};
+
+    define_slist_element!(MyI32 = i32 : MyLink);
+
+    type List = SinglyLinkedList<i32, Box<MyI32>, MyI32, MyLink>;
+
+    pub fn check_links<P, T, S, L>(list: &SinglyLinkedList<P, T, S, L>)
+        where T: OwningPointer<Target=S>,
+              S: Node<P, L>,
+              L: SLinkable<Container=S>,
+    {
+        let mut len = 0;
+        let mut cur = list.head;
+        while let Some(node) = cur.resolve() {
+            len += 1;
+            let next = node.get_next().resolve().expect("unset next link");
+            if ptr::eq(next, node) {
+                break;
+            }
+            assert!(len < list.length, "last element does not link to itself");
+            cur = node.get_next();
+        }
+        assert_eq!(len, list.length);
+    }
+
+    fn list_from(v: &[i32]) -> List {
+        let mut list = SinglyLinkedList::new();
+        for &i in v.iter().rev() {
+            list.push_front(Box::new(MyI32::new(i)));
+        }
+        check_links(&list);
+        list
+    }
+
+    fn to_vec(list: &List) -> Vec<i32> {
+        list.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut m = List::new();
+        assert_eq!(m.pop_front(), None);
+        assert_eq!(m.front(), None);
+        m.push_front(Box::new(MyI32::new(1)));
+        assert_eq!(m.pop_front(), Some(Box::new(MyI32::new(1))));
+        check_links(&m);
+        m.push_front(Box::new(MyI32::new(2)));
+        m.push_front(Box::new(MyI32::new(3)));
+        check_links(&m);
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.front(), Some(&3));
+        *m.front_mut().unwrap() = 4;
+        assert_eq!(m.pop_front(), Some(Box::new(MyI32::new(4))));
+        assert_eq!(m.pop_front(), Some(Box::new(MyI32::new(2))));
+        assert_eq!(m.pop_front(), None);
+        assert!(m.is_empty());
+        check_links(&m);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut m = list_from(&[0, 1, 2, 3]);
+        assert_eq!(m.iter().len(), 4);
+        assert_eq!(to_vec(&m), [0, 1, 2, 3]);
+        for e in m.iter_mut() {
+            *e *= 2;
+        }
+        assert_eq!(to_vec(&m), [0, 2, 4, 6]);
+        let v: Vec<_> = m.into_iter().map(|e| *e.get_val()).collect();
+        assert_eq!(v, [0, 2, 4, 6]);
+    }
+
+    #[test]
+    fn test_append() {
+        {
+            let mut m = List::new();
+            let mut n = List::new();
+            m.append(&mut n);
+            check_links(&m);
+            assert!(m.is_empty() && n.is_empty());
+        }
+        {
+            let mut m = List::new();
+            let mut n = list_from(&[1, 2]);
+            m.append(&mut n);
+            check_links(&m);
+            check_links(&n);
+            assert_eq!(to_vec(&m), [1, 2]);
+            assert!(n.is_empty());
+        }
+        {
+            let mut m = list_from(&[1, 2]);
+            let mut n = List::new();
+            m.append(&mut n);
+            check_links(&m);
+            assert_eq!(to_vec(&m), [1, 2]);
+        }
+        {
+            let mut m = list_from(&[1, 2, 3]);
+            let mut n = list_from(&[4, 5]);
+            m.append(&mut n);
+            check_links(&m);
+            check_links(&n);
+            assert_eq!(to_vec(&m), [1, 2, 3, 4, 5]);
+            assert_eq!(m.len(), 5);
+            assert!(n.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut m = List::new();
+        m.reverse();
+        check_links(&m);
+        assert!(m.is_empty());
+
+        let mut m = list_from(&[1]);
+        m.reverse();
+        check_links(&m);
+        assert_eq!(to_vec(&m), [1]);
+
+        let mut m = list_from(&[1, 2, 3, 4]);
+        m.reverse();
+        check_links(&m);
+        assert_eq!(to_vec(&m), [4, 3, 2, 1]);
+        m.push_front(Box::new(MyI32::new(5)));
+        m.reverse();
+        check_links(&m);
+        assert_eq!(to_vec(&m), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_rc() {
+        let elts: Vec<_> = (0..3).map(|i| Rc::new(MyI32::new(i))).collect();
+        let mut m = SinglyLinkedList::new();
+        for e in elts.iter() {
+            m.push_front(e.clone());
+        }
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), [2, 1, 0]);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        drop(m);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_linked() {
+        let a = Rc::new(MyI32::new(1));
+        let mut m = SinglyLinkedList::new();
+        let mut n = SinglyLinkedList::new();
+        m.push_front(a.clone());
+        n.push_front(a);
+    }
+}
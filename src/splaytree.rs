@@ -0,0 +1,1229 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive splay tree.
+//!
+//! The `SplayTree` keeps its elements sorted by value. Every element which is
+//! inserted or found is moved to the root, so that recently used elements are
+//! the fastest to find again. Lookup, insertion, removal, split and join take
+//! O(log n) amortized time. Elements with equal values are allowed.
+use core::borrow::Borrow;
+use core::cell::Cell;
+use core::cmp::Ordering;
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ptr;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into a `SplayTree`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_splaytree_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: SplayLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into a `SplayTree`
+///
+/// The root of a tree is its own parent, so that an element is in a tree
+/// exactly when its parent link is set.
+///
+/// # Safety
+///
+/// The trait is unsafe because any implementation must impl Drop to call
+/// check_links(), and `offset()` must be the offset of the link within its
+/// `Container`.
+pub unsafe trait SplayLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &SplayLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut SplayLinks<Self>;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+    fn check_links(&self) {
+        assert!(self.get_parent().resolve().is_none());
+        assert!(self.get_left().resolve().is_none());
+        assert!(self.get_right().resolve().is_none());
+    }
+}
+
+/// Accessors for the links of a `SplayLinkable`
+///
+/// They are private to the crate, so that the links of an element are only
+/// followed or changed by the tree it is in.
+pub(crate) trait SplayLinkOps : SplayLinkable
+{
+    fn get_parent(&self) -> Rawlink<Self> {
+        self.get_links().parent.get()
+    }
+    fn set_parent(&self, parent: Rawlink<Self>) {
+        self.get_links().parent.set(parent)
+    }
+    fn get_left(&self) -> Rawlink<Self> {
+        self.get_links().left.get()
+    }
+    fn set_left(&self, left: Rawlink<Self>) {
+        self.get_links().left.set(left)
+    }
+    fn get_right(&self) -> Rawlink<Self> {
+        self.get_links().right.get()
+    }
+    fn set_right(&self, right: Rawlink<Self>) {
+        self.get_links().right.set(right)
+    }
+}
+
+impl<L: SplayLinkable> SplayLinkOps for L {}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into a `SplayTree`
+///
+/// `define_splaytree_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it in a tree. The
+/// tree is ordered by the `i32`.
+#[macro_export]
+macro_rules! define_splaytree_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_splaytree_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_splaytree_link!($link = $elt);
+        impl_splaytree_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_splaytree_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_splaytree_link!($link = $elt);
+        impl_splaytree_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_splaytree_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::splaytree::SplayLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::splaytree::SplayLinks<$link>);
+    );
+}
+
+/// Implements `SplayLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_splaytree_link {
+    ($link:ident = $elt:ident) => (
+        impl_splaytree_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::splaytree::SplayLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::splaytree::SplayLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::splaytree::SplayLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_splaytree_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_splaytree_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::splaytree::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive splay tree
+pub struct SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SplayLinkable<Container=T::Target>
+{
+    length: usize,
+    root: Rawlink<L>,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+#[derive(Default, Debug)]
+pub struct SplayLinks<L: SplayLinkable>
+{
+    parent: Cell<Rawlink<L>>,
+    left: Cell<Rawlink<L>>,
+    right: Cell<Rawlink<L>>,
+    _pin: PhantomPinned
+}
+
+/// An in-order iterator over references to the items of a `SplayTree`
+pub struct Iter<'a, P: 'a, T, L: SplayLinkable<Container=T>> {
+    head: Rawlink<L>,
+    tail: Rawlink<L>,
+    nelem: usize,
+    _marker: PhantomData<&'a P>
+}
+
+pub struct IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SplayLinkable<Container=T::Target>
+{
+    tree: SplayTree<P, T, S, L>
+}
+
+//////////////////////
+// Tree Navigation  //
+//////////////////////
+
+fn parent<L: SplayLinkable>(node: &L) -> Option<&L> {
+    node.get_parent().resolve().filter(|&p| !ptr::eq(p, node))
+}
+
+fn left<L: SplayLinkable>(node: &L) -> Option<&L> {
+    node.get_left().resolve()
+}
+
+fn right<L: SplayLinkable>(node: &L) -> Option<&L> {
+    node.get_right().resolve()
+}
+
+fn same<L>(a: Option<&L>, b: Option<&L>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => ptr::eq(a, b),
+        (None, None) => true,
+        _ => false
+    }
+}
+
+fn minimum<L: SplayLinkable>(mut node: &L) -> &L {
+    while let Some(l) = left(node) {
+        node = l;
+    }
+    node
+}
+
+fn maximum<L: SplayLinkable>(mut node: &L) -> &L {
+    while let Some(r) = right(node) {
+        node = r;
+    }
+    node
+}
+
+fn successor<L: SplayLinkable>(node: &L) -> Option<&L> {
+    if let Some(r) = right(node) {
+        return Some(minimum(r));
+    }
+    let mut node = node;
+    while let Some(p) = parent(node) {
+        if same(left(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+fn predecessor<L: SplayLinkable>(node: &L) -> Option<&L> {
+    if let Some(l) = left(node) {
+        return Some(maximum(l));
+    }
+    let mut node = node;
+    while let Some(p) = parent(node) {
+        if same(right(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+fn link_of<L: SplayLinkable>(node: Option<&L>) -> Rawlink<L> {
+    node.map_or(Rawlink::none(), Rawlink::some)
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// SplayTree impls
+
+impl<P, T, S, L> SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    /// Creates an empty `SplayTree`
+    #[inline]
+    pub fn new() -> SplayTree<P, T, S, L> {
+        SplayTree { length: 0, root: Rawlink::none(),
+                 _marker: PhantomData, _marker2: PhantomData,
+                 _marker3: PhantomData}
+    }
+
+    /// Provides a double-ended iterator over the elements in order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        let root = self.root.resolve();
+        Iter{nelem: self.length,
+             head: link_of(root.map(minimum)),
+             tail: link_of(root.map(maximum)),
+             _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `SplayTree` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of elements in the `SplayTree`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all elements from the `SplayTree`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = SplayTree::new()
+    }
+
+    /// Provides a reference to the smallest element, or `None` if the tree is
+    /// empty.
+    ///
+    /// The tree is not splayed, so this operation takes time proportional to
+    /// the depth of the smallest element.
+    #[inline]
+    pub fn first(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { minimum(root).container_of() }.get_val()
+        })
+    }
+
+    /// Provides a reference to the largest element, or `None` if the tree is
+    /// empty.
+    ///
+    /// The tree is not splayed, so this operation takes time proportional to
+    /// the depth of the largest element.
+    #[inline]
+    pub fn last(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { maximum(root).container_of() }.get_val()
+        })
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) amortized time.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let node = minimum(root);
+            self.delete(node);
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Removes the largest element and returns it, or `None` if the tree is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) amortized time.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| {
+            let node = maximum(root);
+            self.delete(node);
+            unsafe { T::from_raw(node.container_of() as *const S as *mut S) }
+        })
+    }
+
+    /// Unlinks `elt` from the tree and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) amortized time. In debug
+    /// builds the parent links are followed to check that `elt` belongs to
+    /// this tree.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this tree. Removing an element of another tree would leave
+    /// that tree with an incorrect length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SplayTree;
+    ///
+    /// define_splaytree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t = SplayTree::new();
+    /// let two = Box::new(MyI32::new(2));
+    /// let ptr: *const MyI32 = &*two;
+    /// t.insert(Box::new(MyI32::new(1)));
+    /// t.insert(two);
+    /// t.insert(Box::new(MyI32::new(3)));
+    ///
+    /// assert_eq!(unsafe { t.remove(&*ptr) }, Box::new(MyI32::new(2)));
+    /// assert_eq!(t.iter().cloned().collect::<Vec<_>>(), [1, 3]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links();
+        assert!(links.get_parent().resolve().is_some(),
+                "Cannot remove an element which is not in a tree");
+        debug_assert!(self.contains_links(links),
+                      "Cannot remove an element of another tree");
+        self.delete(links);
+        T::from_raw(links.container_of() as *const S as *mut S)
+    }
+
+    /// Climbs to the root from `links` to check it is in this tree
+    fn contains_links(&self, links: &L) -> bool {
+        let mut node = links;
+        while let Some(p) = parent(node) {
+            node = p;
+        }
+        same(self.root.resolve(), Some(node))
+    }
+
+    /// Replaces the subtree rooted at `old` with the one rooted at `new`
+    fn transplant(&mut self, old: &L, new: Option<&L>) {
+        match parent(old) {
+            None => {
+                self.root = link_of(new);
+                if let Some(n) = new {
+                    n.set_parent(Rawlink::some(n));
+                }
+            }
+            Some(p) => {
+                if same(left(p), Some(old)) {
+                    p.set_left(link_of(new));
+                } else {
+                    p.set_right(link_of(new));
+                }
+                if let Some(n) = new {
+                    n.set_parent(Rawlink::some(p));
+                }
+            }
+        }
+    }
+
+    fn rotate_left(&mut self, x: &L) {
+        let y = right(x).unwrap();
+        x.set_right(y.get_left());
+        if let Some(b) = left(y) {
+            b.set_parent(Rawlink::some(x));
+        }
+        self.transplant(x, Some(y));
+        y.set_left(Rawlink::some(x));
+        x.set_parent(Rawlink::some(y));
+    }
+
+    fn rotate_right(&mut self, x: &L) {
+        let y = left(x).unwrap();
+        x.set_left(y.get_right());
+        if let Some(b) = right(y) {
+            b.set_parent(Rawlink::some(x));
+        }
+        self.transplant(x, Some(y));
+        y.set_right(Rawlink::some(x));
+        x.set_parent(Rawlink::some(y));
+    }
+
+    /// Moves `node` above its parent
+    fn rotate_up(&mut self, node: &L, parent: &L) {
+        if same(left(parent), Some(node)) {
+            self.rotate_right(parent);
+        } else {
+            self.rotate_left(parent);
+        }
+    }
+
+    /// Moves `node` to the root of the tree
+    fn splay(&mut self, node: &L) {
+        while let Some(p) = parent(node) {
+            match parent(p) {
+                None => self.rotate_up(node, p),
+                Some(g) => {
+                    if same(left(g), Some(p)) == same(left(p), Some(node)) {
+                        // zig-zig
+                        self.rotate_up(p, g);
+                        self.rotate_up(node, p);
+                    } else {
+                        // zig-zag
+                        self.rotate_up(node, p);
+                        self.rotate_up(node, g);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Makes the subtree at `node` a tree of its own, returning its root
+    fn detach(node: Option<&L>) -> Rawlink<L> {
+        if let Some(n) = node {
+            n.set_parent(Rawlink::some(n));
+        }
+        link_of(node)
+    }
+
+    /// Unlinks `node` from the tree by splaying it to the root and joining
+    /// its subtrees
+    fn delete(&mut self, node: &L) {
+        self.splay(node);
+        let r = right(node);
+        self.root = Self::detach(left(node));
+        if let Some(l) = self.root.resolve() {
+            let max = maximum(l);
+            self.splay(max);
+            max.set_right(link_of(r));
+            if let Some(r) = r {
+                r.set_parent(Rawlink::some(max));
+            }
+        } else {
+            self.root = Self::detach(r);
+        }
+        node.set_parent(Rawlink::none());
+        node.set_left(Rawlink::none());
+        node.set_right(Rawlink::none());
+        self.length -= 1;
+    }
+}
+
+impl<P, T, S, L> SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    /// Inserts an element into the tree and moves it to the root.
+    ///
+    /// An element equal to some already in the tree is placed after them.
+    ///
+    /// This operation should compute in O(log n) amortized time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SplayTree;
+    ///
+    /// define_splaytree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t = SplayTree::new();
+    /// t.insert(Box::new(MyI32::new(3)));
+    /// t.insert(Box::new(MyI32::new(1)));
+    /// t.insert(Box::new(MyI32::new(2)));
+    /// assert_eq!(t.iter().cloned().collect::<Vec<_>>(), [1, 2, 3]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn insert(&mut self, elt: T) {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        let val = elt.get_val();
+        let links = elt.get_links();
+        let mut cur = self.root.resolve();
+        let mut parent = None;
+        let mut go_left = false;
+        while let Some(node) = cur {
+            parent = Some(node);
+            go_left = val < unsafe { node.container_of() }.get_val();
+            cur = if go_left { left(node) } else { right(node) };
+        }
+        match parent {
+            None => {
+                links.set_parent(Rawlink::some(links));
+                self.root = Rawlink::some(links);
+            }
+            Some(p) => {
+                links.set_parent(Rawlink::some(p));
+                if go_left {
+                    p.set_left(Rawlink::some(links));
+                } else {
+                    p.set_right(Rawlink::some(links));
+                }
+            }
+        }
+        self.length += 1;
+        self.splay(links);
+        unsafe { elt.take() };
+    }
+
+    /// Provides a reference to an element equal to `key`, or `None` if there
+    /// is none.
+    ///
+    /// The element found, or the last one visited if there is none, is moved
+    /// to the root of the tree.
+    ///
+    /// This operation should compute in O(log n) amortized time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SplayTree;
+    ///
+    /// define_splaytree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t: SplayTree<_, _, _, MyLink> =
+    ///     (0..8).map(|i| Box::new(MyI32::new(i))).collect();
+    /// assert_eq!(t.find(&3), Some(&3));
+    /// assert_eq!(t.find(&9), None);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn find<Q>(&mut self, key: &Q) -> Option<&P>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        let mut cur = self.root.resolve();
+        let mut last = None;
+        while let Some(node) = cur {
+            last = Some(node);
+            let val = unsafe { node.container_of() }.get_val();
+            cur = match key.cmp(val.borrow()) {
+                Ordering::Less => left(node),
+                Ordering::Greater => right(node),
+                Ordering::Equal => break,
+            };
+        }
+        let last = last?;
+        self.splay(last);
+        let val = unsafe { last.container_of() }.get_val();
+        if val.borrow() == key { Some(val) } else { None }
+    }
+
+    /// Splits the tree in two at `key`. Returns a tree of all the elements
+    /// not less than `key`, leaving the smaller ones in `self`.
+    ///
+    /// This operation should compute in O(log n) amortized time, plus time
+    /// proportional to the number of elements returned, which are counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::SplayTree;
+    ///
+    /// define_splaytree_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t: SplayTree<_, _, _, MyLink> =
+    ///     (0..8).map(|i| Box::new(MyI32::new(i))).collect();
+    /// let mut high = t.split(&5);
+    /// assert_eq!(t.iter().cloned().collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+    /// assert_eq!(high.iter().cloned().collect::<Vec<_>>(), [5, 6, 7]);
+    ///
+    /// t.join(&mut high);
+    /// assert_eq!(t.len(), 8);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn split<Q>(&mut self, key: &Q) -> SplayTree<P, T, S, L>
+        where P: Borrow<Q>,
+              Q: Ord + ?Sized
+    {
+        let mut cur = self.root.resolve();
+        let mut last = None;
+        let mut bound = None;
+        while let Some(node) = cur {
+            last = Some(node);
+            let val = unsafe { node.container_of() }.get_val();
+            if val.borrow() >= key {
+                bound = Some(node);
+                cur = left(node);
+            } else {
+                cur = right(node);
+            }
+        }
+        let mut other = SplayTree::new();
+        match bound {
+            None => {
+                if let Some(last) = last {
+                    self.splay(last);
+                }
+            }
+            Some(bound) => {
+                self.splay(bound);
+                let l = left(bound);
+                bound.set_left(Rawlink::none());
+                self.root = Self::detach(l);
+                other.root = Rawlink::some(bound);
+                let mut node = Some(minimum(bound));
+                while let Some(n) = node {
+                    other.length += 1;
+                    node = successor(n);
+                }
+                self.length -= other.length;
+            }
+        }
+        other
+    }
+
+    /// Moves all elements from `other` into the tree. After this operation,
+    /// `other` becomes empty.
+    ///
+    /// This operation should compute in O(log n) amortized time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some element of `self` is greater than some element of
+    /// `other`.
+    pub fn join(&mut self, other: &mut SplayTree<P, T, S, L>) {
+        let other_root = match other.root.take().resolve() {
+            None => return,
+            Some(root) => root,
+        };
+        match self.root.resolve() {
+            None => self.root = Rawlink::some(other_root),
+            Some(root) => {
+                let max = maximum(root);
+                let min = minimum(other_root);
+                if unsafe { max.container_of() }.get_val() >
+                    unsafe { min.container_of() }.get_val() {
+                    other.root = Rawlink::some(other_root);
+                    panic!("Cannot join a tree holding smaller elements");
+                }
+                self.splay(max);
+                max.set_right(Rawlink::some(other_root));
+                other_root.set_parent(Rawlink::some(max));
+            }
+        }
+        self.length += other.length;
+        other.length = 0;
+    }
+}
+
+impl<P, T, S, L> Default for SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> SplayTree<P, T, S, L> {
+        SplayTree::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl<P, T, S, L> Drop for SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SplayLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        // unlink the leaves one at a time, without rebalancing
+        let mut cur = self.root.take().resolve();
+        while let Some(node) = cur {
+            if let Some(l) = left(node) {
+                cur = Some(l);
+                continue;
+            }
+            if let Some(r) = right(node) {
+                cur = Some(r);
+                continue;
+            }
+            cur = parent(node);
+            if let Some(p) = cur {
+                if same(left(p), Some(node)) {
+                    p.set_left(Rawlink::none());
+                } else {
+                    p.set_right(Rawlink::none());
+                }
+            }
+            node.set_parent(Rawlink::none());
+            // hand back the reference held by the tree
+            drop(unsafe {
+                T::from_raw(node.container_of() as *const S as *mut S)
+            });
+        }
+        self.length = 0;
+    }
+}
+
+impl<P, T, S, L> Extend<T> for SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.insert(elt); }
+    }
+}
+
+impl<P, T, S, L> FromIterator<T> for SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Ord,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> SplayTree<P, T, S, L> {
+        let mut ret = SplayTree::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L> IntoIterator for SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L>;
+
+    /// Consumes the tree into an in-order iterator yielding elements by value.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L> {
+        IntoIter{tree: self}
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SplayLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+unsafe impl<P, T, S, L> Send for SplayTree<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: SplayLinkable<Container=T::Target>
+{}
+
+// SplayLinks impls
+
+unsafe impl<L: SplayLinkable> Send for SplayLinks<L> {}
+
+impl<L: SplayLinkable> Clone for SplayLinks<L>
+{
+    /// A clone is not in any tree, even if the original is
+    fn clone(&self) -> SplayLinks<L> {
+        Default::default()
+    }
+}
+
+impl<L: SplayLinkable> Drop for SplayLinks<L>
+{
+    fn drop(&mut self) {
+        assert!(self.parent.get().resolve().is_none());
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, L: SplayLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            head: self.head,
+            tail: self.tail,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SplayLinkable<Container=T> + 'a> Iterator
+    for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve().unwrap();
+        self.nelem -= 1;
+        self.head = link_of(successor(head));
+        let ret = unsafe { head.container_of() }.get_val();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SplayLinkable<Container=T> + 'a>
+    DoubleEndedIterator for Iter<'a, P, T, L>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let tail = self.tail.resolve().unwrap();
+        self.nelem -= 1;
+        self.tail = link_of(predecessor(tail));
+        let ret = unsafe { tail.container_of() }.get_val();
+        Some(ret)
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SplayLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+// IntoIter impls
+
+impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.tree.pop_first() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.tree.length, Some(self.tree.length))
+    }
+}
+
+impl<P, T, S, L> DoubleEndedIterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SplayLinkable<Container=T::Target>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<T> { self.tree.pop_last() }
+}
+
+///////////
+// Tests //
+///////////
+
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::vec::Vec;
+    use core::ptr;
+    use rand::{self, Rng};
+    use super::{SplayTree, OwningPointer, Node, SplayLinkable, SplayLinkOps};
+    use super::{left, right, parent};
+
+    define_splaytree_element!(MyI32 = i32 : MyLink);
+
+    type Tree = SplayTree<i32, Box<MyI32>, MyI32, MyLink>;
+
+    /// Checks the parent links and ordering of the subtree at `node`,
+    /// returning its size
+    fn check_node<P, S, L>(node: &L) -> usize
+        where P: Ord,
+              S: Node<P, L>,
+              L: SplayLinkable<Container=S>
+    {
+        let val = unsafe { node.container_of() }.get_val();
+        let mut size = 1;
+        for (i, child) in [left(node), right(node)].iter().enumerate() {
+            if let Some(child) = *child {
+                assert!(ptr::eq(parent(child).unwrap(), node));
+                let child_val = unsafe { child.container_of() }.get_val();
+                if i == 0 {
+                    assert!(child_val <= val);
+                } else {
+                    assert!(child_val >= val);
+                }
+                size += check_node(child);
+            }
+        }
+        size
+    }
+
+    pub fn check_links<P, T, S, L>(tree: &SplayTree<P, T, S, L>)
+        where T: OwningPointer<Target=S>,
+              P: Ord,
+              S: Node<P, L>,
+              L: SplayLinkable<Container=S>,
+    {
+        match tree.root.resolve() {
+            None => assert_eq!(tree.length, 0),
+            Some(root) => {
+                assert!(ptr::eq(root.get_parent().resolve().unwrap(), root));
+                assert_eq!(check_node(root), tree.length);
+            }
+        }
+    }
+
+    fn tree_from(v: &[i32]) -> Tree {
+        let tree: Tree = v.iter().map(|&i| Box::new(MyI32::new(i))).collect();
+        check_links(&tree);
+        tree
+    }
+
+    fn to_vec(tree: &Tree) -> Vec<i32> {
+        tree.iter().cloned().collect()
+    }
+
+    fn root_val(tree: &Tree) -> Option<i32> {
+        tree.root.resolve().map(|r| *unsafe { r.container_of() }.get_val())
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut t = Tree::new();
+        assert!(t.is_empty());
+        assert_eq!(t.find(&1), None);
+        assert_eq!(t.pop_first(), None);
+        t.insert(Box::new(MyI32::new(2)));
+        t.insert(Box::new(MyI32::new(1)));
+        t.insert(Box::new(MyI32::new(3)));
+        check_links(&t);
+        assert_eq!(root_val(&t), Some(3));
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.first(), Some(&1));
+        assert_eq!(t.last(), Some(&3));
+        assert_eq!(t.pop_first(), Some(Box::new(MyI32::new(1))));
+        assert_eq!(t.pop_last(), Some(Box::new(MyI32::new(3))));
+        check_links(&t);
+        assert_eq!(to_vec(&t), [2]);
+        t.clear();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_find() {
+        let mut t = tree_from(&[5, 3, 8, 1, 4, 7, 9, 2, 6]);
+        assert_eq!(t.find(&4), Some(&4));
+        check_links(&t);
+        assert_eq!(root_val(&t), Some(4));
+        assert_eq!(t.find(&10), None);
+        check_links(&t);
+        assert_eq!(root_val(&t), Some(9));
+        assert_eq!(to_vec(&t), [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(t.iter().rev().cloned().collect::<Vec<_>>(),
+                   [9, 8, 7, 6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_split_join() {
+        let mut t = tree_from(&[5, 3, 8, 1, 4, 7, 9, 2, 6]);
+        let high = t.split(&10);
+        assert!(high.is_empty());
+        assert_eq!(t.len(), 9);
+        let mut high = t.split(&6);
+        check_links(&t);
+        check_links(&high);
+        assert_eq!(to_vec(&t), [1, 2, 3, 4, 5]);
+        assert_eq!(to_vec(&high), [6, 7, 8, 9]);
+        let mut all = t.split(&0);
+        check_links(&t);
+        assert!(t.is_empty());
+        assert_eq!(all.len(), 5);
+
+        all.join(&mut high);
+        check_links(&all);
+        assert!(high.is_empty());
+        assert_eq!(to_vec(&all), [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        high.join(&mut all);
+        assert_eq!(high.len(), 9);
+        assert!(all.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_join_unordered() {
+        let mut t = tree_from(&[5, 6]);
+        let mut u = tree_from(&[1, 2]);
+        t.join(&mut u);
+    }
+
+    #[test]
+    fn test_rc() {
+        let elts: Vec<_> = (0..6).map(|i| Rc::new(MyI32::new(i))).collect();
+        let mut t: SplayTree<_, _, _, MyLink> = elts.iter().cloned().collect();
+        let high = t.split(&3);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        drop(t);
+        assert_eq!(Rc::strong_count(&elts[0]), 1);
+        assert_eq!(Rc::strong_count(&elts[5]), 2);
+        drop(high);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut t = Tree::new();
+            let mut v: Vec<i32> = Vec::new();
+            let mut elts: Vec<*const MyI32> = Vec::new();
+            for _ in 0..200 {
+                match rng.gen_range(0..5) {
+                    0 if !elts.is_empty() => {
+                        let i = rng.gen_range(0..elts.len());
+                        let e = unsafe { t.remove(&*elts.swap_remove(i)) };
+                        let pos = v.iter().position(|x| x == e.get_val());
+                        v.remove(pos.unwrap());
+                    }
+                    1 => {
+                        let key = rng.gen_range(0..50);
+                        assert_eq!(t.find(&key).is_some(), v.contains(&key));
+                    }
+                    _ => {
+                        let val = rng.gen_range(0..50);
+                        let e = Box::new(MyI32::new(val));
+                        elts.push(&*e);
+                        t.insert(e);
+                        v.push(val);
+                    }
+                }
+                check_links(&t);
+            }
+            v.sort();
+            assert_eq!(to_vec(&t), v);
+        }
+    }
+}
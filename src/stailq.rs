@@ -0,0 +1,579 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive singly-linked tail queue.
+//!
+//! The `STailQ` keeps pointers to both its first and last elements, so that
+//! elements can be pushed at either end and popped from the front in O(1)
+//! time. Elements use the same links as a `SinglyLinkedList` and are defined
+//! with `define_slist_element!`.
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::ops::DerefMut;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+use super::slist::{Iter, IterMut, Node, SLinkable, SListLinkOps};
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive singly-linked tail queue
+pub struct STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SLinkable<Container=T::Target>
+{
+    length: usize,
+    head: Rawlink<L>,
+    tail: Rawlink<L>,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+pub struct IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SLinkable<Container=T::Target>
+{
+    list: STailQ<P, T, S, L>
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// STailQ impls
+
+impl<P, T, S, L> STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    /// Creates an empty `STailQ`
+    #[inline]
+    pub fn new() -> STailQ<P, T, S, L> {
+        STailQ { length: 0, head: Rawlink::none(), tail: Rawlink::none(),
+                 _marker: PhantomData, _marker2: PhantomData,
+                 _marker3: PhantomData}
+    }
+
+    /// Moves all elements from `other` to the end of the queue.
+    ///
+    /// This reuses all the nodes from `other` and moves them into `self`. After
+    /// this operation, `other` becomes empty.
+    ///
+    /// This operation should compute in O(1) time and O(1) memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::STailQ;
+    ///
+    /// define_slist_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut a = STailQ::new();
+    /// let mut b = STailQ::new();
+    /// a.push_back(Box::new(MyI32::new(1)));
+    /// a.push_back(Box::new(MyI32::new(2)));
+    /// b.push_back(Box::new(MyI32::new(3)));
+    /// b.push_back(Box::new(MyI32::new(4)));
+    ///
+    /// a.append(&mut b);
+    ///
+    /// assert_eq!(a.iter().cloned().collect::<Vec<_>>(), [1, 2, 3, 4]);
+    /// assert!(b.is_empty());
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn append(&mut self, other: &mut STailQ<P, T, S, L>) {
+        let other_tail = other.tail.take();
+        match other.head.take().resolve() {
+            None => return,
+            Some(other_head) => match self.tail.resolve() {
+                None => self.head = Rawlink::some(other_head),
+                Some(tail) => tail.set_next(Rawlink::some(other_head)),
+            }
+        }
+        self.tail = other_tail;
+        self.length += other.length;
+        other.length = 0;
+    }
+
+    /// Provides a forward iterator.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        Iter{nelem: self.length, head: self.head, _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `STailQ` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the length of the `STailQ`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all elements from the `STailQ`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = STailQ::new()
+    }
+
+    /// Provides a reference to the front element, or `None` if the queue is
+    /// empty.
+    #[inline]
+    pub fn front(&self) -> Option<&P> {
+        self.head.resolve().map(|head| unsafe{head.container_of()}.get_val())
+    }
+
+    /// Provides a mutable reference to the front element, or `None` if the
+    /// queue is empty.
+    #[inline]
+    pub fn front_mut(&mut self) -> Option<&mut P> where T: DerefMut {
+        self.head.resolve_mut().map(|head| {
+            unsafe {head.container_of_mut()}.get_val_mut()
+        })
+    }
+
+    /// Provides a reference to the back element, or `None` if the queue is
+    /// empty.
+    #[inline]
+    pub fn back(&self) -> Option<&P> {
+        self.tail.resolve().map(|tail| unsafe{tail.container_of()}.get_val())
+    }
+
+    /// Provides a mutable reference to the back element, or `None` if the
+    /// queue is empty.
+    #[inline]
+    pub fn back_mut(&mut self) -> Option<&mut P> where T: DerefMut {
+        self.tail.resolve_mut().map(|tail| {
+            unsafe {tail.container_of_mut()}.get_val_mut()
+        })
+    }
+
+    /// Adds an element first in the queue.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn push_front(&mut self, elt: T) {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        match self.head.resolve() {
+            None => {
+                elt.get_links().set_next(Rawlink::some(elt.get_links()));
+                self.tail = Rawlink::some(elt.get_links());
+            }
+            Some(head) => elt.get_links().set_next(Rawlink::some(head)),
+        }
+        self.head = Rawlink::some(elt.get_links());
+        self.length += 1;
+        unsafe { elt.take() };
+    }
+
+    /// Appends an element to the back of the queue.
+    ///
+    /// This operation should compute in O(1) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::STailQ;
+    ///
+    /// define_slist_element!(MyI32 = i32 : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut d = STailQ::new();
+    /// d.push_back(Box::new(MyI32::new(1)));
+    /// d.push_back(Box::new(MyI32::new(3)));
+    /// assert_eq!(d.back(), Some(&3));
+    /// assert_eq!(d.pop_front(), Some(Box::new(MyI32::new(1))));
+    /// assert_eq!(d.pop_front(), Some(Box::new(MyI32::new(3))));
+    /// assert_eq!(d.pop_front(), None);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn push_back(&mut self, elt: T) {
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        // the last element links to itself
+        elt.get_links().set_next(Rawlink::some(elt.get_links()));
+        match self.tail.resolve() {
+            None => self.head = Rawlink::some(elt.get_links()),
+            Some(tail) => tail.set_next(Rawlink::some(elt.get_links())),
+        }
+        self.tail = Rawlink::some(elt.get_links());
+        self.length += 1;
+        unsafe { elt.take() };
+    }
+
+    /// Removes the first element and returns it, or `None` if the queue is
+    /// empty.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.head.take().resolve().map(|head| {
+            if self.length > 1 {
+                self.head = head.get_next();
+            } else {
+                self.tail = Rawlink::none();
+            }
+            head.set_next(Rawlink::none());
+            self.length -= 1;
+            unsafe {
+                T::from_raw(head.container_of() as *const S as *mut S)
+            }
+        })
+    }
+}
+
+impl<'a, P, T, S, L> STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{
+    /// Provides a forward iterator with mutable references
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> IterMut<'a, P, T, S, L> {
+        IterMut {
+            nelem: self.length,
+            head: self.head,
+            _marker: PhantomData,
+            _marker2: PhantomData,
+            _marker3: PhantomData,
+        }
+    }
+}
+
+impl<P, T, S, L> Default for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> STailQ<P, T, S, L> {
+        STailQ::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+impl<P, T, S, L> Drop for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        while self.length > 0 {
+            let head = self.head.resolve().unwrap();
+            self.head = head.get_next();
+            head.set_next(Rawlink::none());
+            self.length -= 1;
+            // hand back the reference held by the queue
+            drop(unsafe {
+                T::from_raw(head.container_of() as *const S as *mut S)
+            });
+        }
+    }
+}
+
+impl<P, T, S, L> Extend<T> for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.push_back(elt); }
+    }
+}
+
+impl<P, T, S, L> FromIterator<T> for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> STailQ<P, T, S, L> {
+        let mut ret = STailQ::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L> IntoIterator for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L>;
+
+    /// Consumes the queue into an iterator yielding elements by value.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L> {
+        IntoIter{list: self}
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+impl<'a, P, T, S, L> IntoIterator for &'a mut STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S> + DerefMut + 'a,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: SLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a mut P;
+    type IntoIter = IterMut<'a, P, T, S, L>;
+
+    fn into_iter(self) -> IterMut<'a, P, T, S, L> {
+        self.iter_mut()
+    }
+}
+
+impl<P, T, S, L> PartialEq for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: PartialEq,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    fn eq(&self, other: &STailQ<P, T, S, L>) -> bool {
+        self.len() == other.len() &&
+            self.iter().eq(other.iter())
+    }
+}
+
+impl<P, T, S, L> Eq for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: Eq,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{}
+
+unsafe impl<P, T, S, L> Send for STailQ<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: SLinkable<Container=T::Target>
+{}
+
+// IntoIter impls
+
+impl<P, T, S, L> Iterator for IntoIter<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SLinkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.list.pop_front() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.length, Some(self.list.length))
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::format;
+    use std::rc::Rc;
+    use std::vec::Vec;
+    use core::ptr;
+    use super::STailQ;
+    use super::super::linked_list::OwningPointer;
+    use super::super::slist::{Node, SLinkable, SListLinkOps};
+
+    define_slist_element!(MyI32 = i32 : MyLink);
+
+    type Queue = STailQ<i32, Box<MyI32>, MyI32, MyLink>;
+
+    pub fn check_links<P, T, S, L>(queue: &STailQ<P, T, S, L>)
+        where T: OwningPointer<Target=S>,
+              S: Node<P, L>,
+              L: SLinkable<Container=S>,
+    {
+        let mut len = 0;
+        let mut cur = queue.head;
+        let mut last = None;
+        while let Some(node) = cur.resolve() {
+            len += 1;
+            let next = node.get_next().resolve().expect("unset next link");
+            if ptr::eq(next, node) {
+                last = Some(node);
+                break;
+            }
+            assert!(len < queue.length, "last element does not link to itself");
+            cur = node.get_next();
+        }
+        assert_eq!(len, queue.length);
+        assert!(last.map(|l| l as *const L) == queue.tail.resolve().map(|t| t as *const L));
+    }
+
+    fn queue_from(v: &[i32]) -> Queue {
+        let queue: Queue = v.iter().map(|&i| Box::new(MyI32::new(i))).collect();
+        check_links(&queue);
+        queue
+    }
+
+    fn to_vec(queue: &Queue) -> Vec<i32> {
+        queue.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut m = Queue::new();
+        assert_eq!(m.pop_front(), None);
+        assert_eq!(m.back(), None);
+        m.push_back(Box::new(MyI32::new(1)));
+        assert_eq!(m.pop_front(), Some(Box::new(MyI32::new(1))));
+        check_links(&m);
+        m.push_back(Box::new(MyI32::new(2)));
+        m.push_back(Box::new(MyI32::new(3)));
+        m.push_front(Box::new(MyI32::new(1)));
+        check_links(&m);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.front(), Some(&1));
+        assert_eq!(m.back(), Some(&3));
+        *m.back_mut().unwrap() = 4;
+        assert_eq!(to_vec(&m), [1, 2, 4]);
+        assert_eq!(m.pop_front(), Some(Box::new(MyI32::new(1))));
+        assert_eq!(m.pop_front(), Some(Box::new(MyI32::new(2))));
+        check_links(&m);
+        assert_eq!(m.pop_front(), Some(Box::new(MyI32::new(4))));
+        assert_eq!(m.pop_front(), None);
+        assert!(m.is_empty());
+        check_links(&m);
+        m.push_front(Box::new(MyI32::new(5)));
+        m.push_back(Box::new(MyI32::new(6)));
+        check_links(&m);
+        assert_eq!(to_vec(&m), [5, 6]);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut m = queue_from(&[0, 1, 2, 3]);
+        assert_eq!(m.iter().len(), 4);
+        for e in &mut m {
+            *e += 1;
+        }
+        assert_eq!(to_vec(&m), [1, 2, 3, 4]);
+        let v: Vec<_> = m.into_iter().map(|e| *e.get_val()).collect();
+        assert_eq!(v, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_append() {
+        {
+            let mut m = Queue::new();
+            let mut n = Queue::new();
+            m.append(&mut n);
+            check_links(&m);
+            assert!(m.is_empty() && n.is_empty());
+        }
+        {
+            let mut m = Queue::new();
+            let mut n = queue_from(&[1, 2]);
+            m.append(&mut n);
+            check_links(&m);
+            check_links(&n);
+            assert_eq!(to_vec(&m), [1, 2]);
+        }
+        {
+            let mut m = queue_from(&[1, 2]);
+            let mut n = Queue::new();
+            m.append(&mut n);
+            check_links(&m);
+            assert_eq!(to_vec(&m), [1, 2]);
+        }
+        {
+            let mut m = queue_from(&[1, 2, 3]);
+            let mut n = queue_from(&[4, 5]);
+            m.append(&mut n);
+            check_links(&m);
+            check_links(&n);
+            assert_eq!(to_vec(&m), [1, 2, 3, 4, 5]);
+            assert!(n.is_empty());
+            m.push_back(Box::new(MyI32::new(6)));
+            check_links(&m);
+            assert_eq!(m.back(), Some(&6));
+        }
+    }
+
+    #[test]
+    fn test_eq() {
+        let m = queue_from(&[1, 2, 3]);
+        assert_eq!(m, queue_from(&[1, 2, 3]));
+        assert!(m != queue_from(&[1, 2]));
+        assert_eq!(format!("{:?}", m), "[1, 2, 3]");
+    }
+
+    #[test]
+    fn test_rc() {
+        let elts: Vec<_> = (0..3).map(|i| Rc::new(MyI32::new(i))).collect();
+        let mut m: STailQ<_, _, _, MyLink> = elts.iter().cloned().collect();
+        check_links(&m);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        assert_eq!(m.pop_front().map(|e| *e.get_val()), Some(0));
+        drop(m);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+}
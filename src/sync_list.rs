@@ -0,0 +1,806 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive doubly-linked list shared between threads.
+//!
+//! A `SyncLinkedList` is a `LinkedList` behind an embedded spinlock. Locking it
+//! returns a guard through which the list is read and modified.
+//!
+//! Elements record which list they are on, so a thread holding a reference to
+//! an element may unlink it without holding the lock beforehand: `remove`
+//! takes the lock and checks that the element is still on this list, rather
+//! than on another one or on none, before unlinking it. Elements are typically
+//! shared as `Arc`s, which requires them to be `Sync`. The links of a
+//! `SyncLinkedList` element are atomics, only read or written under the lock
+//! of the list recorded in them, so they are `Sync` without further ado. They
+//! are of no use to any other container, which could not check that list:
+//!
+//! ```compile_fail
+//! # #[macro_use] extern crate intrusive_containers;
+//! use std::sync::Arc;
+//! use intrusive_containers::LinkedList;
+//!
+//! define_sync_list_element!(Timer = u64 : TimerLink);
+//!
+//! # fn main() {
+//! let mut list: LinkedList<_, Arc<Timer>, _, _> = LinkedList::new();
+//! list.push_back(Arc::new(Timer::new(100)));
+//! # }
+//! ```
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hint;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+
+/// The id of the next `SyncLinkedList`, `0` standing for no list
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into a `SyncLinkedList`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_sync_list_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: SyncLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into a `SyncLinkedList`
+///
+/// # Safety
+///
+/// The trait is unsafe because `offset()` must be the offset of the link
+/// within its `Container`.
+pub unsafe trait SyncLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &SyncLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut SyncLinks<Self>;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+}
+
+/// Accessors for the links of a `SyncLinkable`
+///
+/// They are private to the module: the links are only followed or changed
+/// under the lock of the list recorded in `owner`.
+trait SyncLinkOps : SyncLinkable
+{
+    fn get_next(&self) -> Rawlink<Self> {
+        Rawlink::from_ptr(self.get_links().next.load(Ordering::Relaxed))
+    }
+    fn set_next(&self, next: Rawlink<Self>) {
+        self.get_links().next.store(next.as_ptr(), Ordering::Relaxed)
+    }
+    fn get_prev(&self) -> Rawlink<Self> {
+        Rawlink::from_ptr(self.get_links().prev.load(Ordering::Relaxed))
+    }
+    fn set_prev(&self, prev: Rawlink<Self>) {
+        self.get_links().prev.store(prev.as_ptr(), Ordering::Relaxed)
+    }
+}
+
+impl<L: SyncLinkable> SyncLinkOps for L {}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into a `SyncLinkedList`
+///
+/// `define_sync_list_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it on a list.
+#[macro_export]
+macro_rules! define_sync_list_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_sync_list_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_sync_list_link!($link = $elt);
+        impl_sync_list_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_sync_list_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_sync_list_link!($link = $elt);
+        impl_sync_list_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_sync_list_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::sync_list::SyncLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::sync_list::SyncLinks<$link>);
+    );
+}
+
+/// Implements `SyncLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_sync_list_link {
+    ($link:ident = $elt:ident) => (
+        impl_sync_list_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::sync_list::SyncLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::sync_list::SyncLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self)
+                             -> &mut $crate::sync_list::SyncLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_sync_list_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_sync_list_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::sync_list::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive doubly-linked list with an embedded lock
+pub struct SyncLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SyncLinkable<Container=T::Target>
+{
+    id: usize,
+    locked: AtomicBool,
+    /// The ends of the list, only accessed under the lock
+    list: UnsafeCell<Ends<L>>,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+struct Ends<L> {
+    length: usize,
+    head: Rawlink<L>,
+    tail: Rawlink<L>
+}
+
+/// A locked `SyncLinkedList`, unlocked when dropped
+pub struct SyncListGuard<'a, P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SyncLinkable<Container=T::Target>
+{
+    list: &'a SyncLinkedList<P, T, S, L>
+}
+
+pub struct SyncLinks<L>
+{
+    /// The id of the list the element is on, or `0`
+    owner: AtomicUsize,
+    prev: AtomicPtr<L>,
+    next: AtomicPtr<L>,
+    _pin: PhantomPinned
+}
+
+/// An iterator over references to the items of a locked `SyncLinkedList`
+pub struct Iter<'a, P: 'a, T, L: SyncLinkable<Container=T>> {
+    head: Rawlink<L>,
+    nelem: usize,
+    _marker: PhantomData<&'a P>
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// SyncLinkedList impls
+
+impl<P, T, S, L> SyncLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SyncLinkable<Container=T::Target>
+{
+    /// Creates an empty `SyncLinkedList`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `usize::MAX` lists have already been created.
+    #[inline]
+    pub fn new() -> SyncLinkedList<P, T, S, L> {
+        let id = NEXT_ID.fetch_update(Ordering::Relaxed, Ordering::Relaxed,
+                                      |id| id.checked_add(1))
+            .expect("Out of SyncLinkedList ids");
+        SyncLinkedList { id, locked: AtomicBool::new(false),
+                         list: UnsafeCell::new(Ends { length: 0,
+                                                      head: Rawlink::none(),
+                                                      tail: Rawlink::none() }),
+                         _marker: PhantomData, _marker2: PhantomData,
+                         _marker3: PhantomData }
+    }
+
+    /// Locks the list, spinning until it is available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use intrusive_containers::SyncLinkedList;
+    ///
+    /// define_sync_list_element!(Task = u32 : TaskLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let runqueue: Arc<SyncLinkedList<_, Box<Task>, _, _>> =
+    ///     Arc::new(SyncLinkedList::new());
+    /// let spawners: Vec<_> = (0..4).map(|i| {
+    ///     let runqueue = runqueue.clone();
+    ///     thread::spawn(move || runqueue.lock().push_back(Box::new(Task::new(i))))
+    /// }).collect();
+    /// for s in spawners {
+    ///     s.join().unwrap();
+    /// }
+    ///
+    /// let mut rq = runqueue.lock();
+    /// assert_eq!(rq.len(), 4);
+    /// let mut ids: Vec<_> = rq.iter().cloned().collect();
+    /// ids.sort();
+    /// assert_eq!(ids, [0, 1, 2, 3]);
+    /// rq.clear();
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn lock(&self) -> SyncListGuard<'_, P, T, S, L> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+    }
+
+    /// Locks the list if it is available, or returns `None`.
+    #[inline]
+    pub fn try_lock(&self) -> Option<SyncListGuard<'_, P, T, S, L>> {
+        self.locked.compare_exchange(false, true, Ordering::Acquire,
+                                     Ordering::Relaxed)
+            .ok().map(|_| SyncListGuard { list: self })
+    }
+
+    /// Removes `elt` from the list and returns it, or returns `None` if it is
+    /// not on this list.
+    ///
+    /// This locks the list, so the caller only needs a reference to `elt`,
+    /// which may be concurrently removed by other threads, or be on another
+    /// list.
+    ///
+    /// This operation should compute in O(1) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use std::sync::Arc;
+    /// use intrusive_containers::SyncLinkedList;
+    ///
+    /// define_sync_list_element!(Timer = u64 : TimerLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let pending: SyncLinkedList<_, Arc<Timer>, _, _> = SyncLinkedList::new();
+    /// let other: SyncLinkedList<_, Arc<Timer>, _, _> = SyncLinkedList::new();
+    /// let timer = Arc::new(Timer::new(100));
+    /// pending.lock().push_back(timer.clone());
+    ///
+    /// assert!(other.remove(&timer).is_none());
+    /// assert!(pending.remove(&timer).is_some());
+    /// // a concurrent cancellation would find it gone
+    /// assert!(pending.remove(&timer).is_none());
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    #[inline]
+    pub fn remove(&self, elt: &S) -> Option<T> {
+        self.lock().remove(elt)
+    }
+}
+
+impl<P, T, S, L> Default for SyncLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SyncLinkable<Container=T::Target>
+{
+    #[inline]
+    fn default() -> SyncLinkedList<P, T, S, L> {
+        SyncLinkedList::new()
+    }
+}
+
+impl<P, T, S, L> fmt::Debug for SyncLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SyncLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SyncLinkedList { .. }")
+    }
+}
+
+impl<P, T, S, L> Drop for SyncLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SyncLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        // elements which outlive the list must be free to go on another one
+        let ends = self.list.get_mut();
+        let mut cur = ends.head.take();
+        while let Some(links) = cur.resolve() {
+            cur = links.get_next();
+            links.set_next(Rawlink::none());
+            links.set_prev(Rawlink::none());
+            links.get_links().owner.store(0, Ordering::Release);
+            unsafe {
+                drop(T::from_raw(links.container_of() as *const S as *mut S));
+            }
+        }
+    }
+}
+
+// The elements are only accessed under the lock, so handing them from one
+// thread to another is all that sharing the list does
+unsafe impl<P, T, S, L> Send for SyncLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: SyncLinkable<Container=T::Target>
+{}
+
+unsafe impl<P, T, S, L> Sync for SyncLinkedList<P, T, S, L>
+    where T: OwningPointer<Target=S> + Send,
+          L: SyncLinkable<Container=T::Target>
+{}
+
+// SyncListGuard impls
+
+impl<'a, P, T, S, L> SyncListGuard<'a, P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: SyncLinkable<Container=T::Target>
+{
+    #[inline]
+    fn ends(&self) -> &Ends<L> {
+        unsafe { &*self.list.list.get() }
+    }
+
+    #[inline]
+    fn ends_mut(&mut self) -> &mut Ends<L> {
+        unsafe { &mut *self.list.list.get() }
+    }
+
+    /// Records `elt` as being on this list
+    fn claim(&self, elt: &T) {
+        let links = elt.get_links().get_links();
+        assert!(links.owner.compare_exchange(0, self.list.id, Ordering::Acquire,
+                                             Ordering::Relaxed).is_ok(),
+                "Cannot insert an element which is already on a list");
+    }
+
+    /// Unlinks `links`, which must be on this list, and returns its element
+    /// as being on no list
+    fn release(&mut self, links: &L) -> T {
+        let prev = links.get_prev();
+        let next = links.get_next();
+        match prev.resolve() {
+            None => self.ends_mut().head = next,
+            Some(prev) => prev.set_next(next)
+        }
+        match next.resolve() {
+            None => self.ends_mut().tail = prev,
+            Some(next) => next.set_prev(prev)
+        }
+        links.set_next(Rawlink::none());
+        links.set_prev(Rawlink::none());
+        self.ends_mut().length -= 1;
+        // Release, so that the next list to claim the element sees it
+        // unlinked
+        links.get_links().owner.store(0, Ordering::Release);
+        unsafe { T::from_raw(links.container_of() as *const S as *mut S) }
+    }
+
+    /// Returns the length of the list.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ends().length
+    }
+
+    /// Returns `true` if the list is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ends().length == 0
+    }
+
+    /// Provides a reference to the front element, or `None` if the list is
+    /// empty.
+    #[inline]
+    pub fn front(&self) -> Option<&P> {
+        self.ends().head.resolve()
+            .map(|head| unsafe { head.container_of() }.get_val())
+    }
+
+    /// Provides a reference to the back element, or `None` if the list is
+    /// empty.
+    #[inline]
+    pub fn back(&self) -> Option<&P> {
+        self.ends().tail.resolve()
+            .map(|tail| unsafe { tail.container_of() }.get_val())
+    }
+
+    /// Provides a forward iterator.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        Iter { head: self.ends().head, nelem: self.len(), _marker: PhantomData }
+    }
+
+    /// Returns `true` if `elt` is on this list.
+    #[inline]
+    pub fn contains(&self, elt: &S) -> bool {
+        elt.get_links().get_links().owner.load(Ordering::Relaxed) == self.list.id
+    }
+
+    /// Adds an element first in the list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element is already on a list.
+    pub fn push_front(&mut self, elt: T) {
+        self.claim(&elt);
+        let links = elt.get_links();
+        let head = self.ends().head;
+        links.set_next(head);
+        match head.resolve() {
+            None => self.ends_mut().tail = Rawlink::some(links),
+            Some(head) => head.set_prev(Rawlink::some(links))
+        }
+        self.ends_mut().head = Rawlink::some(links);
+        self.ends_mut().length += 1;
+        unsafe { elt.take() };
+    }
+
+    /// Appends an element to the back of the list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element is already on a list.
+    pub fn push_back(&mut self, elt: T) {
+        self.claim(&elt);
+        let links = elt.get_links();
+        let tail = self.ends().tail;
+        links.set_prev(tail);
+        match tail.resolve() {
+            None => self.ends_mut().head = Rawlink::some(links),
+            Some(tail) => tail.set_next(Rawlink::some(links))
+        }
+        self.ends_mut().tail = Rawlink::some(links);
+        self.ends_mut().length += 1;
+        unsafe { elt.take() };
+    }
+
+    /// Removes the first element and returns it, or `None` if the list is
+    /// empty.
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.ends().head.resolve().map(|head| self.release(head))
+    }
+
+    /// Removes the last element and returns it, or `None` if the list is
+    /// empty.
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.ends().tail.resolve().map(|tail| self.release(tail))
+    }
+
+    /// Removes `elt` from the list and returns it, or returns `None` if it is
+    /// not on this list.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn remove(&mut self, elt: &S) -> Option<T> {
+        if !self.contains(elt) {
+            return None;
+        }
+        // the element is ours, and we hold the lock
+        Some(self.release(elt.get_links()))
+    }
+
+    /// Removes all elements from the list.
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<'a, P, T, S, L> fmt::Debug for SyncListGuard<'a, P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: SyncLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, P, T, S, L> Drop for SyncListGuard<'a, P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          L: SyncLinkable<Container=T::Target>
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.list.locked.store(false, Ordering::Release);
+    }
+}
+
+// SyncLinks impls
+
+impl<L> Default for SyncLinks<L>
+{
+    fn default() -> SyncLinks<L> {
+        SyncLinks { owner: AtomicUsize::new(0),
+                    prev: AtomicPtr::new(ptr::null_mut()),
+                    next: AtomicPtr::new(ptr::null_mut()),
+                    _pin: PhantomPinned }
+    }
+}
+
+impl<L> fmt::Debug for SyncLinks<L>
+{
+    /// The links are not shown, since only the list the element is on may
+    /// follow them
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SyncLinks { .. }")
+    }
+}
+
+impl<L> Clone for SyncLinks<L>
+{
+    /// A clone is not on any list, even if the original is
+    fn clone(&self) -> SyncLinks<L> {
+        Default::default()
+    }
+}
+
+impl<L> Drop for SyncLinks<L>
+{
+    fn drop(&mut self) {
+        assert_eq!(*self.owner.get_mut(), 0);
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, L: SyncLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            head: self.head,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SyncLinkable<Container=T> + 'a> Iterator
+    for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve().unwrap();
+        self.nelem -= 1;
+        self.head = head.get_next();
+        let ret = unsafe { head.container_of() }.get_val();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: SyncLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+    use super::{SyncLinkedList, Node};
+
+    define_sync_list_element!(MyI32 = i32 : MyLink);
+
+    type List = SyncLinkedList<i32, Arc<MyI32>, MyI32, MyLink>;
+
+    #[test]
+    fn test_basic() {
+        let l = List::new();
+        let elts: Vec<_> = (0..4).map(|i| Arc::new(MyI32::new(i))).collect();
+        {
+            let mut g = l.lock();
+            assert!(g.is_empty());
+            g.push_back(elts[1].clone());
+            g.push_back(elts[2].clone());
+            g.push_front(elts[0].clone());
+            assert!(g.contains(&elts[0]));
+            assert!(!g.contains(&elts[3]));
+            assert_eq!(g.iter().cloned().collect::<Vec<_>>(), [0, 1, 2]);
+            assert!(l.try_lock().is_none());
+        }
+        assert_eq!(l.remove(&elts[1]).map(|e| *e.get_val()), Some(1));
+        assert!(l.remove(&elts[1]).is_none());
+        assert!(l.remove(&elts[3]).is_none());
+        let mut g = l.lock();
+        assert_eq!(g.pop_back().map(|e| *e.get_val()), Some(2));
+        assert_eq!(g.iter().cloned().collect::<Vec<_>>(), [0]);
+        // popped elements can go back on a list
+        g.push_back(elts[1].clone());
+        assert_eq!(g.len(), 2);
+    }
+
+    #[test]
+    fn test_other_list() {
+        let a = List::new();
+        let b = List::new();
+        let elt = Arc::new(MyI32::new(1));
+        a.lock().push_back(elt.clone());
+        assert!(b.remove(&elt).is_none());
+        assert_eq!(a.lock().len(), 1);
+        let elt2 = a.remove(&elt).unwrap();
+        b.lock().push_back(elt2);
+        assert!(a.remove(&elt).is_none());
+        assert!(b.remove(&elt).is_some());
+    }
+
+    #[test]
+    fn test_drop_releases() {
+        let elt = Arc::new(MyI32::new(1));
+        {
+            let l = List::new();
+            l.lock().push_back(elt.clone());
+        }
+        let l = List::new();
+        l.lock().push_back(elt.clone());
+        assert!(l.remove(&elt).is_some());
+        assert_eq!(Arc::strong_count(&elt), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_twice() {
+        let a = List::new();
+        let b = List::new();
+        let elt = Arc::new(MyI32::new(1));
+        a.lock().push_back(elt.clone());
+        b.lock().push_back(elt);
+    }
+
+    #[test]
+    fn test_threads() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let l: Arc<List> = Arc::new(SyncLinkedList::new());
+        assert_send_sync(&l);
+        let elts: Vec<_> = (0..400).map(|i| Arc::new(MyI32::new(i))).collect();
+        for e in &elts {
+            l.lock().push_back(e.clone());
+        }
+        // every element is removed by exactly one of two racing threads
+        let workers: Vec<_> = (0..4).map(|k| {
+            let l = l.clone();
+            let mine: Vec<_> = elts.iter().filter(|e| e.get_val() % 2 == k % 2)
+                .cloned().collect();
+            thread::spawn(move || {
+                mine.iter().filter(|e| l.remove(e).is_some()).count()
+            })
+        }).collect();
+        let removed: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+        assert_eq!(removed, 400);
+        assert!(l.lock().is_empty());
+        assert!(elts.iter().all(|e| Arc::strong_count(e) == 1));
+    }
+}
@@ -0,0 +1,479 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive hierarchical timer wheel.
+//!
+//! The `TimerWheel` has `LEVELS` levels of `SLOTS` slots, each slot being a
+//! `LinkedList` of timers. A slot of level `k` covers `SLOTS^k` ticks, so that
+//! the wheel covers `SLOTS^LEVELS` ticks in all; timers further away wait on
+//! an overflow list. As time advances, the timers of a slot of level `k` are
+//! cascaded down to the lower levels when the slot is reached.
+//!
+//! Scheduling and cancelling a timer take O(1) time. Advancing the wheel
+//! takes time proportional to the number of ticks elapsed, plus that of the
+//! timers expired or cascaded.
+use core::fmt;
+use core::mem;
+use super::linked_list::{LinkedList, Linkable, Node, OwningPointer};
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A value which can be scheduled on a `TimerWheel`
+///
+/// The deadline of a timer must not change while it is on a wheel.
+pub trait Timer {
+    /// Returns the tick at which the timer expires
+    fn deadline(&self) -> u64;
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive hierarchical timer wheel
+pub struct TimerWheel<P, T, S, L, const LEVELS: usize = 4,
+                      const SLOTS: usize = 64>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>
+{
+    now: u64,
+    length: usize,
+    levels: [[LinkedList<P, T, S, L>; SLOTS]; LEVELS],
+    overflow: LinkedList<P, T, S, L>,
+    due: LinkedList<P, T, S, L>
+}
+
+/// The list holding a timer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Position {
+    Due,
+    Slot(usize, usize),
+    Overflow
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// TimerWheel impls
+
+impl<P, T, S, L, const LEVELS: usize, const SLOTS: usize>
+    TimerWheel<P, T, S, L, LEVELS, SLOTS>
+    where T: OwningPointer<Target=S>,
+          P: Timer,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>
+{
+    /// Creates an empty `TimerWheel` whose current tick is `now`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the wheel has no level or fewer than two slots per level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::TimerWheel;
+    /// use intrusive_containers::timer_wheel::Timer;
+    ///
+    /// // a deadline and the task to wake up
+    /// #[derive(Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    /// struct Wakeup(u64, u32);
+    ///
+    /// impl Timer for Wakeup {
+    ///     fn deadline(&self) -> u64 { self.0 }
+    /// }
+    ///
+    /// define_list_element!(WakeupElt = Wakeup : WakeupLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut wheel: TimerWheel<_, Box<WakeupElt>, _, _> = TimerWheel::new(0);
+    /// wheel.schedule(Box::new(WakeupElt::new(Wakeup(300, 1))));
+    /// wheel.schedule(Box::new(WakeupElt::new(Wakeup(5, 2))));
+    ///
+    /// assert!(wheel.advance(4).is_empty());
+    /// let expired = wheel.advance(500);
+    /// let tasks: Vec<_> = expired.iter().map(|w| w.1).collect();
+    /// assert_eq!(tasks, [2, 1]);
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn new(now: u64) -> TimerWheel<P, T, S, L, LEVELS, SLOTS> {
+        assert!(LEVELS > 0, "A timer wheel needs at least one level");
+        assert!(SLOTS > 1, "A timer wheel needs at least two slots per level");
+        TimerWheel {
+            now,
+            length: 0,
+            levels: core::array::from_fn(|_| {
+                core::array::from_fn(|_| LinkedList::new())
+            }),
+            overflow: LinkedList::new(),
+            due: LinkedList::new()
+        }
+    }
+
+    /// Returns the current tick
+    #[inline]
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Returns `true` if no timer is scheduled
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of timers scheduled.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Removes all timers from the wheel.
+    ///
+    /// This operation should compute in O(n) time, where n is the number of
+    /// timers plus the number of slots.
+    pub fn clear(&mut self) {
+        for level in self.levels.iter_mut() {
+            for slot in level.iter_mut() {
+                slot.clear();
+            }
+        }
+        self.overflow.clear();
+        self.due.clear();
+        self.length = 0;
+    }
+
+    /// Returns the number of ticks covered by a slot of level `level`, or
+    /// `None` if it does not fit in a `u64`
+    fn span(level: usize) -> Option<u64> {
+        (SLOTS as u64).checked_pow(level as u32)
+    }
+
+    /// Returns where a timer expiring at `deadline` belongs at the current
+    /// tick
+    fn position(&self, deadline: u64) -> Position {
+        if deadline <= self.now {
+            return Position::Due;
+        }
+        for level in 0..LEVELS {
+            let block = match Self::span(level + 1) {
+                Some(span) => deadline / span == self.now / span,
+                None => true,
+            };
+            if block {
+                let span = Self::span(level).unwrap();
+                return Position::Slot(level, (deadline / span) as usize % SLOTS);
+            }
+        }
+        Position::Overflow
+    }
+
+    fn list_mut(&mut self, pos: Position) -> &mut LinkedList<P, T, S, L> {
+        match pos {
+            Position::Due => &mut self.due,
+            Position::Slot(level, slot) => &mut self.levels[level][slot],
+            Position::Overflow => &mut self.overflow,
+        }
+    }
+
+    /// Schedules a timer to expire at its deadline. A timer whose deadline
+    /// has already passed expires on the next call to `advance`.
+    ///
+    /// This operation should compute in O(1) time.
+    pub fn schedule(&mut self, elt: T) {
+        let pos = self.position(elt.get_val().deadline());
+        self.list_mut(pos).push_back(elt);
+        self.length += 1;
+    }
+
+    /// Cancels a scheduled timer and returns ownership of it.
+    ///
+    /// This operation should compute in O(1) time. In debug builds the slot
+    /// of `elt` is walked to check that `elt` belongs to it.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be
+    /// scheduled on this wheel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not linked into any list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::TimerWheel;
+    /// use intrusive_containers::linked_list::Node;
+    /// use intrusive_containers::timer_wheel::Timer;
+    ///
+    /// #[derive(Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    /// struct Timeout(u64);
+    ///
+    /// impl Timer for Timeout {
+    ///     fn deadline(&self) -> u64 { self.0 }
+    /// }
+    ///
+    /// define_list_element!(TimeoutElt = Timeout : TimeoutLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut wheel: TimerWheel<_, Box<TimeoutElt>, _, _> = TimerWheel::new(0);
+    /// let timeout = Box::new(TimeoutElt::new(Timeout(10)));
+    /// let ptr: *const TimeoutElt = &*timeout;
+    /// wheel.schedule(timeout);
+    ///
+    /// let timeout = unsafe { wheel.cancel(&*ptr) };
+    /// assert_eq!(timeout.get_val().0, 10);
+    /// assert!(wheel.advance(20).is_empty());
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub unsafe fn cancel(&mut self, elt: &S) -> T {
+        let pos = self.position(elt.get_val().deadline());
+        let ret = self.list_mut(pos).remove(elt);
+        self.length -= 1;
+        ret
+    }
+
+    /// Moves the timers of `list` to where they belong at the current tick
+    fn cascade(&mut self, mut list: LinkedList<P, T, S, L>) {
+        while let Some(elt) = list.pop_front() {
+            let pos = self.position(elt.get_val().deadline());
+            self.list_mut(pos).push_back(elt);
+        }
+    }
+
+    /// Advances the wheel to tick `now` and returns the timers which expired,
+    /// in order of their deadlines.
+    ///
+    /// Timers with the same deadline are returned in the order they were
+    /// scheduled, unless they were scheduled at different levels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `now` is before the current tick.
+    pub fn advance(&mut self, now: u64) -> LinkedList<P, T, S, L> {
+        assert!(now >= self.now, "Cannot move a timer wheel back in time");
+        let mut expired = mem::take(&mut self.due);
+        while self.now < now && self.length > expired.len() {
+            self.now += 1;
+            let tick = self.now;
+            if Self::span(LEVELS).is_some_and(|span| tick.is_multiple_of(span)) {
+                let overflow = mem::take(&mut self.overflow);
+                self.cascade(overflow);
+            }
+            for level in (1..LEVELS).rev() {
+                let span = Self::span(level).unwrap();
+                if tick.is_multiple_of(span) {
+                    let slot = (tick / span) as usize % SLOTS;
+                    let list = mem::take(&mut self.levels[level][slot]);
+                    self.cascade(list);
+                }
+            }
+            let slot = tick as usize % SLOTS;
+            expired.append(&mut self.levels[0][slot]);
+            expired.append(&mut self.due);
+        }
+        // nothing is left to expire before `now`
+        self.now = now;
+        self.length -= expired.len();
+        expired
+    }
+}
+
+impl<P, T, S, L, const LEVELS: usize, const SLOTS: usize> fmt::Debug
+    for TimerWheel<P, T, S, L, LEVELS, SLOTS>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: Linkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TimerWheel")
+            .field("now", &self.now)
+            .field("len", &self.length)
+            .finish()
+    }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::thread;
+    use std::vec::Vec;
+    use rand::{self, Rng};
+    use super::{Timer, TimerWheel};
+    use super::super::linked_list::{LinkedList, Node};
+
+    #[derive(Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    struct Deadline(u64, u32);
+
+    impl Timer for Deadline {
+        fn deadline(&self) -> u64 {
+            self.0
+        }
+    }
+
+    define_list_element!(MyTimer = Deadline : MyLink);
+
+    type Wheel = TimerWheel<Deadline, Box<MyTimer>, MyTimer, MyLink, 3, 4>;
+
+    fn timer(at: u64, id: u32) -> Box<MyTimer> {
+        Box::new(MyTimer::new(Deadline(at, id)))
+    }
+
+    fn ids<T>(list: LinkedList<Deadline, T, MyTimer, MyLink>) -> Vec<u32>
+        where T: super::OwningPointer<Target=MyTimer>
+    {
+        list.iter().map(|d| d.1).collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut w = Wheel::new(0);
+        assert!(w.is_empty());
+        assert!(w.advance(10).is_empty());
+        assert_eq!(w.now(), 10);
+        w.schedule(timer(12, 0));
+        w.schedule(timer(11, 1));
+        w.schedule(timer(12, 2));
+        w.schedule(timer(5, 3));
+        assert_eq!(w.len(), 4);
+        assert_eq!(ids(w.advance(10)), [3]);
+        assert_eq!(ids(w.advance(11)), [1]);
+        assert_eq!(ids(w.advance(12)), [0, 2]);
+        assert!(w.is_empty());
+    }
+
+    #[test]
+    fn test_send() {
+        let mut w = Wheel::new(0);
+        w.schedule(timer(5, 0));
+        thread::spawn(move || {
+            assert_eq!(ids(w.advance(5)), [0]);
+        }).join().ok().unwrap();
+    }
+
+    #[test]
+    fn test_cascade() {
+        // 3 levels of 4 slots cover 64 ticks, the rest overflows
+        let mut w = Wheel::new(3);
+        let deadlines = [4, 7, 15, 16, 17, 63, 64, 65, 200, 1000];
+        for (i, &at) in deadlines.iter().enumerate() {
+            w.schedule(timer(at, i as u32));
+        }
+        assert_eq!(w.overflow.len(), 4);
+        assert_eq!(ids(w.advance(16)), [0, 1, 2, 3]);
+        assert_eq!(ids(w.advance(63)), [4, 5]);
+        assert_eq!(ids(w.advance(64)), [6]);
+        assert_eq!(w.overflow.len(), 2);
+        assert_eq!(ids(w.advance(199)), [7]);
+        assert_eq!(ids(w.advance(1 << 20)), [8, 9]);
+        assert!(w.is_empty());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut w = Wheel::new(0);
+        let elts: Vec<*const MyTimer> = [2, 9, 40, 100].iter().enumerate()
+            .map(|(i, &at)| {
+                let t = timer(at, i as u32);
+                let ptr: *const MyTimer = &*t;
+                w.schedule(t);
+                ptr
+            }).collect();
+        assert_eq!(ids(w.advance(8)), [0]);
+        // 40 has cascaded by now, 100 is still on the overflow list
+        let t = unsafe { w.cancel(&*elts[2]) };
+        assert_eq!(t.get_val(), &Deadline(40, 2));
+        let t = unsafe { w.cancel(&*elts[3]) };
+        assert_eq!(t.get_val(), &Deadline(100, 3));
+        assert_eq!(w.len(), 1);
+        assert_eq!(ids(w.advance(1000)), [1]);
+    }
+
+    #[test]
+    fn test_rc() {
+        let elts: Vec<_> = (0..6).map(|i| {
+            Rc::new(MyTimer::new(Deadline(i * 30, i as u32)))
+        }).collect();
+        let mut w: TimerWheel<_, Rc<MyTimer>, _, _, 2, 8> = TimerWheel::new(0);
+        for e in elts.iter() {
+            w.schedule(e.clone());
+        }
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 2));
+        drop(w.advance(60));
+        assert_eq!(Rc::strong_count(&elts[2]), 1);
+        assert_eq!(Rc::strong_count(&elts[3]), 2);
+        w.clear();
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut w = Wheel::new(rng.gen_range(0..100));
+            let mut pending: Vec<(u64, *const MyTimer)> = Vec::new();
+            for id in 0..200 {
+                match rng.gen_range(0..4) {
+                    0 if !pending.is_empty() => {
+                        let i = rng.gen_range(0..pending.len());
+                        let (at, ptr) = pending.swap_remove(i);
+                        let t = unsafe { w.cancel(&*ptr) };
+                        assert_eq!(t.get_val().0, at);
+                    }
+                    1 => {
+                        let now = w.now() + rng.gen_range(0..40);
+                        let expired = w.advance(now);
+                        let mut last = 0;
+                        for d in expired.iter() {
+                            assert!(d.0 <= now && d.0 >= last);
+                            last = d.0;
+                        }
+                        let n = pending.len();
+                        pending.retain(|&(at, _)| at > now);
+                        assert_eq!(expired.len(), n - pending.len());
+                    }
+                    _ => {
+                        let at = w.now() + rng.gen_range(0..150);
+                        let t = timer(at, id);
+                        pending.push((at, &*t));
+                        w.schedule(t);
+                    }
+                }
+                assert_eq!(w.len(), pending.len());
+            }
+        }
+    }
+}
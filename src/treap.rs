@@ -0,0 +1,1259 @@
+// This file is part of Intrusive.
+
+// Intrusive is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Intrusive is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+
+// You should have received a copy of the GNU Lesser General Public License
+// along with Intrusive.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An intrusive implicit-key treap.
+//!
+//! The `Treap` is a sequence, like a `LinkedList`, stored as a binary tree
+//! whose in-order traversal gives the order of the elements. Each link holds
+//! the size of its subtree, which serves as the key, and a random priority,
+//! which keeps the tree balanced in expectation. Looking up, inserting or
+//! removing the element at an index, splitting the sequence in two and
+//! concatenating two sequences all take O(log n) expected time.
+use core::cell::Cell;
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ptr;
+use super::linked_list::OwningPointer;
+use super::rawlink::Rawlink;
+
+///////////////////////
+// Trait Definitions //
+///////////////////////
+
+/// A trait that allows a struct to be inserted into a `Treap`
+///
+/// Rather than implement this directly, it is expected to use the
+/// `define_treap_element` macro.
+///
+/// # Safety
+///
+/// `get_links` and `get_links_mut` must always return the same field, the one
+/// whose offset is given by `L::offset()`.
+pub unsafe trait Node<T, L> : Sized
+    where L: TreapLinkable<Container=Self>
+{
+    /// Getter for underlying value
+    fn get_val(&self) -> &T;
+
+    /// Getter for mutable underlying value
+    fn get_val_mut(&mut self) -> &mut T;
+
+    /// Getter for links
+    fn get_links(&self) -> &L;
+
+    /// Getter for mutable links
+    fn get_links_mut(&mut self) -> &mut L;
+}
+
+/// Link trait allowing a struct to be inserted into a `Treap`
+///
+/// The root of a treap is its own parent, so that an element is in a treap
+/// exactly when its parent link is set.
+///
+/// # Safety
+///
+/// The trait is unsafe because any implementation must impl Drop to call
+/// check_links(), and `offset()` must be the offset of the link within its
+/// `Container`.
+pub unsafe trait TreapLinkable : Default + Sized
+{
+    type Container;
+
+    fn get_links(&self) -> &TreapLinks<Self>;
+    fn get_links_mut(&mut self) -> &mut TreapLinks<Self>;
+    fn offset() -> usize;
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of(&self) -> &Self::Container {
+        let val = self as *const Self;
+        &*(val.byte_sub(Self::offset()) as *const Self::Container)
+    }
+    /// Returns the element containing these links
+    ///
+    /// # Safety
+    ///
+    /// `self` must be embedded in a `Container`.
+    unsafe fn container_of_mut(&mut self) -> &mut Self::Container {
+        let val = self as *mut Self;
+        &mut *(val.byte_sub(Self::offset()) as *mut Self::Container)
+    }
+    fn check_links(&self) {
+        assert!(self.get_parent().resolve().is_none());
+        assert!(self.get_left().resolve().is_none());
+        assert!(self.get_right().resolve().is_none());
+    }
+}
+
+/// Accessors for the links of a `TreapLinkable`
+///
+/// They are private to the crate, so that the links of an element are only
+/// followed or changed by the treap it is in.
+pub(crate) trait TreapLinkOps : TreapLinkable
+{
+    fn get_parent(&self) -> Rawlink<Self> {
+        self.get_links().parent.get()
+    }
+    fn set_parent(&self, parent: Rawlink<Self>) {
+        self.get_links().parent.set(parent)
+    }
+    fn get_left(&self) -> Rawlink<Self> {
+        self.get_links().left.get()
+    }
+    fn set_left(&self, left: Rawlink<Self>) {
+        self.get_links().left.set(left)
+    }
+    fn get_right(&self) -> Rawlink<Self> {
+        self.get_links().right.get()
+    }
+    fn set_right(&self, right: Rawlink<Self>) {
+        self.get_links().right.set(right)
+    }
+    fn get_size(&self) -> usize {
+        self.get_links().size.get()
+    }
+    fn set_size(&self, size: usize) {
+        self.get_links().size.set(size)
+    }
+    fn get_priority(&self) -> u64 {
+        self.get_links().priority.get()
+    }
+    fn set_priority(&self, priority: u64) {
+        self.get_links().priority.set(priority)
+    }
+}
+
+impl<L: TreapLinkable> TreapLinkOps for L {}
+
+/// Chooses the priority of each element inserted into a `Treap`
+///
+/// An element is never below one of lower priority, so priorities must be
+/// drawn independently at random for the treap to stay balanced. Closures
+/// returning a priority implement this trait, which makes it easy to build
+/// deterministic treaps in tests.
+pub trait PriorityGenerator {
+    /// Returns the priority of the next element inserted
+    fn priority(&mut self) -> u64;
+}
+
+///////////////////////
+// Macro Definitions //
+///////////////////////
+
+/// Defines a struct which can be inserted into a `Treap`
+///
+/// `define_treap_element!(MyI32 = i32 : MyLink)` defines an element `MyI32`
+/// holding an `i32` and a link type `MyLink` used to put it in a treap.
+#[macro_export]
+macro_rules! define_treap_element {
+    ($elt:ident = $container:ty : $link:ident) => (
+        declare_treap_link!($link);
+        declare_list_elt!($elt = $container : $link);
+        impl_treap_link!($link = $elt);
+        impl_treap_elt!($elt = $container : $link);
+    );
+    (pub $elt:ident = $container:ty : $link:ident) => (
+        declare_treap_link!(pub $link);
+        declare_list_elt!(pub $elt = $container : $link);
+        impl_treap_link!($link = $elt);
+        impl_treap_elt!($elt = $container : $link);
+    );
+}
+
+#[macro_export]
+macro_rules! declare_treap_link {
+    ($link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        struct $link($crate::treap::TreapLinks<$link>);
+    );
+    (pub $link:ident) => (
+        #[derive(Clone, Default, Debug)]
+        pub struct $link($crate::treap::TreapLinks<$link>);
+    );
+}
+
+/// Implements `TreapLinkable` for `$link`, which is found at the field path
+/// `$field` (`link` by default) of the `NodeImpl` wrapped by `$elt`
+#[macro_export]
+macro_rules! impl_treap_link {
+    ($link:ident = $elt:ident) => (
+        impl_treap_link!($link = $elt : link);
+    );
+    ($link:ident = $elt:ident : $($field:tt)+) => (
+        unsafe impl $crate::treap::TreapLinkable for $link {
+            type Container = $elt;
+
+            #[inline]
+            fn get_links(&self) -> &$crate::treap::TreapLinks<$link> {
+                &self.0
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $crate::treap::TreapLinks<$link> {
+                &mut self.0
+            }
+
+            #[inline]
+            fn offset() -> usize {
+                $crate::linked_list::offset_of!($elt, 0.$($field)+)
+            }
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! impl_treap_elt {
+    ($elt:ident = $container:ty : $link:ident) => (
+        impl $elt {
+            #[inline]
+            fn new(val: $container) -> $elt {
+                $elt($crate::linked_list::NodeImpl {
+                    link: Default::default(),
+                    val
+                })
+            }
+        }
+
+        impl_treap_elt!(@node $elt = $container : $link : link);
+    );
+    (@node $elt:ident = $container:ty : $link:ident : $($field:tt)+) => (
+        unsafe impl $crate::treap::Node<$container, $link> for $elt {
+            #[inline]
+            fn get_val(&self) -> &$container {
+                &self.0.val
+            }
+
+            #[inline]
+            fn get_val_mut(&mut self) -> &mut $container {
+                &mut self.0.val
+            }
+
+            #[inline]
+            fn get_links(&self) -> &$link {
+                &self.0.$($field)+
+            }
+
+            #[inline]
+            fn get_links_mut(&mut self) -> &mut $link {
+                &mut self.0.$($field)+
+            }
+        }
+    );
+}
+
+////////////////////////
+// Struct Definitions //
+////////////////////////
+
+/// An intrusive implicit-key treap
+pub struct Treap<P, T, S, L, G = XorShiftPriorities>
+    where T: OwningPointer<Target=S>,
+          L: TreapLinkable<Container=T::Target>
+{
+    root: Rawlink<L>,
+    generator: G,
+    _marker: PhantomData<P>,
+    _marker2: PhantomData<T>,
+    _marker3: PhantomData<S>
+}
+
+#[derive(Default, Debug)]
+pub struct TreapLinks<L: TreapLinkable>
+{
+    parent: Cell<Rawlink<L>>,
+    left: Cell<Rawlink<L>>,
+    right: Cell<Rawlink<L>>,
+    size: Cell<usize>,
+    priority: Cell<u64>,
+    _pin: PhantomPinned
+}
+
+/// A `PriorityGenerator` drawing priorities from a xorshift pseudo-random
+/// number generator
+#[derive(Clone, Debug)]
+pub struct XorShiftPriorities {
+    state: u64
+}
+
+/// An iterator over references to the items of a `Treap`, in order
+pub struct Iter<'a, P: 'a, T, L: TreapLinkable<Container=T>> {
+    head: Rawlink<L>,
+    tail: Rawlink<L>,
+    nelem: usize,
+    _marker: PhantomData<&'a P>
+}
+
+/// An iterator yielding the elements of a `Treap` by value, in order
+pub struct IntoIter<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          L: TreapLinkable<Container=T::Target>
+{
+    treap: Treap<P, T, S, L, G>
+}
+
+//////////////////////
+// Tree Navigation  //
+//////////////////////
+
+fn parent<L: TreapLinkable>(node: &L) -> Option<&L> {
+    node.get_parent().resolve().filter(|&p| !ptr::eq(p, node))
+}
+
+fn left<L: TreapLinkable>(node: &L) -> Option<&L> {
+    node.get_left().resolve()
+}
+
+fn right<L: TreapLinkable>(node: &L) -> Option<&L> {
+    node.get_right().resolve()
+}
+
+fn same<L>(a: Option<&L>, b: Option<&L>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => ptr::eq(a, b),
+        (None, None) => true,
+        _ => false
+    }
+}
+
+fn minimum<L: TreapLinkable>(mut node: &L) -> &L {
+    while let Some(l) = left(node) {
+        node = l;
+    }
+    node
+}
+
+fn maximum<L: TreapLinkable>(mut node: &L) -> &L {
+    while let Some(r) = right(node) {
+        node = r;
+    }
+    node
+}
+
+fn successor<L: TreapLinkable>(node: &L) -> Option<&L> {
+    if let Some(r) = right(node) {
+        return Some(minimum(r));
+    }
+    let mut node = node;
+    while let Some(p) = parent(node) {
+        if same(left(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+fn predecessor<L: TreapLinkable>(node: &L) -> Option<&L> {
+    if let Some(l) = left(node) {
+        return Some(maximum(l));
+    }
+    let mut node = node;
+    while let Some(p) = parent(node) {
+        if same(right(p), Some(node)) {
+            return Some(p);
+        }
+        node = p;
+    }
+    None
+}
+
+fn link_of<L: TreapLinkable>(node: Option<&L>) -> Rawlink<L> {
+    node.map_or(Rawlink::none(), Rawlink::some)
+}
+
+fn size<L: TreapLinkable>(node: Option<&L>) -> usize {
+    node.map_or(0, L::get_size)
+}
+
+/// Finds the node at `index` in the tree at `root`
+fn node_at<L: TreapLinkable>(root: Option<&L>, mut index: usize) -> Option<&L> {
+    let mut cur = root;
+    while let Some(node) = cur {
+        let here = size(left(node));
+        if index < here {
+            cur = left(node);
+        } else if index == here {
+            return Some(node);
+        } else {
+            index -= here + 1;
+            cur = right(node);
+        }
+    }
+    None
+}
+
+/// Recomputes the sizes on the path from `node` to the root
+fn update_to_root<L: TreapLinkable>(mut node: Option<&L>) {
+    while let Some(n) = node {
+        n.set_size(size(left(n)) + 1 + size(right(n)));
+        node = parent(n);
+    }
+}
+
+/// Links `child` below `parent`, on the right if `on_right`, or makes it a
+/// root if there is no parent
+fn attach<L: TreapLinkable>(parent: Option<(&L, bool)>, child: Option<&L>) {
+    match parent {
+        None => {
+            if let Some(c) = child {
+                c.set_parent(Rawlink::some(c));
+            }
+        }
+        Some((p, on_right)) => {
+            if on_right {
+                p.set_right(link_of(child));
+            } else {
+                p.set_left(link_of(child));
+            }
+            if let Some(c) = child {
+                c.set_parent(Rawlink::some(p));
+            }
+        }
+    }
+}
+
+/// Splits the tree at `root` into the trees of its first `at` elements and
+/// of the rest, returning their roots
+///
+/// The nodes of each side met on the way down are chained along its inner
+/// edge, so no recursion is needed however unbalanced the tree is.
+fn split<L: TreapLinkable>(root: Option<&L>, mut at: usize)
+                           -> (Option<&L>, Option<&L>) {
+    let (mut first, mut rest) = (None, None);
+    // the nodes whose right, respectively left, child is still to be found
+    let mut first_edge: Option<&L> = None;
+    let mut rest_edge: Option<&L> = None;
+    let mut cur = root;
+    while let Some(node) = cur {
+        let here = size(left(node));
+        if at <= here {
+            attach(rest_edge.map(|e| (e, false)), Some(node));
+            rest = rest.or(Some(node));
+            rest_edge = Some(node);
+            cur = left(node);
+        } else {
+            attach(first_edge.map(|e| (e, true)), Some(node));
+            first = first.or(Some(node));
+            first_edge = Some(node);
+            at -= here + 1;
+            cur = right(node);
+        }
+    }
+    if let Some(e) = first_edge {
+        e.set_right(Rawlink::none());
+    }
+    if let Some(e) = rest_edge {
+        e.set_left(Rawlink::none());
+    }
+    update_to_root(first_edge);
+    update_to_root(rest_edge);
+    (first, rest)
+}
+
+/// Joins the trees at `first` and `rest`, which come before and after each
+/// other in that order, returning the root of the result
+fn merge<'a, L: TreapLinkable>(mut first: Option<&'a L>,
+                               mut rest: Option<&'a L>) -> Option<&'a L> {
+    let mut root = None;
+    let mut edge: Option<(&L, bool)> = None;
+    loop {
+        let node = match (first, rest) {
+            (Some(f), Some(r)) => {
+                if f.get_priority() >= r.get_priority() {
+                    first = right(f);
+                    (f, true)
+                } else {
+                    rest = left(r);
+                    (r, false)
+                }
+            }
+            (f, r) => {
+                // one side is used up, so the other hangs whole below the edge
+                let last = f.or(r);
+                attach(edge, last);
+                update_to_root(last.or(edge.map(|e| e.0)));
+                return root.or(last);
+            }
+        };
+        attach(edge, Some(node.0));
+        root = root.or(Some(node.0));
+        edge = Some(node);
+    }
+}
+
+/////////////////////////
+// Struct Implementations
+/////////////////////////
+
+// Treap impls
+
+impl<P, T, S, L> Treap<P, T, S, L>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>
+{
+    /// Creates an empty `Treap` drawing priorities from a default
+    /// `XorShiftPriorities`
+    #[inline]
+    pub fn new() -> Treap<P, T, S, L> {
+        Treap::with_generator(XorShiftPriorities::default())
+    }
+}
+
+impl<P, T, S, L, G> Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>
+{
+    /// Creates an empty `Treap` drawing priorities from `generator`
+    #[inline]
+    pub fn with_generator(generator: G) -> Treap<P, T, S, L, G> {
+        Treap { root: Rawlink::none(), generator,
+                _marker: PhantomData, _marker2: PhantomData,
+                _marker3: PhantomData}
+    }
+
+    /// Provides a double-ended iterator over the elements in order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, P, S, L> {
+        let root = self.root.resolve();
+        Iter{nelem: self.len(),
+             head: link_of(root.map(minimum)),
+             tail: link_of(root.map(maximum)),
+             _marker: PhantomData}
+    }
+
+    /// Returns `true` if the `Treap` is empty
+    ///
+    /// This operation should compute in O(1) time
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.root.resolve().is_none()
+    }
+
+    /// Returns the number of elements in the `Treap`.
+    ///
+    /// This operation should compute in O(1) time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        size(self.root.resolve())
+    }
+
+    /// Removes all elements from the `Treap`.
+    ///
+    /// This operation should compute in O(n) time.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.unlink_all();
+    }
+
+    /// Provides a reference to the first element, or `None` if the treap is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    #[inline]
+    pub fn front(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { minimum(root).container_of() }.get_val()
+        })
+    }
+
+    /// Provides a reference to the last element, or `None` if the treap is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    #[inline]
+    pub fn back(&self) -> Option<&P> {
+        self.root.resolve().map(|root| {
+            unsafe { maximum(root).container_of() }.get_val()
+        })
+    }
+
+    /// Provides a reference to the element at `index`, or `None` if the index
+    /// is out of bounds.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    pub fn get(&self, index: usize) -> Option<&P> {
+        node_at(self.root.resolve(), index).map(|node| unsafe { node.container_of() }.get_val())
+    }
+
+    /// Returns the index of `elt` in the treap.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any treap.
+    pub fn index_of(&self, elt: &S) -> usize {
+        let mut node = elt.get_links();
+        assert!(node.get_parent().resolve().is_some(),
+                "Cannot find the index of an element which is not in a treap");
+        let mut index = size(left(node));
+        while let Some(p) = parent(node) {
+            if same(right(p), Some(node)) {
+                index += size(left(p)) + 1;
+            }
+            node = p;
+        }
+        debug_assert!(same(self.root.resolve(), Some(node)),
+                      "Cannot find the index of an element of another treap");
+        index
+    }
+
+    /// Removes the first element and returns it, or `None` if the treap is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| unsafe { self.unlink(minimum(root)) })
+    }
+
+    /// Removes the last element and returns it, or `None` if the treap is
+    /// empty.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.root.resolve().map(|root| unsafe { self.unlink(maximum(root)) })
+    }
+
+    /// Removes the element at `index` and returns it, or `None` if the index
+    /// is out of bounds.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    pub fn remove_at(&mut self, index: usize) -> Option<T> {
+        node_at(self.root.resolve(), index).map(|node| unsafe { self.unlink(node) })
+    }
+
+    /// Unlinks `elt` from the treap and returns ownership of it.
+    ///
+    /// This operation should compute in O(log n) expected time. In debug
+    /// builds the parent links are followed to check that `elt` belongs to
+    /// this treap.
+    ///
+    /// # Safety
+    ///
+    /// This operation is marked unsafe because `elt` must currently be an
+    /// element of this treap. Removing an element of another treap would
+    /// leave that treap with an incorrect length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `elt` is not in any treap.
+    pub unsafe fn remove(&mut self, elt: &S) -> T {
+        let links = elt.get_links();
+        assert!(links.get_parent().resolve().is_some(),
+                "Cannot remove an element which is not in a treap");
+        debug_assert!(self.contains_links(links),
+                      "Cannot remove an element of another treap");
+        self.unlink(links)
+    }
+
+    /// Moves all elements from `other` to the end of the treap, leaving
+    /// `other` empty.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    pub fn merge<H>(&mut self, mut other: Treap<P, T, S, L, H>) {
+        let root = merge(self.root.resolve(), other.root.take().resolve());
+        self.root = link_of(root);
+    }
+
+    /// Splits the treap into two at the given index. Returns everything after
+    /// the given index, including the index, in a treap drawing priorities
+    /// from a clone of the generator of this one.
+    ///
+    /// Unlike `LinkedList::split_off`, this operation should compute in
+    /// O(log n) expected time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate intrusive_containers;
+    /// use intrusive_containers::Treap;
+    ///
+    /// define_treap_element!(MyChar = char : MyLink);
+    ///
+    /// # #[cfg(feature = "alloc")]
+    /// # fn main() {
+    /// let mut t: Treap<_, _, _, MyLink> =
+    ///     "hello world".chars().map(|c| Box::new(MyChar::new(c))).collect();
+    /// let mut world = t.split_at(6);
+    /// world.insert_at(0, Box::new(MyChar::new('W')));
+    /// world.remove_at(1);
+    /// t.merge(world);
+    /// assert_eq!(t.iter().collect::<String>(), "hello World");
+    /// assert_eq!(t.get(6), Some(&'W'));
+    /// # }
+    /// # #[cfg(not(feature = "alloc"))] fn main() {}
+    /// ```
+    pub fn split_at(&mut self, at: usize) -> Treap<P, T, S, L, G>
+        where G: Clone
+    {
+        assert!(at <= self.len(), "Cannot split off at a nonexistent index");
+        let (first, rest) = split(self.root.resolve(), at);
+        self.root = link_of(first);
+        let mut ret = Treap::with_generator(self.generator.clone());
+        ret.root = link_of(rest);
+        ret
+    }
+
+    /// Climbs to the root from `links` to check it is in this treap
+    fn contains_links(&self, links: &L) -> bool {
+        let mut node = links;
+        while let Some(p) = parent(node) {
+            node = p;
+        }
+        same(self.root.resolve(), Some(node))
+    }
+
+    /// Replaces `node` with the merge of its children and returns ownership
+    /// of it
+    ///
+    /// # Safety
+    ///
+    /// `node` must be in this treap.
+    unsafe fn unlink(&mut self, node: &L) -> T {
+        let up = parent(node);
+        let on_right = up.is_some_and(|p| same(right(p), Some(node)));
+        let children = merge(left(node), right(node));
+        attach(up.map(|p| (p, on_right)), children);
+        if up.is_none() {
+            self.root = link_of(children);
+        }
+        update_to_root(up);
+        node.set_parent(Rawlink::none());
+        node.set_left(Rawlink::none());
+        node.set_right(Rawlink::none());
+        T::from_raw(node.container_of() as *const S as *mut S)
+    }
+}
+
+impl<P, T, S, L, G> Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>,
+          G: PriorityGenerator
+{
+    /// Inserts `elt` at `index`, shifting the elements after it.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_at(&mut self, index: usize, elt: T) {
+        assert!(index <= self.len(), "Cannot insert at a nonexistent index");
+        // ensure links are not already being used
+        elt.get_links().check_links();
+
+        let links = elt.get_links();
+        links.set_size(1);
+        links.set_priority(self.generator.priority());
+        let (first, rest) = split(self.root.resolve(), index);
+        let root = merge(merge(first, Some(links)), rest);
+        self.root = link_of(root);
+        unsafe { elt.take() };
+    }
+
+    /// Adds an element first in the treap.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    #[inline]
+    pub fn push_front(&mut self, elt: T) {
+        self.insert_at(0, elt);
+    }
+
+    /// Appends an element to the back of the treap.
+    ///
+    /// This operation should compute in O(log n) expected time.
+    #[inline]
+    pub fn push_back(&mut self, elt: T) {
+        let len = self.len();
+        self.insert_at(len, elt);
+    }
+}
+
+impl<P, T, S, L, G> Default for Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>,
+          G: Default
+{
+    #[inline]
+    fn default() -> Treap<P, T, S, L, G> {
+        Treap::with_generator(G::default())
+    }
+}
+
+impl<P, T, S, L, G> fmt::Debug for Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          P: fmt::Debug,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+
+        for (i, e) in self.iter().enumerate() {
+            if i != 0 { write!(f, ", ")?; }
+            write!(f, "{:?}", e)?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+impl<P, T, S, L, G> Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          L: TreapLinkable<Container=T::Target>
+{
+    /// Unlinks the leaves one at a time, handing back every element
+    fn unlink_all(&mut self) {
+        let mut cur = self.root.take().resolve();
+        while let Some(node) = cur {
+            if let Some(l) = left(node) {
+                cur = Some(l);
+                continue;
+            }
+            if let Some(r) = right(node) {
+                cur = Some(r);
+                continue;
+            }
+            cur = parent(node);
+            if let Some(p) = cur {
+                if same(left(p), Some(node)) {
+                    p.set_left(Rawlink::none());
+                } else {
+                    p.set_right(Rawlink::none());
+                }
+            }
+            node.set_parent(Rawlink::none());
+            // hand back the reference held by the treap
+            drop(unsafe {
+                T::from_raw(node.container_of() as *const S as *mut S)
+            });
+        }
+    }
+}
+
+impl<P, T, S, L, G> Drop for Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          L: TreapLinkable<Container=T::Target>
+{
+    fn drop(&mut self) {
+        self.unlink_all();
+    }
+}
+
+impl<P, T, S, L, G> Extend<T> for Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>,
+          G: PriorityGenerator
+{
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for elt in iter { self.push_back(elt); }
+    }
+}
+
+impl<P, T, S, L, G> FromIterator<T> for Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>,
+          G: PriorityGenerator + Default
+{
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> Treap<P, T, S, L, G> {
+        let mut ret = Treap::default();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<P, T, S, L, G> IntoIterator for Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>
+{
+    type Item = T;
+    type IntoIter = IntoIter<P, T, S, L, G>;
+
+    /// Consumes the treap into an iterator yielding elements by value, in
+    /// order.
+    #[inline]
+    fn into_iter(self) -> IntoIter<P, T, S, L, G> {
+        IntoIter{treap: self}
+    }
+}
+
+impl<'a, P, T, S, L, G> IntoIterator for &'a Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          P: 'a,
+          S: Node<P, L> + 'a,
+          L: TreapLinkable<Container=T::Target> + 'a
+{
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P, S, L>;
+
+    fn into_iter(self) -> Iter<'a, P, S, L> {
+        self.iter()
+    }
+}
+
+unsafe impl<P, T, S, L, G> Send for Treap<P, T, S, L, G>
+    where T: OwningPointer<Target=S> + Send,
+          L: TreapLinkable<Container=T::Target>,
+          G: Send
+{}
+
+// TreapLinks impls
+
+unsafe impl<L: TreapLinkable> Send for TreapLinks<L> {}
+
+impl<L: TreapLinkable> Clone for TreapLinks<L>
+{
+    /// A clone is not in any treap, even if the original is
+    fn clone(&self) -> TreapLinks<L> {
+        Default::default()
+    }
+}
+
+impl<L: TreapLinkable> Drop for TreapLinks<L>
+{
+    fn drop(&mut self) {
+        assert!(self.parent.get().resolve().is_none());
+    }
+}
+
+// XorShiftPriorities impls
+
+impl XorShiftPriorities {
+    /// Creates a generator from `seed`. A zero seed is replaced by a fixed
+    /// non-zero one.
+    pub fn new(seed: u64) -> XorShiftPriorities {
+        XorShiftPriorities {
+            state: if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed }
+        }
+    }
+}
+
+impl Default for XorShiftPriorities {
+    fn default() -> XorShiftPriorities {
+        XorShiftPriorities::new(0)
+    }
+}
+
+impl PriorityGenerator for XorShiftPriorities {
+    fn priority(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+}
+
+impl<F: FnMut() -> u64> PriorityGenerator for F {
+    #[inline]
+    fn priority(&mut self) -> u64 {
+        self()
+    }
+}
+
+// Iter impls
+
+impl<'a, P, T, L: TreapLinkable<Container=T>> Clone for Iter<'a, P, T, L> {
+    fn clone(&self) -> Iter<'a, P, T, L> {
+        Iter {
+            head: self.head,
+            tail: self.tail,
+            nelem: self.nelem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: TreapLinkable<Container=T> + 'a>
+    Iterator for Iter<'a, P, T, L>
+{
+    type Item = &'a P;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let head = self.head.resolve().unwrap();
+        self.nelem -= 1;
+        self.head = link_of(successor(head));
+        let ret = unsafe { head.container_of() }.get_val();
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.nelem, Some(self.nelem))
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: TreapLinkable<Container=T> + 'a>
+    DoubleEndedIterator for Iter<'a, P, T, L>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a P> {
+        if self.nelem == 0 {
+            return None;
+        }
+        let tail = self.tail.resolve().unwrap();
+        self.nelem -= 1;
+        self.tail = link_of(predecessor(tail));
+        let ret = unsafe { tail.container_of() }.get_val();
+        Some(ret)
+    }
+}
+
+impl<'a, P: 'a, T: Node<P, L> + 'a, L: TreapLinkable<Container=T> + 'a>
+    ExactSizeIterator for Iter<'a, P, T, L> {}
+
+// IntoIter impls
+
+impl<P, T, S, L, G> Iterator for IntoIter<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> { self.treap.pop_front() }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.treap.len(), Some(self.treap.len()))
+    }
+}
+
+impl<P, T, S, L, G> DoubleEndedIterator for IntoIter<P, T, S, L, G>
+    where T: OwningPointer<Target=S>,
+          S: Node<P, L>,
+          L: TreapLinkable<Container=T::Target>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<T> { self.treap.pop_back() }
+}
+
+///////////
+// Tests //
+///////////
+
+#[cfg(all(test, feature="alloc"))]
+mod tests {
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::vec::Vec;
+    use core::ptr;
+    use rand::{self, Rng};
+    use super::{Treap, OwningPointer, Node, TreapLinkable, TreapLinkOps, PriorityGenerator};
+    use super::{left, right, parent};
+
+    define_treap_element!(MyI32 = i32 : MyLink);
+
+    type Tree = Treap<i32, Box<MyI32>, MyI32, MyLink>;
+
+    /// Checks the parent links, sizes and heap order of the subtree at `node`,
+    /// returning its size
+    fn check_node<L: TreapLinkable>(node: &L) -> usize {
+        let mut size = 1;
+        for child in [left(node), right(node)].into_iter().flatten() {
+            assert!(ptr::eq(parent(child).unwrap(), node));
+            assert!(child.get_priority() <= node.get_priority());
+            size += check_node(child);
+        }
+        assert_eq!(node.get_size(), size);
+        size
+    }
+
+    pub fn check_links<P, T, S, L, G>(treap: &Treap<P, T, S, L, G>)
+        where T: OwningPointer<Target=S>,
+              S: Node<P, L>,
+              L: TreapLinkable<Container=S>,
+    {
+        if let Some(root) = treap.root.resolve() {
+            assert!(ptr::eq(root.get_parent().resolve().unwrap(), root));
+            check_node(root);
+        }
+    }
+
+    fn treap_from(v: &[i32]) -> Tree {
+        let treap: Tree = v.iter().map(|&i| Box::new(MyI32::new(i))).collect();
+        check_links(&treap);
+        treap
+    }
+
+    fn to_vec<G>(treap: &Treap<i32, Box<MyI32>, MyI32, MyLink, G>) -> Vec<i32> {
+        treap.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut t = Tree::new();
+        assert!(t.is_empty());
+        assert_eq!(t.front(), None);
+        assert_eq!(t.pop_front(), None);
+        t.push_back(Box::new(MyI32::new(2)));
+        t.push_front(Box::new(MyI32::new(1)));
+        t.push_back(Box::new(MyI32::new(3)));
+        check_links(&t);
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.front(), Some(&1));
+        assert_eq!(t.back(), Some(&3));
+        assert_eq!(t.iter().rev().cloned().collect::<Vec<_>>(), [3, 2, 1]);
+        assert_eq!(t.pop_front(), Some(Box::new(MyI32::new(1))));
+        assert_eq!(t.pop_back(), Some(Box::new(MyI32::new(3))));
+        check_links(&t);
+        assert_eq!(to_vec(&t), [2]);
+        t.clear();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_index() {
+        let mut t = treap_from(&[10, 20, 30, 40, 50]);
+        assert_eq!(t.get(0), Some(&10));
+        assert_eq!(t.get(4), Some(&50));
+        assert_eq!(t.get(5), None);
+        t.insert_at(2, Box::new(MyI32::new(25)));
+        t.insert_at(6, Box::new(MyI32::new(60)));
+        check_links(&t);
+        assert_eq!(to_vec(&t), [10, 20, 25, 30, 40, 50, 60]);
+        assert_eq!(t.remove_at(1), Some(Box::new(MyI32::new(20))));
+        assert_eq!(t.remove_at(6), None);
+        check_links(&t);
+        assert_eq!(to_vec(&t), [10, 25, 30, 40, 50, 60]);
+    }
+
+    #[test]
+    fn test_split_merge() {
+        let elts: Vec<_> = (0..10).map(|i| Rc::new(MyI32::new(i))).collect();
+        let mut t: Treap<_, _, _, MyLink> = elts.iter().cloned().collect();
+        assert_eq!(t.index_of(&elts[7]), 7);
+        let mut tail = t.split_at(4);
+        check_links(&t);
+        check_links(&tail);
+        assert_eq!(t.len(), 4);
+        assert_eq!(tail.iter().cloned().collect::<Vec<_>>(), [4, 5, 6, 7, 8, 9]);
+        assert_eq!(tail.index_of(&elts[7]), 3);
+        assert!(t.split_at(4).is_empty());
+        let all = t.split_at(0);
+        assert!(t.is_empty());
+        tail.merge(all);
+        check_links(&tail);
+        assert_eq!(tail.iter().cloned().collect::<Vec<_>>(),
+                   [4, 5, 6, 7, 8, 9, 0, 1, 2, 3]);
+        drop(tail);
+        assert!(elts.iter().all(|e| Rc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_degenerate_priorities() {
+        // a constant priority makes every split and merge walk a spine as
+        // long as the treap
+        let mut t = Treap::with_generator(|| 0);
+        for i in 0..2_000 {
+            t.push_back(Box::new(MyI32::new(i)));
+        }
+        let tail = t.split_at(1_000);
+        assert_eq!(tail.get(0), Some(&1_000));
+        t.merge(tail);
+        assert_eq!(t.len(), 2_000);
+        assert_eq!(t.get(1_999), Some(&1_999));
+        assert!(to_vec(&t).iter().enumerate().all(|(i, &v)| i as i32 == v));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut elts: Vec<*const MyI32> = Vec::new();
+        let mut t = Tree::new();
+        for i in 0..32 {
+            let e = Box::new(MyI32::new(i));
+            elts.push(&*e);
+            t.push_back(e);
+        }
+        for i in (0..32).step_by(3) {
+            let e = unsafe { t.remove(&*elts[i]) };
+            assert_eq!(*e.get_val(), i as i32);
+            check_links(&t);
+        }
+        assert_eq!(t.len(), 21);
+        assert!(t.iter().all(|&v| v % 3 != 0));
+    }
+
+    #[test]
+    fn test_fuzz() {
+        #[derive(Clone)]
+        struct Random(rand::rngs::ThreadRng);
+
+        impl PriorityGenerator for Random {
+            fn priority(&mut self) -> u64 {
+                self.0.gen_range(0..16)
+            }
+        }
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..25 {
+            let mut t = Treap::with_generator(Random(rand::thread_rng()));
+            let mut v: Vec<i32> = Vec::new();
+            for i in 0..200 {
+                match rng.gen_range(0..4) {
+                    0 if !v.is_empty() => {
+                        let at = rng.gen_range(0..v.len());
+                        let e: Box<MyI32> = t.remove_at(at).unwrap();
+                        assert_eq!(*e.get_val(), v.remove(at));
+                    }
+                    1 => {
+                        let at = rng.gen_range(0..=v.len());
+                        let a = rng.gen_range(0..=v.len() - at);
+                        let mut mid = t.split_at(at);
+                        let tail = mid.split_at(a);
+                        check_links(&mid);
+                        // move the middle run to the front
+                        let head = core::mem::replace(&mut t, mid);
+                        t.merge(head);
+                        t.merge(tail);
+                        let moved: Vec<i32> = v.drain(at..at + a).collect();
+                        v.splice(0..0, moved);
+                    }
+                    _ => {
+                        let at = rng.gen_range(0..=v.len());
+                        t.insert_at(at, Box::new(MyI32::new(i)));
+                        v.insert(at, i);
+                    }
+                }
+                check_links(&t);
+                assert_eq!(t.len(), v.len());
+            }
+            assert_eq!(to_vec(&t), v);
+            for (i, x) in v.iter().enumerate() {
+                assert_eq!(t.get(i), Some(x));
+            }
+        }
+    }
+}